@@ -0,0 +1,151 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+/// Example integration: a contract that escrows funds in a `LockUnlockSmartContract`
+/// through the `LockUnlock` trait alone.
+///
+/// The relay is the locker of every lock it creates, so it is also the account that
+/// redeems them. It forwards whatever the escrow pays out to its own caller, so no funds
+/// are left in the relay.
+#[ink::contract]
+mod lock_relay {
+    use ink::codegen::TraitCallBuilder;
+    use ink::prelude::vec::Vec;
+    use lock_unlock_smart_contract::{Error, LockReceipt, LockUnlock};
+
+    #[ink(storage)]
+    pub struct LockRelay {
+        /// The escrow every call is forwarded to.
+        escrow: ink::contract_ref!(LockUnlock),
+    }
+
+    impl LockRelay {
+        /// Creates a relay for the escrow deployed at `escrow`.
+        #[ink(constructor)]
+        pub fn new(escrow: AccountId) -> Self {
+            Self {
+                escrow: escrow.into(),
+            }
+        }
+
+        /// Locks the transferred value in the escrow behind `hash`, with this contract as
+        /// the locker.
+        #[ink(message, payable)]
+        pub fn lock(&mut self, hash: [u8; 32]) -> Result<LockReceipt, Error> {
+            self.escrow
+                .call_mut()
+                .lock(hash, None, None, None, None)
+                .transferred_value(self.env().transferred_value())
+                .invoke()
+        }
+
+        /// Redeems all of lock `lock_id` and forwards the payout to the caller.
+        ///
+        /// As with the escrow itself, whoever knows the preimage is entitled to the funds.
+        /// Fails with `TransferFailed` if the payout cannot be forwarded, reverting the
+        /// redemption.
+        #[ink(message)]
+        pub fn redeem(&mut self, lock_id: u64, preimage: Vec<u8>) -> Result<(), Error> {
+            let before = self.env().balance();
+            self.escrow.redeem(lock_id, preimage, None)?;
+            let payout = self.env().balance().saturating_sub(before);
+            self.env()
+                .transfer(self.env().caller(), payout)
+                .map_err(|_| Error::TransferFailed)
+        }
+
+        /// Returns the locker of `lock_id` as reported by the escrow.
+        #[ink(message)]
+        pub fn locker(&self, lock_id: u64) -> Option<AccountId> {
+            self.escrow.get_locker(lock_id)
+        }
+
+        /// Returns the amount the escrow holds for `lock_id`.
+        #[ink(message)]
+        pub fn locked_amount(&self, lock_id: u64) -> Balance {
+            self.escrow.get_locked_amount(lock_id)
+        }
+    }
+
+    //----------------------------------
+    // End-to-End Tests
+    //----------------------------------
+
+    /// Deploys the escrow next to the relay; run with `cargo test --features e2e-tests` from
+    /// this directory.
+    #[cfg(all(test, feature = "e2e-tests"))]
+    mod e2e_tests {
+        use super::*;
+        use ink_e2e::{ChainBackend, ContractsBackend};
+        use lock_unlock_smart_contract::LockUnlockSmartContractRef;
+
+        type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+        const SECRET: &[u8] = b"correct horse battery staple";
+        const LOCKED: Balance = 1_000_000_000;
+
+        #[ink_e2e::test]
+        async fn e2e_relay_locks_and_redeems_through_trait<Client: E2EBackend>(
+            mut client: Client,
+        ) -> E2EResult<()> {
+            let mut constructor = LockUnlockSmartContractRef::new(1, [0; 32], 12, b"UNIT".to_vec());
+            let escrow = client
+                .instantiate(
+                    "lock_unlock_smart_contract",
+                    &ink_e2e::alice(),
+                    &mut constructor,
+                )
+                .submit()
+                .await
+                .expect("escrow instantiate failed")
+                .account_id;
+            let mut constructor = LockRelayRef::new(escrow);
+            let relay = client
+                .instantiate("lock_relay", &ink_e2e::alice(), &mut constructor)
+                .submit()
+                .await
+                .expect("relay instantiate failed");
+            let mut call_builder = relay.call_builder::<LockRelay>();
+            let relay = relay.account_id;
+
+            // Lock through the relay: the escrow records the relay as locker
+            let mut hash = [0; 32];
+            ink::env::hash_bytes::<ink::env::hash::Blake2x256>(SECRET, &mut hash);
+            let lock = call_builder.lock(hash);
+            let receipt = client
+                .call(&ink_e2e::alice(), &lock)
+                .value(LOCKED)
+                .submit()
+                .await
+                .expect("lock failed")
+                .return_value()
+                .expect("lock rejected");
+            assert_eq!(receipt.amount, LOCKED);
+            let locker = call_builder.locker(receipt.lock_id);
+            let locker = client.call(&ink_e2e::alice(), &locker).dry_run().await?;
+            assert_eq!(locker.return_value(), Some(relay));
+
+            // Redeem through the relay: the funds pass through the relay to Bob
+            let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+            let relay_before = client.free_balance(relay).await?;
+            let bob_before = client.free_balance(bob).await?;
+            let redeem = call_builder.redeem(receipt.lock_id, SECRET.to_vec());
+            client
+                .call(&ink_e2e::bob(), &redeem)
+                .submit()
+                .await
+                .expect("redeem failed")
+                .return_value()
+                .expect("redeem rejected");
+            assert_eq!(client.free_balance(relay).await?, relay_before);
+            let bob_after = client.free_balance(bob).await?;
+            // Bob paid for the transaction out of the forwarded funds
+            assert!(bob_after > bob_before);
+            assert!(bob_after <= bob_before + LOCKED);
+            let amount = call_builder.locked_amount(receipt.lock_id);
+            let amount = client.call(&ink_e2e::alice(), &amount).dry_run().await?;
+            assert_eq!(amount.return_value(), 0);
+
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,276 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+pub use self::psp22_mock::{PSP22Metadata, Psp22Mock, Psp22MockRef, Reentry, CALL_FAILED, PSP22};
+
+/// PSP22 token used by the end-to-end tests of `LockUnlockSmartContract`.
+///
+/// Besides the standard balance and allowance messages, it can be told to call back into
+/// the escrow from `transfer`, the way a malicious token would, and to report display
+/// metadata or trap like a token without the metadata extension.
+#[ink::contract]
+pub mod psp22_mock {
+    use ink::env::call::{build_call, ExecutionInput, Selector};
+    use ink::env::CallFlags;
+    use ink::prelude::{string::String, vec::Vec};
+    use ink::storage::Mapping;
+
+    /// Selector of `redeem` on the escrow, pinned by its `LockUnlock` trait.
+    const REDEEM_SELECTOR: [u8; 4] = [0xEC, 0x3E, 0x92, 0x90];
+
+    /// Returned by `redeem_from` when the call into the escrow itself failed.
+    pub const CALL_FAILED: u8 = u8::MAX;
+
+    /// Errors of the PSP22 standard.
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum PSP22Error {
+        Custom(String),
+        InsufficientBalance,
+        InsufficientAllowance,
+        ZeroRecipientAddress,
+        ZeroSenderAddress,
+        SafeTransferCheckFailed(String),
+    }
+
+    /// A redemption `transfer` attempts on the escrow before moving any tokens.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Reentry {
+        /// The escrow called back into.
+        pub escrow: AccountId,
+        /// The lock to redeem.
+        pub lock_id: u64,
+        /// The preimage to redeem it with.
+        pub preimage: Vec<u8>,
+    }
+
+    /// The PSP22 messages, with the standard's trait and message names so their selectors
+    /// match any other implementation.
+    #[ink::trait_definition]
+    pub trait PSP22 {
+        /// Returns the number of tokens in existence.
+        #[ink(message)]
+        fn total_supply(&self) -> Balance;
+
+        /// Returns the number of tokens held by `owner`.
+        #[ink(message)]
+        fn balance_of(&self, owner: AccountId) -> Balance;
+
+        /// Returns the number of tokens `spender` may still move for `owner`.
+        #[ink(message)]
+        fn allowance(&self, owner: AccountId, spender: AccountId) -> Balance;
+
+        /// Transfers `value` tokens from the caller to `to`.
+        #[ink(message)]
+        fn transfer(
+            &mut self,
+            to: AccountId,
+            value: Balance,
+            data: Vec<u8>,
+        ) -> Result<(), PSP22Error>;
+
+        /// Transfers `value` tokens from `from` to `to` using the caller's allowance.
+        #[ink(message)]
+        fn transfer_from(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+            data: Vec<u8>,
+        ) -> Result<(), PSP22Error>;
+
+        /// Lets `spender` move up to `value` of the caller's tokens.
+        #[ink(message)]
+        fn approve(&mut self, spender: AccountId, value: Balance) -> Result<(), PSP22Error>;
+    }
+
+    /// The display part of the PSP22 Metadata extension.
+    #[ink::trait_definition]
+    pub trait PSP22Metadata {
+        /// Returns the ticker symbol of the token, if it has one.
+        #[ink(message)]
+        fn token_symbol(&self) -> Option<String>;
+
+        /// Returns the number of decimals used to display amounts of the token.
+        #[ink(message)]
+        fn token_decimals(&self) -> u8;
+    }
+
+    #[ink(storage)]
+    pub struct Psp22Mock {
+        /// Number of tokens in existence.
+        supply: Balance,
+        /// Token balance of each account.
+        balances: Mapping<AccountId, Balance>,
+        /// Remaining allowance, by owner and spender.
+        allowances: Mapping<(AccountId, AccountId), Balance>,
+        /// Redemption the next `transfer` attempts, if any.
+        reentry: Option<Reentry>,
+        /// Decimals and symbol reported by the metadata messages; they trap while unset.
+        metadata: Option<(u8, String)>,
+    }
+
+    impl Psp22Mock {
+        /// Mints `supply` tokens to the caller.
+        #[ink(constructor)]
+        pub fn new(supply: Balance) -> Self {
+            let mut balances = Mapping::default();
+            balances.insert(Self::env().caller(), &supply);
+            Self {
+                supply,
+                balances,
+                allowances: Mapping::default(),
+                reentry: None,
+                metadata: None,
+            }
+        }
+
+        /// Makes the next `transfer` attempt `reentry` first, or stops doing so if it is
+        /// `None`.
+        #[ink(message)]
+        pub fn set_reentry(&mut self, reentry: Option<Reentry>) {
+            self.reentry = reentry;
+        }
+
+        /// Makes the metadata messages report `metadata`, or trap if it is `None`.
+        #[ink(message)]
+        pub fn set_metadata(&mut self, metadata: Option<(u8, String)>) {
+            self.metadata = metadata;
+        }
+
+        /// Redeems lock `lock_id` on `escrow` with this contract as the caller.
+        ///
+        /// The escrow may call back into this contract to pay it. Returns the index of the
+        /// escrow's error, or `CALL_FAILED`.
+        #[ink(message)]
+        pub fn redeem_from(
+            &mut self,
+            escrow: AccountId,
+            lock_id: u64,
+            preimage: Vec<u8>,
+        ) -> Result<(), u8> {
+            Self::redeem(escrow, lock_id, preimage, CallFlags::ALLOW_REENTRY)
+        }
+
+        /// Calls `redeem` on `escrow` with `flags`.
+        fn redeem(
+            escrow: AccountId,
+            lock_id: u64,
+            preimage: Vec<u8>,
+            flags: CallFlags,
+        ) -> Result<(), u8> {
+            let result = build_call::<Environment>()
+                .call(escrow)
+                .call_flags(flags)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(REDEEM_SELECTOR))
+                        .push_arg(lock_id)
+                        .push_arg(preimage)
+                        .push_arg(None::<Balance>),
+                )
+                .returns::<Result<(), u8>>()
+                .try_invoke();
+            match result {
+                Ok(Ok(result)) => result,
+                _ => Err(CALL_FAILED),
+            }
+        }
+
+        /// Moves `value` tokens from `from` to `to`.
+        fn move_tokens(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+        ) -> Result<(), PSP22Error> {
+            let from_balance = self.balance_of(from);
+            if from_balance < value {
+                return Err(PSP22Error::InsufficientBalance);
+            }
+            self.balances.insert(from, &(from_balance - value));
+            let to_balance = self.balance_of(to);
+            self.balances.insert(to, &(to_balance + value));
+            Ok(())
+        }
+    }
+
+    impl PSP22 for Psp22Mock {
+        #[ink(message)]
+        fn total_supply(&self) -> Balance {
+            self.supply
+        }
+
+        #[ink(message)]
+        fn balance_of(&self, owner: AccountId) -> Balance {
+            self.balances.get(owner).unwrap_or_default()
+        }
+
+        #[ink(message)]
+        fn allowance(&self, owner: AccountId, spender: AccountId) -> Balance {
+            self.allowances.get((owner, spender)).unwrap_or_default()
+        }
+
+        #[ink(message)]
+        fn transfer(
+            &mut self,
+            to: AccountId,
+            value: Balance,
+            _data: Vec<u8>,
+        ) -> Result<(), PSP22Error> {
+            if let Some(reentry) = self.reentry.take() {
+                // If the runtime refuses the call back into the escrow, this contract traps
+                // here and the transfer fails
+                let _ = Self::redeem(
+                    reentry.escrow,
+                    reentry.lock_id,
+                    reentry.preimage,
+                    CallFlags::empty(),
+                );
+            }
+            self.move_tokens(self.env().caller(), to, value)
+        }
+
+        #[ink(message)]
+        fn transfer_from(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+            _data: Vec<u8>,
+        ) -> Result<(), PSP22Error> {
+            let spender = self.env().caller();
+            let allowance = self.allowance(from, spender);
+            if allowance < value {
+                return Err(PSP22Error::InsufficientAllowance);
+            }
+            self.move_tokens(from, to, value)?;
+            self.allowances
+                .insert((from, spender), &(allowance - value));
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn approve(&mut self, spender: AccountId, value: Balance) -> Result<(), PSP22Error> {
+            self.allowances
+                .insert((self.env().caller(), spender), &value);
+            Ok(())
+        }
+    }
+
+    impl PSP22Metadata for Psp22Mock {
+        #[ink(message)]
+        fn token_symbol(&self) -> Option<String> {
+            let (_, symbol) = self.metadata.clone().expect("no metadata set");
+            Some(symbol)
+        }
+
+        #[ink(message)]
+        fn token_decimals(&self) -> u8 {
+            let (decimals, _) = self.metadata.clone().expect("no metadata set");
+            decimals
+        }
+    }
+}
@@ -1,29 +1,809 @@
 #![cfg_attr(not(feature = "std"), no_std, no_main)]
 
-#[ink::contract]
-mod lock_unlock_smart_contract {
-    use ink::prelude::string::String;
+/// Access to `pallet-assets` through the runtime's chain extension, enabled by the `assets`
+/// feature.
+///
+/// The runtime must register the extension under `ASSETS_EXTENSION_ID`, executing each
+/// function with this contract as the origin.
+#[cfg(feature = "assets")]
+pub mod assets {
+    use ink::env::{DefaultEnvironment, Environment};
+    use ink::primitives::AccountId;
 
-    /// Represents the possible errors that can occur during escrow operations.
+    type Balance = <DefaultEnvironment as Environment>::Balance;
+
+    /// Id under which the runtime registers the assets chain extension.
+    pub const ASSETS_EXTENSION_ID: u16 = 0x0a5e;
+
+    /// The `pallet-assets` functions used to escrow assets.
+    #[ink::chain_extension(extension = 0x0a5e)]
+    pub trait PalletAssets {
+        type ErrorCode = AssetsError;
+
+        /// Transfers `amount` of `asset_id` from this contract to `to`.
+        #[ink(function = 1)]
+        fn transfer(asset_id: u32, to: AccountId, amount: Balance) -> Result<(), AssetsError>;
+
+        /// Transfers `amount` of `asset_id` from `from` to `to`, using the approval `from`
+        /// gave this contract.
+        #[ink(function = 2)]
+        fn transfer_approved(
+            asset_id: u32,
+            from: AccountId,
+            to: AccountId,
+            amount: Balance,
+        ) -> Result<(), AssetsError>;
+
+        /// Returns the balance of `asset_id` held by `owner`.
+        #[ink(function = 3, handle_status = false)]
+        fn balance_of(asset_id: u32, owner: AccountId) -> Balance;
+    }
+
+    /// Failure reported by the assets chain extension.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum AssetsError {
+        /// The runtime rejected the call, e.g. for a missing approval or balance.
+        Failed,
+    }
+
+    impl From<scale::Error> for AssetsError {
+        fn from(_: scale::Error) -> Self {
+            Self::Failed
+        }
+    }
+
+    impl ink::env::chain_extension::FromStatusCode for AssetsError {
+        fn from_status_code(status_code: u32) -> Result<(), Self> {
+            match status_code {
+                0 => Ok(()),
+                _ => Err(Self::Failed),
+            }
+        }
+    }
+
+    /// The default environment extended with `PalletAssets`.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum AssetsEnvironment {}
+
+    impl Environment for AssetsEnvironment {
+        const MAX_EVENT_TOPICS: usize = <DefaultEnvironment as Environment>::MAX_EVENT_TOPICS;
+
+        type AccountId = <DefaultEnvironment as Environment>::AccountId;
+        type Balance = <DefaultEnvironment as Environment>::Balance;
+        type Hash = <DefaultEnvironment as Environment>::Hash;
+        type Timestamp = <DefaultEnvironment as Environment>::Timestamp;
+        type BlockNumber = <DefaultEnvironment as Environment>::BlockNumber;
+        type ChainExtension = PalletAssets;
+    }
+}
+
+pub use self::lock_unlock_smart_contract::{
+    Error, LockReceipt, LockUnlock, LockUnlockSmartContract, LockUnlockSmartContractRef,
+};
+
+#[cfg_attr(
+    feature = "assets",
+    ink::contract(env = crate::assets::AssetsEnvironment)
+)]
+#[cfg_attr(not(feature = "assets"), ink::contract)]
+pub mod lock_unlock_smart_contract {
+    use ink::codegen::TraitCallBuilder;
+    use ink::prelude::{string::String, vec::Vec};
+    use ink::storage::{Lazy, Mapping};
+
+    /// The subset of the PSP22 fungible token standard used to escrow tokens.
+    ///
+    /// Message selectors are derived from the trait and message names, so any standard
+    /// PSP22 implementation can be called through `contract_ref!(PSP22)`.
+    #[ink::trait_definition]
+    pub trait PSP22 {
+        /// Transfers `value` tokens from the caller to `to`.
+        #[ink(message)]
+        fn transfer(
+            &mut self,
+            to: AccountId,
+            value: Balance,
+            data: Vec<u8>,
+        ) -> Result<(), PSP22Error>;
+
+        /// Transfers `value` tokens from `from` to `to` using the caller's allowance.
+        #[ink(message)]
+        fn transfer_from(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+            data: Vec<u8>,
+        ) -> Result<(), PSP22Error>;
+    }
+
+    /// The display part of the PSP22 Metadata extension, read once per token and cached.
+    #[ink::trait_definition]
+    pub trait PSP22Metadata {
+        /// Returns the ticker symbol of the token, if it has one.
+        #[ink(message)]
+        fn token_symbol(&self) -> Option<String>;
+
+        /// Returns the number of decimals used to display amounts of the token.
+        #[ink(message)]
+        fn token_decimals(&self) -> u8;
+    }
+
+    /// The core hash-lock interface, for contracts that integrate with the escrow through
+    /// `contract_ref!(LockUnlock)`.
+    ///
+    /// Selectors are pinned to the ones these messages had before the trait existed, so
+    /// clients built against earlier metadata keep working.
+    #[ink::trait_definition]
+    pub trait LockUnlock {
+        /// Locks the transferred value behind `hash` and returns a receipt for the new lock.
+        #[ink(message, payable, selector = 0xBBAA6540)]
+        fn lock(
+            &mut self,
+            hash: [u8; 32],
+            beneficiary: Option<AccountId>,
+            expiry: Option<Timestamp>,
+            cancel_grace: Option<Timestamp>,
+            redeemer_pubkey: Option<[u8; 33]>,
+        ) -> Result<LockReceipt, Error>;
+
+        /// Redeems `amount` of lock `lock_id`, or all of it, with the preimage of its hash.
+        #[ink(message, selector = 0xEC3E9290)]
+        fn redeem(
+            &mut self,
+            lock_id: u64,
+            preimage: Vec<u8>,
+            amount: Option<Balance>,
+        ) -> Result<(), Error>;
+
+        /// Returns the locker of `lock_id`, or `None` if no such lock is active.
+        #[ink(message, selector = 0x8050B208)]
+        fn get_locker(&self, lock_id: u64) -> Option<AccountId>;
+
+        /// Returns the amount held by `lock_id`, or zero if no such lock is active.
+        #[ink(message, selector = 0xAC35ABB3)]
+        fn get_locked_amount(&self, lock_id: u64) -> Balance;
+    }
+
+    /// Flags for every call into a token contract. `ALLOW_REENTRY` is left unset, so the
+    /// runtime refuses any call from the token back into this contract until it returns.
+    const TOKEN_CALL_FLAGS: ink::env::CallFlags = ink::env::CallFlags::empty();
+
+    /// Errors returned by a PSP22 token contract.
     #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum PSP22Error {
+        Custom(String),
+        InsufficientBalance,
+        InsufficientAllowance,
+        ZeroRecipientAddress,
+        ZeroSenderAddress,
+        SafeTransferCheckFailed(String),
+    }
+
+    /// Display metadata of a token: the native one, as reported by `token_metadata`, or a
+    /// PSP22 token, as recorded in its locks.
+    #[derive(Debug, Clone, Default, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct TokenMetadata {
+        /// Number of decimals used to display amounts.
+        pub decimals: u8,
+        /// Ticker symbol, as raw bytes.
+        pub symbol: Vec<u8>,
+    }
+
+    /// Upper bound of the redemption fee, in basis points (10%).
+    pub const MAX_FEE_BPS: u16 = 1_000;
+
+    /// Semantic version of the contract as `(major, minor, patch)`, reported by
+    /// `contract_version`. The major part is bumped on every breaking ABI change, the minor
+    /// part when messages are added and the patch part for fixes.
+    #[cfg(not(feature = "next-version"))]
+    pub const CONTRACT_SEMVER: (u16, u16, u16) = (2, 0, 0);
+
+    /// Semantic version of the code built by `fixtures/lock_unlock_next`, which the
+    /// end-to-end tests upgrade to.
+    #[cfg(feature = "next-version")]
+    pub const CONTRACT_SEMVER: (u16, u16, u16) = (3, 0, 0);
+
+    /// Version of the contract interface, the major part of `CONTRACT_SEMVER`.
+    pub const CONTRACT_VERSION: u32 = CONTRACT_SEMVER.0 as u32;
+
+    /// `capabilities` bit: locks can be given an expiry, after which they are refunded.
+    pub const CAP_EXPIRY: u32 = 1 << 0;
+    /// `capabilities` bit: a redemption fee is charged.
+    pub const CAP_FEES: u32 = 1 << 1;
+    /// `capabilities` bit: PSP22 tokens can be locked with `lock_token`.
+    pub const CAP_PSP22: u32 = 1 << 2;
+    /// `capabilities` bit: runtime assets can be locked with `lock_asset`.
+    pub const CAP_ASSETS: u32 = 1 << 3;
+    /// `capabilities` bit: redemptions earn a reward from the pool.
+    pub const CAP_REWARDS: u32 = 1 << 4;
+    /// `capabilities` bit: only allow-listed accounts may lock.
+    pub const CAP_ALLOWLIST: u32 = 1 << 5;
+    /// `capabilities` bit: this is a canary deployment with a ceiling or sunset.
+    pub const CAP_CANARY: u32 = 1 << 6;
+    /// `capabilities` bit: new locks are accepted, the contract being neither paused nor
+    /// past its sunset.
+    pub const CAP_LOCKING: u32 = 1 << 7;
+
+    /// Layout version of the storage this code expects; `migrate` brings older storage up to it.
+    ///
+    /// Version 2 moved the fields only read by a few messages out of the storage root into
+    /// `Lazy` cells. Version 1 storage cannot be decoded by this code, so it cannot be
+    /// upgraded in place.
+    pub const STORAGE_VERSION: u32 = 2;
+
+    /// Maximum number of conditions, nested ones included, a `lock_with` call may set and
+    /// of proofs a `redeem_with` call may present.
+    pub const MAX_CONDITIONS: u32 = 8;
+
+    /// Maximum nesting depth of `lock_with` conditions; top-level conditions are at depth 1.
+    pub const MAX_CONDITION_DEPTH: u32 = 2;
+
+    /// Maximum number of redeemers a `lock_with_redeemers` call may list.
+    pub const MAX_REDEEMERS: u32 = 16;
+
+    /// Maximum length in bytes of a `lock_with_memo` memo.
+    pub const MAX_MEMO_LEN: usize = 128;
+
+    /// Maximum number of recipients a `lock_with_split` call may list.
+    pub const MAX_SPLIT_RECIPIENTS: usize = 8;
+
+    /// Default number of blocks a redemption commitment must wait before `reveal`.
+    pub const DEFAULT_MIN_COMMIT_AGE_BLOCKS: u32 = 1;
+
+    /// Default time a lock must have been expired before `terminate` may refund it (one year).
+    pub const DEFAULT_TERMINATION_GRACE: Timestamp = 365 * MILLIS_PER_DAY;
+
+    /// Default time a lock must have been expired before anyone may `finalize_expired` it.
+    pub const DEFAULT_FINALIZE_GRACE: Timestamp = 7 * MILLIS_PER_DAY;
+
+    /// Length of a day in block timestamp units (milliseconds), used for reward accrual.
+    pub const MILLIS_PER_DAY: Timestamp = 24 * 60 * 60 * 1000;
+
+    /// Represents the possible errors that can occur during escrow operations.
+    ///
+    /// Each variant is encoded as its discriminant, followed by its fields if it has any.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    #[repr(u8)]
     pub enum Error {
         AssetsAlreadyLocked = 0,
         NoAssetsSent = 1,
         NotLocker = 2,
-        IncorrectMessage = 3,
+        InvalidPreimage = 3,
         TransferFailed = 4,
+        ReentrantCall = 5,
+        CanaryLimit = 6,
+        Sunset = 7,
+        NotBeneficiary = 8,
+        LockNotFound = 9,
+        NotExpired = 10,
+        Expired = 11,
+        CancelWindowClosed = 12,
+        Overflow = 13,
+        Shielded = 14,
+        InsufficientLockedBalance = 15,
+        ZeroAmount = 16,
+        TokenTransferFailed = 17,
+        WrongAsset = 18,
+        InvalidSignature = 19,
+        NoRedeemerKey = 20,
+        NotOwner = 21,
+        ContractPaused = 22,
+        FeeTooHigh = 23,
+        AmountBelowMinimum = 24,
+        AmountAboveMaximum = 25,
+        VestingLock = 26,
+        NotVesting = 27,
+        NothingToClaim = 28,
+        NotCounterparty = 29,
+        NotAccepted = 30,
+        DeadlinePassed = 31,
+        AlreadyAccepted = 32,
+        EscrowLock = 33,
+        NotEscrow = 34,
+        Disputed = 35,
+        NotDisputed = 36,
+        NotArbiter = 37,
+        NotArbitrated = 38,
+        NotParty = 39,
+        BatchAmountMismatch = 40,
+        /// A batch had `got` entries where at most `max` are accepted.
+        BatchTooLarge {
+            max: u32,
+            got: u32,
+        } = 41,
+        BatchLengthMismatch = 42,
+        ActiveLockExists = 43,
+        InvalidLocker = 44,
+        NotAllowed = 45,
+        RewardsCommitted = 46,
+        UpgradeFailed = 47,
+        AlreadyMigrated = 48,
+        AssetMismatch = 49,
+        NoCommitment = 50,
+        CommitmentTooFresh = 51,
+        CommitmentMismatch = 52,
+        InvalidExpiry = 53,
+        NoExtensionProposal = 54,
+        WouldReapContract = 55,
+        NotApprover = 56,
+        AlreadyApproved = 57,
+        InsufficientApprovals = 58,
+        ApprovalLock = 59,
+        DuplicateApprover = 60,
+        NotStream = 61,
+        BountyExceedsLock = 62,
+        TooManyConditions = 63,
+        InvalidConditions = 64,
+        TooManyRedeemers = 65,
+        MemoTooLong = 66,
+        InvalidSplit = 67,
+        NoBeneficiary = 68,
+        NoRecoveryAccount = 69,
+        NotRecoveryAccount = 70,
+        RecoveryDelayNotElapsed = 71,
+        NoScheduledClaim = 72,
+        ClaimNotDue = 73,
+        TipExceedsPayout = 74,
+        InvalidBond = 75,
+        InvalidPercent = 76,
+    }
+
+    /// How a lock, or part of it, was settled.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub enum Settlement {
+        Redeemed,
+        Refunded,
+        Cancelled,
+        Claimed,
+        Released,
+        Withdrawn,
+        Resolved,
+        Rejected,
+        Recovered,
+    }
+
+    /// A completed settlement, kept in the on-chain history.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct LockRecord {
+        /// The id of the settled lock.
+        pub lock_id: u64,
+        /// The account ID of the user who locked the assets.
+        pub locker: AccountId,
+        /// The amount settled by this operation.
+        pub amount: Balance,
+        /// Block timestamp at which the lock was created.
+        pub locked_at: Timestamp,
+        /// Block timestamp of the settlement.
+        pub completed_at: Timestamp,
+        /// How the lock was settled.
+        pub outcome: Settlement,
+    }
+
+    /// Maximum number of records returned by a single `get_records` call.
+    pub const MAX_RECORDS_PER_QUERY: u32 = 50;
+
+    /// Maximum number of specs accepted by `lock_batch`.
+    pub const MAX_BATCH_LOCKS: u32 = 50;
+
+    /// Maximum number of ids accepted by `redeem_batch`.
+    pub const MAX_BATCH_REDEEMS: u32 = 50;
+
+    /// The limits on list arguments, reported by `get_batch_limits` so clients can split
+    /// their requests to fit.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct BatchLimits {
+        /// `MAX_BATCH_LOCKS`, the specs per `lock_batch` call.
+        pub batch_locks: u32,
+        /// `MAX_BATCH_REDEEMS`, the ids per `redeem_batch` call.
+        pub batch_redeems: u32,
+        /// `MAX_SPLIT_RECIPIENTS`, the recipients per `lock_with_split` call.
+        pub split_recipients: u32,
+        /// `MAX_REDEEMERS`, the redeemers per `lock_with_redeemers` call.
+        pub redeemers: u32,
+        /// `MAX_CONDITIONS`, the conditions per `lock_with` and proofs per `redeem_with` call.
+        pub conditions: u32,
+        /// `MAX_RECORDS_PER_QUERY`, the records returned per `get_records` call.
+        pub records_per_query: u32,
+    }
+
+    /// One lock to create with `lock_batch`.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct LockSpec {
+        /// The part of the transferred value to lock.
+        pub amount: Balance,
+        /// Blake2x256 hash of the secret that redeems the lock.
+        pub hash: [u8; 32],
+        /// The only account allowed to redeem, if any.
+        pub beneficiary: Option<AccountId>,
+        /// Timestamp after which the lock can no longer be redeemed, if any.
+        pub expiry: Option<Timestamp>,
+    }
+
+    /// What `lock` and `lock_for` created, so callers need no follow-up query.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct LockReceipt {
+        /// The id of the new lock.
+        pub lock_id: u64,
+        /// The amount locked.
+        pub amount: Balance,
+        /// Timestamp of the block the lock was created in.
+        pub timestamp: Timestamp,
+        /// Number of the block the lock was created in.
+        pub block_number: BlockNumber,
+    }
+
+    /// Result of `lock_or_refund`, which reports rejections instead of reverting.
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum LockOutcome {
+        /// The assets were locked under `lock_id`.
+        Locked { lock_id: u64, amount: Balance },
+        /// No assets were sent, so nothing was locked.
+        NothingSent,
+        /// The lock was refused with `reason` and the sent assets were returned.
+        Rejected { reason: Error, refunded: Balance },
+    }
+
+    /// Result of `can_redeem`, which predicts what `redeem` would do without doing it.
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum RedeemCheck {
+        /// `redeem` would withdraw `amount` and pay `payout` to the caller.
+        Ok { amount: Balance, payout: Balance },
+        /// `redeem` would fail with `reason`.
+        Rejected { reason: Error },
+    }
+
+    /// Breakdown of a full redemption reported by `preview_settlement`.
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct SettlementPreview {
+        /// The time the preview was computed for.
+        pub at: Timestamp,
+        /// The amount that would be withdrawn from the lock.
+        pub amount: Balance,
+        /// The reward that would be paid from the pool on top of `amount`.
+        pub reward: Balance,
+        /// The part of `amount` that would go to the fee collector.
+        pub fee: Balance,
+        /// What would be withheld to keep the contract above the existential deposit.
+        pub shortfall: Balance,
+        /// The total paid out, net of the fee and the shortfall and including the reward.
+        pub payout: Balance,
+        /// How `payout` would be divided: the caller alone, or the split recipients.
+        pub recipients: Vec<(AccountId, Balance)>,
+    }
+
+    /// Deployment information reported by `contract_info`.
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct ContractInfo {
+        /// `true` if the instance was deployed in canary mode.
+        pub canary: bool,
+        /// Per-lock ceiling enforced in canary mode, if any.
+        pub canary_max_amount: Option<Balance>,
+        /// Timestamp after which no new locks are accepted, if any.
+        pub sunset_at: Option<Timestamp>,
+        /// `true` once the sunset timestamp has been reached.
+        pub sunset: bool,
+        /// Identifier of the chain this instance was deployed on.
+        pub chain_id: u32,
+    }
+
+    /// Lifetime usage counters reported by `stats`.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct ContractStats {
+        /// Number of locks ever created.
+        pub total_locks: u64,
+        /// Number of redemptions, including claims, releases and resolutions.
+        pub total_redeemed: u64,
+        /// Number of locks refunded to their locker after expiry, withdrawn, rejected by
+        /// their beneficiary or recovered.
+        pub total_refunded: u64,
+        /// Number of locks cancelled by their locker.
+        pub total_cancelled: u64,
+        /// Total native amount ever locked, including top-ups. A shielded lock only counts
+        /// as its amount is revealed by settlements.
+        pub cumulative_volume: Balance,
+    }
+
+    /// Linear release schedule of a vesting lock.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Vesting {
+        /// Block timestamp from which the funds start vesting.
+        pub start: Timestamp,
+        /// Time over which the whole amount becomes claimable.
+        pub duration: Timestamp,
+        /// The amount originally locked; the lock's `amount` is what is left unclaimed.
+        pub total: Balance,
+        /// Whether this is a payment stream, which the locker may end with `cancel_stream`.
+        pub stream: bool,
+    }
+
+    /// Which party an accepted escrow pays out to on `release`.
+    ///
+    /// The other party is the one who triggers the release.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub enum EscrowDirection {
+        /// The counterparty releases the funds back to the locker.
+        ToLocker,
+        /// The locker releases the funds to the counterparty.
+        ToCounterparty,
+    }
+
+    /// Terms of a two-party escrow lock.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Escrow {
+        /// The other party of the escrow, who must `accept` it.
+        pub counterparty: AccountId,
+        /// Who receives the funds on `release`.
+        pub direction: EscrowDirection,
+        /// Timestamp by which the counterparty must accept.
+        pub deadline: Timestamp,
+        /// Whether the counterparty has accepted.
+        pub accepted: bool,
+    }
+
+    /// Arbiter of a lock and the dispute raised on it, if any.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Arbitration {
+        /// The only account that may `resolve` a dispute.
+        pub arbiter: AccountId,
+        /// The locker or beneficiary who raised a dispute, if one was raised.
+        pub disputed_by: Option<AccountId>,
+        /// Native bond posted by `disputed_by` with the dispute.
+        pub bond: Balance,
+    }
+
+    /// What `redeem` does when a native payout would take the contract below the
+    /// existential deposit.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub enum ReapPolicy {
+        /// Refuse the redemption with `WouldReapContract`.
+        #[default]
+        Fail,
+        /// Pay out what the contract can spare and report the rest as `shortfall`.
+        PayAvailable,
+    }
+
+    /// The two approvers of a lock created with `lock_with_approvers` and which of them
+    /// have approved its redemption.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Approvals {
+        /// The accounts whose approval is required.
+        pub approvers: [AccountId; 2],
+        /// Bit `i` is set while `approvers[i]` approves.
+        pub granted: u8,
+    }
+
+    /// A pending `propose_extension` awaiting the other party.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct ExtensionProposal {
+        /// The locker or beneficiary who proposed the extension.
+        pub proposer: AccountId,
+        /// The expiry the lock gets once the proposal is accepted.
+        pub new_expiry: Timestamp,
+    }
+
+    /// A pending `commit` for a redemption.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Commitment {
+        /// `redeem_commitment(committer, preimage)`.
+        pub hash: [u8; 32],
+        /// Block in which the commitment was made.
+        pub committed_at: BlockNumber,
+    }
+
+    /// A claim the beneficiary scheduled with `schedule_claim`, for a keeper to execute.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct ScheduledClaim {
+        /// Timestamp from which anyone may execute the claim.
+        pub execute_after: Timestamp,
+        /// Part of the payout paid to the account executing the claim.
+        pub tip: Balance,
+        /// The preimage the beneficiary revealed when scheduling.
+        pub preimage: Vec<u8>,
+    }
+
+    /// A single active lock, stored under its lock id.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct LockInfo {
+        /// The account ID of the user who locked the assets.
+        pub locker: AccountId,
+        /// The amount of assets (in native tokens) held by the lock.
+        pub amount: Balance,
+        /// Blake2x256 hash of the secret that unlocks the lock.
+        pub secret_hash: [u8; 32],
+        /// The account entitled to redeem the lock, if not the locker.
+        pub beneficiary: Option<AccountId>,
+        /// Salt of the amount commitment if the lock is shielded.
+        pub shield_salt: Option<[u8; 32]>,
+        /// Timestamp from which the locker may reclaim the funds, if any.
+        pub expiry: Option<Timestamp>,
+        /// Block timestamp at which the lock was created.
+        pub locked_at: Timestamp,
+        /// How long after `locked_at` the locker may still cancel a lock that names a
+        /// beneficiary.
+        pub cancel_grace: Option<Timestamp>,
+        /// The PSP22 token contract holding the assets, or `None` for native tokens.
+        pub token: Option<AccountId>,
+        /// Display metadata of `token` when the lock was created, so the lock still renders
+        /// correctly if the token's metadata changes later.
+        pub token_metadata: Option<TokenMetadata>,
+        /// The `pallet-assets` asset held, for locks created with `lock_asset`.
+        pub asset_id: Option<u32>,
+        /// Compressed ECDSA public key whose signature can redeem the lock instead of the
+        /// secret preimage.
+        pub redeemer_pubkey: Option<[u8; 33]>,
+        /// Number of signature redemptions so far, part of the signed challenge.
+        pub nonce: u64,
+        /// Release schedule if this is a vesting lock, which is settled with `claim`.
+        pub vesting: Option<Vesting>,
+        /// Terms if this is an escrow lock, which is settled with `release` or `withdraw`.
+        pub escrow: Option<Escrow>,
+        /// Arbiter if this lock was created with `lock_with_arbiter`.
+        pub arbitration: Option<Arbitration>,
+        /// Pending expiry extension, if one party has proposed one.
+        pub extension: Option<ExtensionProposal>,
+        /// Required approvers if this lock was created with `lock_with_approvers`.
+        pub approvals: Option<Approvals>,
+        /// Accounts besides the locker that may redeem a lock without a beneficiary, set
+        /// with `lock_with_redeemers`.
+        pub redeemers: Vec<AccountId>,
+        /// Reference attached with `lock_with_memo`, such as an order id.
+        pub memo: Option<String>,
+        /// Recipients of a redemption and their shares in basis points, set with
+        /// `lock_with_split`; empty if the redeemer is paid.
+        pub split: Vec<(AccountId, u16)>,
+        /// Account that may take over the lock after a period of inactivity, set with
+        /// `lock_with_recovery`.
+        pub recovery: Option<Recovery>,
+    }
+
+    /// Reward paid to whoever calls `finalize_expired` on someone else's lock, taken from
+    /// the locked funds.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub enum FinalizeBounty {
+        /// A fixed amount per lock.
+        Fixed(Balance),
+        /// A share of the locked amount, in basis points.
+        Bps(u16),
+    }
+
+    /// A checked redemption, worked out by `plan_redeem` before any state changes.
+    struct RedeemPlan {
+        /// The lock as stored before the redemption.
+        stored: LockInfo,
+        /// The lock after the redemption, with its nonce advanced if a signature was used.
+        lock: LockInfo,
+        amount: Balance,
+        remaining: Balance,
+        reward: Balance,
+        fee: Balance,
+        payout: Balance,
+        shortfall: Balance,
+        /// Account executing a scheduled claim and the part of `payout` it is paid.
+        tip: Option<(AccountId, Balance)>,
+    }
+
+    /// A requirement for redeeming a lock created with `lock_with`.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum Condition {
+        /// The redeemer must present the preimage of this Blake2x256 hash.
+        MessageHash([u8; 32]),
+        /// Only this account may redeem, and it receives the funds.
+        Beneficiary(AccountId),
+        /// Redemption is refused from this timestamp on, and the locker may `refund` instead.
+        Timelock(Timestamp),
+        /// All of the nested conditions.
+        And(Vec<Condition>),
+    }
+
+    /// Fallback account of a `lock_with_recovery` lock, for a locker who lost their key.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Recovery {
+        /// The account that may `recover` the lock.
+        pub account: AccountId,
+        /// How long the lock must go without a redemption before it can be recovered.
+        pub delay: Timestamp,
+        /// Timestamp of the lock's creation or of its latest partial redemption.
+        pub active_since: Timestamp,
+    }
+
+    /// Proof presented to redeem a lock.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum Proof {
+        /// The preimage of the lock's secret hash.
+        Preimage(Vec<u8>),
+        /// A signature by the lock's redeemer key over its current challenge.
+        Signature([u8; 65]),
     }
 
-    /// This smart contract allows a user to lock assets and later redeem them by providing a specific message.
+    /// This smart contract allows users to lock assets and later redeem them by revealing a secret.
     ///
     /// **Functionality:**
-    /// 1. **Locking:** A user can lock a certain amount of assets (native tokens) by calling the `lock` function.
-    ///    - Only one user can lock assets at a time.
+    /// 1. **Locking:** A user can lock a certain amount of assets (native tokens) by calling the `lock` function
+    ///    with the Blake2x256 hash of a secret of their choice.
+    ///    - Any number of locks can be active at the same time, each identified by a lock id.
     ///    - The amount locked must be greater than zero.
-    /// 2. **Redeeming:** The user who locked the assets can redeem them by calling the `redeem` function and providing the correct message ("Hello, World!").
+    /// 2. **Redeeming:** The user who locked the assets can redeem them by calling the `redeem` function with the
+    ///    lock id and the preimage of that hash.
     ///    - The contract will transfer the locked assets back to the locker.
-    ///    - After redemption, the contract's state is reset, allowing another user to lock assets.
+    ///    - After redemption, the lock is removed from storage.
     ///
     /// **Events:**
     /// - `Locked`: Emitted when assets are successfully locked.
@@ -31,268 +811,10019 @@ mod lock_unlock_smart_contract {
     ///
     /// **Security Considerations:**
     /// - The contract ensures that only the locker can redeem the assets.
-    /// - The contract verifies that the correct message is provided during redemption.
-    /// - The contract prevents double-locking by checking if assets are already locked.
+    /// - The contract verifies that the provided preimage hashes to the committed secret hash during redemption.
+    /// - Lock ids are never reused, so a redeemed lock cannot be redeemed again.
     #[ink(storage)]
     pub struct LockUnlockSmartContract {
-        /// The account ID of the user who has locked the assets.
-        /// `None` if no assets are currently locked.
-        locker: Option<AccountId>,
-        /// The amount of assets (in native tokens) that are currently locked.
-        locked_amount: Balance,
+        /// Active locks, keyed by lock id.
+        locks: Mapping<u64, LockInfo>,
+        /// The id assigned to the next lock; incremented on every successful `lock`.
+        next_lock_id: u64,
+        /// Reentrancy flag, set while a settlement message is executing. It has a cell of
+        /// its own because the storage root is only written back when a message returns,
+        /// so a nested call would not see the flag there.
+        entered: Lazy<bool>,
+        /// Maximum amount a single lock may hold on a canary deployment.
+        canary_max_amount: Option<Balance>,
+        /// Timestamp from which new locks are refused on a canary deployment.
+        sunset_at: Option<Timestamp>,
+        /// Identifier of the chain this instance was deployed on, fixed at instantiation.
+        chain_id: u32,
+        /// Deployer-supplied marker of the chain (e.g. its genesis hash), fixed at instantiation.
+        genesis_marker: Lazy<[u8; 32]>,
+        /// Decimals and symbol of the native token, fixed at instantiation.
+        native_metadata: Lazy<TokenMetadata>,
+        /// Metadata read from each PSP22 token on its first lock.
+        token_metadata_cache: Mapping<AccountId, TokenMetadata>,
+        /// Account allowed to perform privileged maintenance; `None` once renounced.
+        owner: Option<AccountId>,
+        /// Emergency brake set by the owner; refuses new locks and top-ups while set.
+        paused: bool,
+        /// Fee taken from every redemption, in basis points of the redeemed amount.
+        fee_bps: u16,
+        /// Account receiving the redemption fees; the deployer by default.
+        fee_collector: AccountId,
+        /// Completed settlements, keyed by their index in the history.
+        history: Mapping<u64, LockRecord>,
+        /// Number of settlements recorded so far; the index of the next record.
+        history_len: u64,
+        /// Index of the oldest record that has not been pruned.
+        history_start: u64,
+        /// Smallest amount a native lock may be created with.
+        min_amount: Balance,
+        /// Largest amount a native lock may hold, if limited.
+        max_amount: Option<Balance>,
+        /// Number of locks currently stored.
+        active_locks: u64,
+        /// Ids of the stored locks, by position `0..active_locks`.
+        open_locks: Mapping<u64, u64>,
+        /// Position of each stored lock in `open_locks`.
+        open_position: Mapping<u64, u64>,
+        /// Sum of the amounts of the stored native locks and of the dispute bonds they hold.
+        native_locked: Balance,
+        /// Sum of the amounts of the stored locks that earn redemption rewards.
+        earning_locked: Balance,
+        /// Sum of `amount * locked_at` over the locks counted in `earning_locked`, with
+        /// `locked_at` in whole seconds.
+        earning_weight: Balance,
+        /// How long past its expiry a lock must be before `terminate` refunds it; unset
+        /// means `DEFAULT_TERMINATION_GRACE`.
+        termination_grace: Lazy<Timestamp>,
+        /// Lifetime usage counters.
+        stats: ContractStats,
+        /// Accounts allowed to lock while `allowlist_enabled` is set.
+        allowlist: Mapping<AccountId, ()>,
+        /// Whether only accounts on the `allowlist` may create locks.
+        allowlist_enabled: bool,
+        /// Owner-funded balance that redemption rewards are paid from.
+        reward_pool: Balance,
+        /// Reward accrued per full day locked, in basis points of the redeemed amount.
+        rate_bps_per_day: u16,
+        /// Layout version of the stored data, see `STORAGE_VERSION`.
+        storage_version: u32,
+        /// Pending redemption commitments, by committer and lock id.
+        commitments: Mapping<(AccountId, u64), Commitment>,
+        /// Number of blocks a commitment must wait before it can be revealed.
+        min_commit_age_blocks: u32,
+        /// How redemptions that would reap the contract account are handled.
+        reap_policy: ReapPolicy,
+        /// Time after its expiry from which anyone may `finalize_expired` a lock; unset
+        /// means `DEFAULT_FINALIZE_GRACE`.
+        finalize_grace: Lazy<Timestamp>,
+        /// What `finalize_expired` pays a caller other than the locker; unset means none.
+        finalize_bounty: Lazy<FinalizeBounty>,
+        /// Account `reconcile` sends the excess balance to; unset means the owner.
+        surplus_recipient: Lazy<AccountId>,
+        /// Claims scheduled by beneficiaries, by lock id.
+        scheduled_claims: Mapping<u64, ScheduledClaim>,
+        /// Commitment to the immutable fields of each active lock, see `get_lock_commitment`.
+        lock_commitments: Mapping<u64, [u8; 32]>,
+        /// Dispute bond in basis points of the disputed amount.
+        dispute_bond_bps: u16,
+        /// Smallest dispute bond, whatever the disputed amount.
+        dispute_bond_floor: Balance,
     }
 
     /// Event emitted when assets are successfully locked.
     #[ink(event)]
     pub struct Locked {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// The id of the new lock.
+        #[ink(topic)]
+        lock_id: u64,
+        /// The account ID of the user who locked the assets.
+        #[ink(topic)]
+        locker: AccountId,
+        /// The amount of assets that were locked, or `None` if the lock is shielded.
+        amount: Option<Balance>,
+        /// Commitment to the amount of a shielded lock, see `amount_commitment`.
+        amount_commitment: Option<[u8; 32]>,
+        /// Blake2x256 hash of the secret required to redeem the lock.
+        hash: [u8; 32],
+        /// The account entitled to redeem the lock, if not the locker.
+        beneficiary: Option<AccountId>,
+        /// Timestamp from which the locker may reclaim the funds, if any.
+        expiry: Option<Timestamp>,
+        /// The PSP22 token contract holding the assets, or `None` for native tokens.
+        token: Option<AccountId>,
+        /// The `pallet-assets` asset held, if any.
+        asset_id: Option<u32>,
+        /// The account that sent the assets, which differs from `locker` with `lock_for`.
+        funded_by: AccountId,
+        /// Timestamp of the block the lock was created in.
+        timestamp: Timestamp,
+        /// Number of the block the lock was created in.
+        block_number: BlockNumber,
+        /// Blake2x256 hash of the lock's memo, if it has one.
+        #[ink(topic)]
+        memo_hash: Option<[u8; 32]>,
+        /// Commitment to the lock's immutable fields, see `get_lock_commitment`.
+        commitment: [u8; 32],
+    }
+
+    /// Event emitted when `lock_or_refund` refuses a lock and returns the sent assets.
+    #[ink(event)]
+    pub struct LockRejected {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// The account ID of the user whose lock was refused.
+        #[ink(topic)]
+        caller: AccountId,
+        /// Why the lock was refused.
+        reason: Error,
+        /// The amount returned to the caller.
+        refunded: Balance,
+    }
+
+    /// Event emitted when ownership of the contract changes.
+    #[ink(event)]
+    pub struct OwnershipTransferred {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// The previous owner.
+        #[ink(topic)]
+        previous: Option<AccountId>,
+        /// The new owner, or `None` if ownership was renounced.
+        #[ink(topic)]
+        new: Option<AccountId>,
+    }
+
+    /// Event emitted when the owner pauses lock operations.
+    #[ink(event)]
+    pub struct Paused {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// The owner who paused the contract.
+        #[ink(topic)]
+        by: AccountId,
+    }
+
+    /// Event emitted when the owner adds an account to or removes it from the allow-list.
+    #[ink(event)]
+    pub struct AllowlistUpdated {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// The account whose entry changed.
+        #[ink(topic)]
+        account: AccountId,
+        /// Whether the account is now on the allow-list.
+        allowed: bool,
+    }
+
+    /// Event emitted when the owner sweeps the excess native balance.
+    #[ink(event)]
+    pub struct ExcessSwept {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// The account the excess was sent to.
+        #[ink(topic)]
+        recovery: AccountId,
+        /// The amount sent.
+        amount: Balance,
+    }
+
+    /// Event emitted when the owner replaces the code of the contract.
+    #[ink(event)]
+    pub struct Upgraded {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// Hash of the new code.
+        code_hash: Hash,
+    }
+
+    /// Event emitted when `migrate` brings the storage to a new layout version.
+    #[ink(event)]
+    pub struct Migrated {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// The storage version before the migration.
+        from: u32,
+        /// The storage version after the migration.
+        to: u32,
+    }
+
+    /// Event emitted when the owner resumes lock operations.
+    #[ink(event)]
+    pub struct Unpaused {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// The owner who unpaused the contract.
+        #[ink(topic)]
+        by: AccountId,
+    }
+
+    /// Event emitted when a vesting lock is created.
+    #[ink(event)]
+    pub struct VestingStarted {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// The id of the vesting lock.
+        #[ink(topic)]
+        lock_id: u64,
+        /// The account ID of the user who locked the assets.
+        #[ink(topic)]
+        locker: AccountId,
+        /// The account entitled to claim, if not the locker.
+        beneficiary: Option<AccountId>,
+        /// The amount that vests.
+        amount: Balance,
+        /// Block timestamp from which the funds vest.
+        start: Timestamp,
+        /// Time over which the whole amount vests.
+        duration: Timestamp,
+    }
+
+    /// Event emitted when a payment stream is opened.
+    #[ink(event)]
+    pub struct StreamOpened {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// The id of the stream.
+        #[ink(topic)]
+        lock_id: u64,
+        /// The account ID of the user who funds the stream.
+        #[ink(topic)]
+        locker: AccountId,
+        /// The account the stream pays.
+        #[ink(topic)]
+        beneficiary: AccountId,
+        /// The amount streamed in total.
+        amount: Balance,
+        /// Block timestamp from which the stream runs.
+        start: Timestamp,
+        /// Time over which the whole amount streams.
+        duration: Timestamp,
+    }
+
+    /// Event emitted when the beneficiary withdraws from a stream.
+    #[ink(event)]
+    pub struct StreamWithdrawn {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// The id of the stream.
+        #[ink(topic)]
+        lock_id: u64,
+        /// The beneficiary who withdrew.
+        #[ink(topic)]
+        beneficiary: AccountId,
+        /// The amount withdrawn, including the fee.
+        amount: Balance,
+        /// The amount withdrawn from the stream so far, including this withdrawal.
+        withdrawn: Balance,
+        /// The amount still held by the stream.
+        remaining: Balance,
+        /// The part of `amount` paid to the fee collector.
+        fee: Balance,
+    }
+
+    /// Event emitted when the locker ends a stream early.
+    #[ink(event)]
+    pub struct StreamCancelled {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// The id of the stream.
+        #[ink(topic)]
+        lock_id: u64,
+        /// The beneficiary paid the streamed part.
+        #[ink(topic)]
+        beneficiary: AccountId,
+        /// The streamed but unwithdrawn part paid to the beneficiary, including the fee.
+        streamed: Balance,
+        /// The part of `streamed` paid to the fee collector.
+        fee: Balance,
+        /// The unstreamed part returned to the locker.
+        returned: Balance,
+    }
+
+    /// Event emitted when vested funds are claimed.
+    #[ink(event)]
+    pub struct Claimed {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// The id of the vesting lock.
+        #[ink(topic)]
+        lock_id: u64,
+        /// The account that received the funds.
+        #[ink(topic)]
+        recipient: AccountId,
+        /// The amount claimed, including the fee.
+        amount: Balance,
+        /// The amount still held by the lock after this claim.
+        remaining: Balance,
+        /// The part of `amount` paid to the fee collector.
+        fee: Balance,
+    }
+
+    /// Event emitted when an escrow lock is opened.
+    #[ink(event)]
+    pub struct EscrowOpened {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// The id of the escrow lock.
+        #[ink(topic)]
+        lock_id: u64,
         /// The account ID of the user who locked the assets.
         #[ink(topic)]
         locker: AccountId,
-        /// The amount of assets that were locked.
+        /// The other party of the escrow.
+        #[ink(topic)]
+        counterparty: AccountId,
+        /// Who receives the funds on release.
+        direction: EscrowDirection,
+        /// Timestamp by which the counterparty must accept.
+        deadline: Timestamp,
+    }
+
+    /// Event emitted when the counterparty accepts an escrow.
+    #[ink(event)]
+    pub struct EscrowAccepted {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// The id of the escrow lock.
+        #[ink(topic)]
+        lock_id: u64,
+        /// The counterparty who accepted.
+        #[ink(topic)]
+        counterparty: AccountId,
+    }
+
+    /// Event emitted when an accepted escrow is released.
+    #[ink(event)]
+    pub struct EscrowReleased {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// The id of the escrow lock.
+        #[ink(topic)]
+        lock_id: u64,
+        /// The account that received the funds.
+        #[ink(topic)]
+        recipient: AccountId,
+        /// The amount released, including the fee.
+        amount: Balance,
+        /// The part of `amount` paid to the fee collector.
+        fee: Balance,
+    }
+
+    /// Event emitted when the locker withdraws an escrow that was never accepted.
+    #[ink(event)]
+    pub struct EscrowWithdrawn {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// The id of the escrow lock.
+        #[ink(topic)]
+        lock_id: u64,
+        /// The account ID of the locker.
+        #[ink(topic)]
+        locker: AccountId,
+        /// The amount returned.
+        amount: Balance,
+    }
+
+    /// Event emitted when the locker or beneficiary raises a dispute.
+    #[ink(event)]
+    pub struct DisputeRaised {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// The id of the disputed lock.
+        #[ink(topic)]
+        lock_id: u64,
+        /// The party that raised the dispute.
+        #[ink(topic)]
+        by: AccountId,
+    }
+
+    /// Event emitted when the arbiter resolves a dispute.
+    #[ink(event)]
+    pub struct Resolved {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// The id of the resolved lock.
+        #[ink(topic)]
+        lock_id: u64,
+        /// The side the funds were sent to.
+        #[ink(topic)]
+        winner: AccountId,
+        /// The amount resolved, including the fee.
         amount: Balance,
+        /// The part of `amount` paid to the fee collector.
+        fee: Balance,
+        /// The dispute bond, paid to `winner` along with the funds.
+        bond: Balance,
+        /// Whether `winner` raised the dispute and so got their bond back, rather than
+        /// the counterparty forfeiting it.
+        bond_returned: bool,
     }
 
-    /// Event emitted when assets are successfully redeemed.
-    #[ink(event)]
-    pub struct Redeemed {
-        /// The account ID of the user who redeemed the assets.
-        #[ink(topic)]
-        locker: AccountId,
-        /// The amount of assets that were redeemed.
-        amount: Balance,
-    }
+    /// Event emitted when an approver approves the redemption of a lock.
+    #[ink(event)]
+    pub struct ApprovalGiven {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// The id of the lock.
+        #[ink(topic)]
+        lock_id: u64,
+        /// The approver who approved.
+        #[ink(topic)]
+        approver: AccountId,
+    }
+
+    /// Event emitted when an approver withdraws their approval.
+    #[ink(event)]
+    pub struct ApprovalRevoked {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// The id of the lock.
+        #[ink(topic)]
+        lock_id: u64,
+        /// The approver who revoked.
+        #[ink(topic)]
+        approver: AccountId,
+    }
+
+    /// Event emitted when a fully approved lock is paid out to its locker.
+    #[ink(event)]
+    pub struct ApprovedRedeemed {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// The id of the lock.
+        #[ink(topic)]
+        lock_id: u64,
+        /// The locker who received the funds.
+        #[ink(topic)]
+        locker: AccountId,
+        /// The amount redeemed, including the fee.
+        amount: Balance,
+        /// The part of `amount` paid to the fee collector.
+        fee: Balance,
+    }
+
+    /// Event emitted when a locker adds funds to an active lock.
+    #[ink(event)]
+    pub struct ToppedUp {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// The id of the topped-up lock.
+        #[ink(topic)]
+        lock_id: u64,
+        /// The account ID of the user who locked the assets.
+        #[ink(topic)]
+        locker: AccountId,
+        /// The amount added by this call.
+        added: Balance,
+        /// The amount held by the lock after this call.
+        new_total: Balance,
+    }
+
+    /// Event emitted when assets are successfully redeemed.
+    #[ink(event)]
+    pub struct Redeemed {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// The id of the redeemed lock.
+        #[ink(topic)]
+        lock_id: u64,
+        /// The account ID of the user who locked the assets.
+        #[ink(topic)]
+        locker: AccountId,
+        /// The beneficiary who redeemed the assets, if the lock named one.
+        beneficiary: Option<AccountId>,
+        /// The account that redeemed and was paid: the beneficiary, the locker or one of
+        /// the lock's redeemers.
+        redeemer: AccountId,
+        /// The amount of assets that were redeemed, including the fee.
+        amount: Balance,
+        /// The salt revealing the amount commitment if the lock was shielded.
+        salt: Option<[u8; 32]>,
+        /// The amount still held by the lock after this redemption.
+        remaining: Balance,
+        /// The part of `amount` paid to the fee collector; the redeemer received the rest.
+        fee: Balance,
+        /// Timestamp of the block the redemption happened in.
+        timestamp: Timestamp,
+        /// Number of the block the redemption happened in.
+        block_number: BlockNumber,
+        /// Reward paid on top of `amount` from the reward pool.
+        reward: Balance,
+        /// Part of the payout withheld to keep the contract at the existential deposit,
+        /// under `ReapPolicy::PayAvailable`.
+        shortfall: Balance,
+        /// Blake2x256 hash of the lock's memo, if it has one.
+        #[ink(topic)]
+        memo_hash: Option<[u8; 32]>,
+    }
+
+    /// Event emitted when a lock is redeemed with `redeem_percent`, next to the `Redeemed`
+    /// event of the redemption itself.
+    #[ink(event)]
+    pub struct RedeemedPercent {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// The id of the redeemed lock.
+        #[ink(topic)]
+        lock_id: u64,
+        /// The share of the locked amount requested, in basis points.
+        bps: u16,
+        /// The amount redeemed, including the fee.
+        amount: Balance,
+        /// Whether the lock was closed because the remainder would have been dust.
+        swept: bool,
+    }
+
+    /// Event emitted for each recipient paid by the redemption of a `lock_with_split` lock.
+    #[ink(event)]
+    pub struct PayoutSent {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// The id of the redeemed lock.
+        #[ink(topic)]
+        lock_id: u64,
+        /// The recipient.
+        #[ink(topic)]
+        to: AccountId,
+        /// The recipient's share of the payout.
+        amount: Balance,
+    }
+
+    /// Event emitted when a lock is cancelled by its locker.
+    #[ink(event)]
+    pub struct Cancelled {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// The id of the cancelled lock.
+        #[ink(topic)]
+        lock_id: u64,
+        /// The account ID of the user who locked and took back the assets.
+        #[ink(topic)]
+        locker: AccountId,
+        /// The amount of assets that were returned.
+        amount: Balance,
+    }
+
+    /// Event emitted when the beneficiary of a lock turns it down with `reject`.
+    #[ink(event)]
+    pub struct Rejected {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// The id of the rejected lock.
+        #[ink(topic)]
+        lock_id: u64,
+        /// The account ID of the user who locked the assets and got them back.
+        #[ink(topic)]
+        locker: AccountId,
+        /// The beneficiary who turned the lock down.
+        #[ink(topic)]
+        beneficiary: AccountId,
+        /// The amount of assets returned to the locker.
+        amount: Balance,
+    }
+
+    /// Event emitted when the recovery account takes over an inactive lock with `recover`.
+    #[ink(event)]
+    pub struct Recovered {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// The id of the recovered lock.
+        #[ink(topic)]
+        lock_id: u64,
+        /// The account ID of the user who locked the assets.
+        #[ink(topic)]
+        original_locker: AccountId,
+        /// The account the assets were sent to.
+        #[ink(topic)]
+        recovery_account: AccountId,
+        /// The amount of assets recovered.
+        amount: Balance,
+    }
+
+    /// Event emitted when the locker or beneficiary proposes a later expiry.
+    #[ink(event)]
+    pub struct ExtensionProposed {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// The id of the lock.
+        #[ink(topic)]
+        lock_id: u64,
+        /// The party who proposed the extension.
+        #[ink(topic)]
+        proposer: AccountId,
+        /// The proposed expiry.
+        new_expiry: Timestamp,
+    }
+
+    /// Event emitted when the other party accepts an extension and the expiry moves.
+    #[ink(event)]
+    pub struct ExtensionAccepted {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// The id of the lock.
+        #[ink(topic)]
+        lock_id: u64,
+        /// The party who accepted the extension.
+        #[ink(topic)]
+        accepted_by: AccountId,
+        /// The new expiry of the lock.
+        new_expiry: Timestamp,
+    }
+
+    /// Event emitted when an expired lock is reclaimed by its locker.
+    #[ink(event)]
+    pub struct Refunded {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// The id of the refunded lock.
+        #[ink(topic)]
+        lock_id: u64,
+        /// The account ID of the user who locked and reclaimed the assets.
+        #[ink(topic)]
+        locker: AccountId,
+        /// The amount of assets that were refunded.
+        amount: Balance,
+    }
+
+    /// Event emitted when an expired lock is returned to its locker by `finalize_expired`.
+    #[ink(event)]
+    pub struct ExpiredFinalized {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// The id of the finalized lock.
+        #[ink(topic)]
+        lock_id: u64,
+        /// The account ID of the user who locked the assets and got them back.
+        #[ink(topic)]
+        locker: AccountId,
+        /// The amount returned to the locker, after the bounty.
+        amount: Balance,
+        /// The account that called `finalize_expired`.
+        #[ink(topic)]
+        finalizer: AccountId,
+        /// The bounty paid to the finalizer; zero if it was the locker.
+        bounty: Balance,
+    }
+
+    /// Event emitted when a beneficiary schedules a claim with `schedule_claim`.
+    #[ink(event)]
+    pub struct ClaimScheduled {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// The id of the lock to be claimed.
+        #[ink(topic)]
+        lock_id: u64,
+        /// The beneficiary who scheduled the claim.
+        #[ink(topic)]
+        beneficiary: AccountId,
+        /// Timestamp from which anyone may execute the claim.
+        execute_after: Timestamp,
+        /// Part of the payout offered to the account executing the claim.
+        tip: Balance,
+    }
+
+    /// Event emitted when a beneficiary cancels their scheduled claim.
+    #[ink(event)]
+    pub struct ScheduledClaimCancelled {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// The id of the lock whose claim was cancelled.
+        #[ink(topic)]
+        lock_id: u64,
+        /// The beneficiary who cancelled the claim.
+        #[ink(topic)]
+        beneficiary: AccountId,
+    }
+
+    /// Event emitted when a keeper executes a scheduled claim, next to the `Redeemed` event
+    /// of the redemption itself.
+    #[ink(event)]
+    pub struct ScheduledClaimExecuted {
+        /// The chain this instance was deployed on.
+        chain_id: u32,
+        /// The id of the claimed lock.
+        #[ink(topic)]
+        lock_id: u64,
+        /// The account that executed the claim.
+        #[ink(topic)]
+        keeper: AccountId,
+        /// The tip paid to the keeper; the beneficiary received the rest of the payout.
+        tip: Balance,
+    }
+
+    //----------------------------------
+    // Default Implementation
+    //----------------------------------
+    /// Provides default initialization values for the contract.
+    ///
+    /// When the contract is first deployed, it starts with no locks; the first lock gets id 1.
+    impl Default for LockUnlockSmartContract {
+        fn default() -> Self {
+            Self {
+                locks: Mapping::default(),
+                next_lock_id: 1,
+                entered: Lazy::new(),
+                canary_max_amount: None,
+                sunset_at: None,
+                chain_id: 0,
+                genesis_marker: Lazy::new(),
+                native_metadata: Lazy::new(),
+                token_metadata_cache: Mapping::default(),
+                owner: None,
+                paused: false,
+                fee_bps: 0,
+                fee_collector: AccountId::from([0; 32]),
+                history: Mapping::default(),
+                history_len: 0,
+                history_start: 0,
+                min_amount: 0,
+                max_amount: None,
+                active_locks: 0,
+                open_locks: Mapping::default(),
+                open_position: Mapping::default(),
+                native_locked: 0,
+                earning_locked: 0,
+                earning_weight: 0,
+                termination_grace: Lazy::new(),
+                stats: ContractStats::default(),
+                allowlist: Mapping::default(),
+                allowlist_enabled: false,
+                reward_pool: 0,
+                rate_bps_per_day: 0,
+                storage_version: STORAGE_VERSION,
+                commitments: Mapping::default(),
+                min_commit_age_blocks: DEFAULT_MIN_COMMIT_AGE_BLOCKS,
+                reap_policy: ReapPolicy::Fail,
+                finalize_grace: Lazy::new(),
+                finalize_bounty: Lazy::new(),
+                surplus_recipient: Lazy::new(),
+                scheduled_claims: Mapping::default(),
+                lock_commitments: Mapping::default(),
+                dispute_bond_bps: 0,
+                dispute_bond_floor: 0,
+            }
+        }
+    }
+
+    impl LockUnlockSmartContract {
+        /// Constructor that initializes the contract with default values.
+        ///
+        /// This function is called when the contract is deployed. `chain_id` and
+        /// `genesis_marker` identify the deployment to multi-chain indexers, while
+        /// `native_decimals` and `native_symbol` tell front-ends how to display amounts.
+        /// None of them can be changed afterwards.
+        #[ink(constructor)]
+        pub fn new(
+            chain_id: u32,
+            genesis_marker: [u8; 32],
+            native_decimals: u8,
+            native_symbol: Vec<u8>,
+        ) -> Self {
+            let mut contract = Self {
+                chain_id,
+                owner: Some(Self::env().caller()),
+                fee_collector: Self::env().caller(),
+                ..Self::default()
+            };
+            contract.genesis_marker.set(&genesis_marker);
+            contract.native_metadata.set(&TokenMetadata {
+                decimals: native_decimals,
+                symbol: native_symbol,
+            });
+            contract
+        }
+
+        /// Constructor for a deployment with lock amount limits.
+        ///
+        /// Native locks must be created with at least `min_amount` and may never hold more
+        /// than `max_amount`, if set. The chain identity and native metadata come first, as
+        /// in `new`, because they can only be set at instantiation; `new` deploys without
+        /// limits.
+        #[ink(constructor)]
+        pub fn new_with_limits(
+            chain_id: u32,
+            genesis_marker: [u8; 32],
+            native_decimals: u8,
+            native_symbol: Vec<u8>,
+            min_amount: Balance,
+            max_amount: Option<Balance>,
+        ) -> Self {
+            Self {
+                min_amount,
+                max_amount,
+                ..Self::new(chain_id, genesis_marker, native_decimals, native_symbol)
+            }
+        }
+
+        /// Constructor setting how many blocks a redemption commitment must wait before
+        /// `reveal`; `new` uses `DEFAULT_MIN_COMMIT_AGE_BLOCKS`.
+        #[ink(constructor)]
+        pub fn new_with_commit_age(
+            chain_id: u32,
+            genesis_marker: [u8; 32],
+            native_decimals: u8,
+            native_symbol: Vec<u8>,
+            min_commit_age_blocks: u32,
+        ) -> Self {
+            Self {
+                min_commit_age_blocks,
+                ..Self::new(chain_id, genesis_marker, native_decimals, native_symbol)
+            }
+        }
+
+        /// Constructor choosing how a redemption that would take the contract below the
+        /// existential deposit is handled; `new` uses `ReapPolicy::Fail`.
+        #[ink(constructor)]
+        pub fn new_with_reap_policy(
+            chain_id: u32,
+            genesis_marker: [u8; 32],
+            native_decimals: u8,
+            native_symbol: Vec<u8>,
+            reap_policy: ReapPolicy,
+        ) -> Self {
+            Self {
+                reap_policy,
+                ..Self::new(chain_id, genesis_marker, native_decimals, native_symbol)
+            }
+        }
+
+        /// Constructor setting how long after its expiry anyone may `finalize_expired` a
+        /// lock, and the bounty they are paid for it; `new` uses `DEFAULT_FINALIZE_GRACE`
+        /// and no bounty.
+        #[ink(constructor)]
+        pub fn new_with_finalize_bounty(
+            chain_id: u32,
+            genesis_marker: [u8; 32],
+            native_decimals: u8,
+            native_symbol: Vec<u8>,
+            finalize_grace: Timestamp,
+            finalize_bounty: FinalizeBounty,
+        ) -> Self {
+            let mut contract = Self::new(chain_id, genesis_marker, native_decimals, native_symbol);
+            contract.finalize_grace.set(&finalize_grace);
+            contract.finalize_bounty.set(&finalize_bounty);
+            contract
+        }
+
+        /// Constructor that also creates the first lock, from the instantiation endowment.
+        ///
+        /// The endowment minus the existential deposit, which stays with the contract
+        /// account, is locked behind `hash` with the deployer as locker, so the contract
+        /// never exists without it. The `Locked` event is emitted during instantiation.
+        ///
+        /// **Requirements:**
+        /// - The endowment must exceed the existential deposit (`NoAssetsSent`).
+        #[ink(constructor, payable)]
+        pub fn new_and_lock(
+            chain_id: u32,
+            genesis_marker: [u8; 32],
+            native_decimals: u8,
+            native_symbol: Vec<u8>,
+            hash: [u8; 32],
+        ) -> Result<Self, Error> {
+            let amount = Self::env()
+                .transferred_value()
+                .saturating_sub(Self::env().minimum_balance());
+            if amount == 0 {
+                return Err(Error::NoAssetsSent);
+            }
+            let mut contract = Self::new(chain_id, genesis_marker, native_decimals, native_symbol);
+            let lock = contract.native_lock(amount, hash, None);
+            contract.insert_lock(lock);
+            Ok(contract)
+        }
+
+        /// Constructor for a canary deployment.
+        ///
+        /// `canary_max_amount` caps the value of each lock and `sunset_at` stops new locks
+        /// from being accepted once reached. Redemptions are never affected, so funds locked
+        /// before the sunset can always be withdrawn.
+        #[ink(constructor)]
+        pub fn new_canary(
+            chain_id: u32,
+            genesis_marker: [u8; 32],
+            native_decimals: u8,
+            native_symbol: Vec<u8>,
+            canary_max_amount: Option<Balance>,
+            sunset_at: Option<Timestamp>,
+        ) -> Self {
+            Self {
+                canary_max_amount,
+                sunset_at,
+                ..Self::new(chain_id, genesis_marker, native_decimals, native_symbol)
+            }
+        }
+
+        /// Locks the assets sent by the caller like `lock`, but on behalf of `locker`.
+        ///
+        /// `locker` holds every right the caller would have had with `lock`: it redeems when
+        /// no beneficiary is named, and it cancels and refunds. The caller keeps none of them
+        /// and is only recorded as `funded_by` in the `Locked` event. With `locker` equal to
+        /// the caller this is the same as `lock`.
+        ///
+        /// **Requirements:**
+        /// - Same as `lock`.
+        /// - `locker` must not be the zero address or this contract (`InvalidLocker`).
+        #[ink(message, payable)]
+        pub fn lock_for(
+            &mut self,
+            locker: AccountId,
+            hash: [u8; 32],
+            beneficiary: Option<AccountId>,
+            expiry: Option<Timestamp>,
+            cancel_grace: Option<Timestamp>,
+            redeemer_pubkey: Option<[u8; 33]>,
+        ) -> Result<LockReceipt, Error> {
+            if locker == AccountId::from([0; 32]) || locker == self.env().account_id() {
+                return Err(Error::InvalidLocker);
+            }
+            let amount = self.check_deposit()?;
+            let lock_id = self.insert_lock(LockInfo {
+                locker,
+                expiry,
+                cancel_grace,
+                redeemer_pubkey,
+                ..self.native_lock(amount, hash, beneficiary)
+            });
+            Ok(self.receipt(lock_id))
+        }
+
+        /// Locks the assets sent by the caller under a list of `conditions`, all of which
+        /// must hold for redemption, and returns a receipt for the new lock.
+        ///
+        /// This is `lock` with its terms spelled out as data: exactly one
+        /// `Condition::MessageHash` sets the secret hash, and at most one
+        /// `Condition::Beneficiary` and one `Condition::Timelock` set the beneficiary and
+        /// expiry. `Condition::And` only groups conditions. The lock is stored and redeemed
+        /// like any other; `lock_conditions` reads its conditions back.
+        ///
+        /// **Requirements:**
+        /// - Same as `lock`.
+        /// - At most `MAX_CONDITIONS` conditions, nested ones included, nested at most
+        ///   `MAX_CONDITION_DEPTH` deep (`TooManyConditions`).
+        /// - Exactly one hash and no repeated beneficiary or timelock (`InvalidConditions`).
+        #[ink(message, payable)]
+        pub fn lock_with(&mut self, conditions: Vec<Condition>) -> Result<LockReceipt, Error> {
+            let amount = self.check_deposit()?;
+            let lock = self.conditional_lock(amount, &conditions)?;
+            let lock_id = self.insert_lock(lock);
+            Ok(self.receipt(lock_id))
+        }
+
+        /// Redeems `amount` of lock `lock_id`, or all of it, with the first of `proofs`
+        /// that satisfies it.
+        ///
+        /// **Requirements:**
+        /// - Same as `redeem`, with each `Proof::Preimage` checked like a preimage and each
+        ///   `Proof::Signature` like a signature for `redeem_with_signature`. If no proof
+        ///   satisfies the lock, the error for the last one is returned.
+        /// - At most `MAX_CONDITIONS` proofs (`TooManyConditions`).
+        ///
+        /// **Effects:**
+        /// - Same as `redeem`.
+        #[ink(message)]
+        pub fn redeem_with(
+            &mut self,
+            lock_id: u64,
+            proofs: Vec<Proof>,
+            amount: Option<Balance>,
+        ) -> Result<(), Error> {
+            if proofs.len() > MAX_CONDITIONS as usize {
+                return Err(Error::TooManyConditions);
+            }
+            self.guarded(|this| this.redeem_inner(lock_id, proofs, amount))
+        }
+
+        /// Redeems `bps` basis points of what lock `lock_id` currently holds.
+        ///
+        /// The amount is rounded down, so it never exceeds what is locked, and 10_000 bps
+        /// redeems everything. If the remainder of a native lock would fall below the
+        /// minimum lock amount, the whole lock is redeemed instead, so no dust is left
+        /// behind.
+        ///
+        /// **Requirements:**
+        /// - `bps` must be between 1 and 10_000 (`InvalidPercent`).
+        /// - Same as `redeem` with the resulting amount; a shielded lock can only be
+        ///   redeemed whole (`Shielded`).
+        ///
+        /// **Effects:**
+        /// - Same as `redeem`.
+        /// - Emits a `RedeemedPercent` event.
+        #[ink(message)]
+        pub fn redeem_percent(
+            &mut self,
+            lock_id: u64,
+            preimage: Vec<u8>,
+            bps: u16,
+        ) -> Result<(), Error> {
+            if bps == 0 || bps > 10_000 {
+                return Err(Error::InvalidPercent);
+            }
+            self.guarded(|this| {
+                let lock = this.locks.get(lock_id).ok_or(Error::LockNotFound)?;
+                let share = Balance::from(bps);
+                let mut amount =
+                    lock.amount / 10_000 * share + lock.amount % 10_000 * share / 10_000;
+                let native = lock.token.is_none() && lock.asset_id.is_none();
+                let swept = native
+                    && lock.shield_salt.is_none()
+                    && amount < lock.amount
+                    && lock.amount - amount < this.min_amount;
+                if swept {
+                    amount = lock.amount;
+                }
+
+                this.redeem_inner(lock_id, [Proof::Preimage(preimage)], Some(amount))?;
+                this.env().emit_event(RedeemedPercent {
+                    chain_id: this.chain_id,
+                    lock_id,
+                    bps,
+                    amount,
+                    swept,
+                });
+                Ok(())
+            })
+        }
+
+        /// Locks the assets sent by the caller like `lock`, but never reverts on rejection.
+        ///
+        /// A lock refused by `lock` is reported as `LockOutcome::Rejected` instead: the sent
+        /// assets are transferred back to the caller within the same call, storage is left
+        /// untouched and a `LockRejected` event records the reason. Sending nothing
+        /// returns `LockOutcome::NothingSent`.
+        ///
+        /// Only fails, with `TransferFailed`, if the rejected assets cannot be sent back; the
+        /// call then reverts, so the caller keeps them.
+        #[ink(message, payable)]
+        pub fn lock_or_refund(
+            &mut self,
+            hash: [u8; 32],
+            beneficiary: Option<AccountId>,
+            expiry: Option<Timestamp>,
+            cancel_grace: Option<Timestamp>,
+            redeemer_pubkey: Option<[u8; 33]>,
+        ) -> Result<LockOutcome, Error> {
+            let amount = self.env().transferred_value();
+            match self.lock_inner(
+                hash,
+                beneficiary,
+                expiry,
+                cancel_grace,
+                redeemer_pubkey,
+                None,
+            ) {
+                Ok(lock_id) => Ok(LockOutcome::Locked { lock_id, amount }),
+                Err(Error::NoAssetsSent) => Ok(LockOutcome::NothingSent),
+                Err(reason) => {
+                    let caller = self.env().caller();
+                    self.env()
+                        .transfer(caller, amount)
+                        .map_err(|_| Error::TransferFailed)?;
+                    self.env().emit_event(LockRejected {
+                        chain_id: self.chain_id,
+                        caller,
+                        reason,
+                        refunded: amount,
+                    });
+                    Ok(LockOutcome::Rejected {
+                        reason,
+                        refunded: amount,
+                    })
+                }
+            }
+        }
+
+        /// Locks the assets sent by the caller without publishing the amount.
+        ///
+        /// Behaves like `lock`, except the `Locked` event carries
+        /// `amount_commitment(amount, salt)` instead of the amount. The amount and the
+        /// salt are revealed in the `Redeemed` event, so anyone can check them against the
+        /// commitment. The contract always stores the real amount for its own accounting.
+        #[ink(message, payable)]
+        pub fn lock_shielded(
+            &mut self,
+            hash: [u8; 32],
+            beneficiary: Option<AccountId>,
+            expiry: Option<Timestamp>,
+            cancel_grace: Option<Timestamp>,
+            salt: [u8; 32],
+        ) -> Result<u64, Error> {
+            self.lock_inner(hash, beneficiary, expiry, cancel_grace, None, Some(salt))
+        }
+
+        /// Locks `amount` of the PSP22 `token` held by the caller.
+        ///
+        /// Behaves like `lock`, except the assets are pulled from the caller with
+        /// `transfer_from`, so the caller must first approve this contract for at least
+        /// `amount`. Redeeming, refunding or cancelling the lock pays out in the same token.
+        ///
+        /// **Requirements:**
+        /// - `amount` must be non-zero.
+        /// - The contract must not be paused.
+        /// - On a canary deployment, the sunset timestamp must not have been reached. The
+        ///   canary ceiling applies to native locks only.
+        /// - The token transfer must succeed, otherwise fails with `TokenTransferFailed`.
+        ///
+        /// **Effects:**
+        /// - Stores a new `LockInfo` recording `token` alongside the amount.
+        /// - Emits a `Locked` event.
+        #[ink(message)]
+        pub fn lock_token(
+            &mut self,
+            token: AccountId,
+            amount: Balance,
+            hash: [u8; 32],
+            beneficiary: Option<AccountId>,
+            expiry: Option<Timestamp>,
+            cancel_grace: Option<Timestamp>,
+        ) -> Result<u64, Error> {
+            self.guarded(|this| {
+                this.lock_token_inner(token, amount, hash, beneficiary, expiry, cancel_grace)
+            })
+        }
+
+        /// Locks the assets sent by the caller so they vest linearly over `duration`.
+        ///
+        /// From now on `claimable` grows in proportion to the elapsed time and the whole
+        /// amount is claimable at `start + duration`. The lock is settled with `claim` by
+        /// the `beneficiary` if given, otherwise by the locker; `redeem` and `top_up` do not
+        /// apply to it.
+        ///
+        /// **Requirements:**
+        /// - Same as `lock`.
+        ///
+        /// **Effects:**
+        /// - Stores a new `LockInfo` with its `Vesting` schedule.
+        /// - Emits a `Locked` and a `VestingStarted` event.
+        #[ink(message, payable)]
+        pub fn lock_with_vesting(
+            &mut self,
+            beneficiary: Option<AccountId>,
+            duration: Timestamp,
+        ) -> Result<u64, Error> {
+            let amount = self.check_deposit()?;
+            let start = self.env().block_timestamp();
+            let lock = LockInfo {
+                vesting: Some(Vesting {
+                    start,
+                    duration,
+                    total: amount,
+                    stream: false,
+                }),
+                ..self.native_lock(amount, [0; 32], beneficiary)
+            };
+            let locker = lock.locker;
+            let lock_id = self.insert_lock(lock);
+
+            self.env().emit_event(VestingStarted {
+                chain_id: self.chain_id,
+                lock_id,
+                locker,
+                beneficiary,
+                amount,
+                start,
+                duration,
+            });
+
+            Ok(lock_id)
+        }
+
+        /// Returns the amount of vesting lock `lock_id` that can be claimed now.
+        ///
+        /// Zero if the lock does not exist or is not a vesting lock.
+        #[ink(message)]
+        pub fn claimable(&self, lock_id: u64) -> Balance {
+            self.locks
+                .get(lock_id)
+                .map_or(0, |lock| self.claimable_of(&lock))
+        }
+
+        /// Transfers the currently claimable part of vesting lock `lock_id`.
+        ///
+        /// **Requirements:**
+        /// - The lock must exist and be a vesting lock (`NotVesting`).
+        /// - The caller must be the beneficiary if the lock names one, otherwise the locker.
+        /// - Something must be claimable (`NothingToClaim`).
+        ///
+        /// **Effects:**
+        /// - Transfers the claimable amount to the caller, minus the redemption fee.
+        /// - Removes the lock once everything has been claimed.
+        /// - Emits a `Claimed` event.
+        #[ink(message)]
+        pub fn claim(&mut self, lock_id: u64) -> Result<(), Error> {
+            self.guarded(|this| this.claim_inner(lock_id))
+        }
+
+        /// Locks the assets sent by the caller as a payment stream to `beneficiary` over
+        /// `duration`.
+        ///
+        /// A stream is a vesting lock the beneficiary draws from with `withdraw_stream`,
+        /// and which the locker may end at any time with `cancel_stream`.
+        ///
+        /// **Requirements:**
+        /// - Same as `lock`.
+        ///
+        /// **Effects:**
+        /// - Stores a new `LockInfo` with its `Vesting` schedule marked as a stream.
+        /// - Emits a `Locked` and a `StreamOpened` event.
+        #[ink(message, payable)]
+        pub fn open_stream(
+            &mut self,
+            beneficiary: AccountId,
+            duration: Timestamp,
+        ) -> Result<u64, Error> {
+            let amount = self.check_deposit()?;
+            let start = self.env().block_timestamp();
+            let lock = LockInfo {
+                vesting: Some(Vesting {
+                    start,
+                    duration,
+                    total: amount,
+                    stream: true,
+                }),
+                ..self.native_lock(amount, [0; 32], Some(beneficiary))
+            };
+            let locker = lock.locker;
+            let lock_id = self.insert_lock(lock);
+
+            self.env().emit_event(StreamOpened {
+                chain_id: self.chain_id,
+                lock_id,
+                locker,
+                beneficiary,
+                amount,
+                start,
+                duration,
+            });
+
+            Ok(lock_id)
+        }
+
+        /// Transfers the part of stream `lock_id` streamed but not yet withdrawn.
+        ///
+        /// **Requirements:**
+        /// - Same as `claim`, and the lock must be a stream (`NotStream`).
+        ///
+        /// **Effects:**
+        /// - Same as `claim`, emitting a `StreamWithdrawn` event instead of `Claimed`.
+        #[ink(message)]
+        pub fn withdraw_stream(&mut self, lock_id: u64) -> Result<(), Error> {
+            self.stream_of(lock_id)?;
+            self.guarded(|this| this.claim_inner(lock_id))
+        }
+
+        /// Ends stream `lock_id`, paying the beneficiary what has streamed so far and
+        /// returning the rest to the locker.
+        ///
+        /// **Requirements:**
+        /// - The lock must exist and be a stream (`NotStream`).
+        /// - The caller must be its locker.
+        ///
+        /// **Effects:**
+        /// - Transfers the streamed, unwithdrawn part to the beneficiary, minus the
+        ///   redemption fee, and the remainder to the `locker`.
+        /// - Removes the lock from storage.
+        /// - Emits a `StreamCancelled` event.
+        #[ink(message)]
+        pub fn cancel_stream(&mut self, lock_id: u64) -> Result<(), Error> {
+            self.guarded(|this| this.cancel_stream_inner(lock_id))
+        }
+
+        /// Locks the assets sent by the caller in a two-party escrow with `counterparty`.
+        ///
+        /// The counterparty must `accept` by `deadline`. Once accepted, the escrow can only
+        /// be settled with `release`, triggered by the party that does not receive the funds
+        /// according to `direction`. If it is never accepted, the locker can `withdraw` once
+        /// the deadline has passed. `redeem`, `cancel` and `top_up` do not apply to it.
+        ///
+        /// **Requirements:**
+        /// - Same as `lock`.
+        ///
+        /// **Effects:**
+        /// - Stores a new `LockInfo` with its `Escrow` terms.
+        /// - Emits a `Locked` and an `EscrowOpened` event.
+        #[ink(message, payable)]
+        pub fn lock_escrow(
+            &mut self,
+            counterparty: AccountId,
+            direction: EscrowDirection,
+            deadline: Timestamp,
+        ) -> Result<u64, Error> {
+            let amount = self.check_deposit()?;
+            let lock = LockInfo {
+                escrow: Some(Escrow {
+                    counterparty,
+                    direction,
+                    deadline,
+                    accepted: false,
+                }),
+                ..self.native_lock(amount, [0; 32], None)
+            };
+            let locker = lock.locker;
+            let lock_id = self.insert_lock(lock);
+
+            self.env().emit_event(EscrowOpened {
+                chain_id: self.chain_id,
+                lock_id,
+                locker,
+                counterparty,
+                direction,
+                deadline,
+            });
+
+            Ok(lock_id)
+        }
+
+        /// Accepts escrow `lock_id` as its counterparty.
+        ///
+        /// Fails with `NotEscrow` for other locks, `NotCounterparty` unless called by the
+        /// counterparty, `AlreadyAccepted` if accepted before, and `DeadlinePassed` from the
+        /// deadline on. Emits an `EscrowAccepted` event.
+        #[ink(message)]
+        pub fn accept(&mut self, lock_id: u64) -> Result<(), Error> {
+            let (mut lock, mut escrow) = self.escrow_of(lock_id)?;
+            if self.env().caller() != escrow.counterparty {
+                return Err(Error::NotCounterparty);
+            }
+            if escrow.accepted {
+                return Err(Error::AlreadyAccepted);
+            }
+            if self.env().block_timestamp() >= escrow.deadline {
+                return Err(Error::DeadlinePassed);
+            }
+
+            escrow.accepted = true;
+            lock.escrow = Some(escrow);
+            self.store_lock(lock_id, &lock);
+
+            self.env().emit_event(EscrowAccepted {
+                chain_id: self.chain_id,
+                lock_id,
+                counterparty: escrow.counterparty,
+            });
+
+            Ok(())
+        }
+
+        /// Pays out accepted escrow `lock_id` according to its direction.
+        ///
+        /// With `ToLocker` the counterparty releases the funds to the locker; with
+        /// `ToCounterparty` the locker releases them to the counterparty. Fails with
+        /// `NotAccepted` before acceptance, and with `NotCounterparty` or `NotLocker` if the
+        /// wrong party calls. The redemption fee applies. Emits an `EscrowReleased` event.
+        #[ink(message)]
+        pub fn release(&mut self, lock_id: u64) -> Result<(), Error> {
+            self.guarded(|this| this.release_inner(lock_id))
+        }
+
+        /// Returns the funds of escrow `lock_id` to the locker if it was never accepted.
+        ///
+        /// Fails with `NotLocker` unless called by the locker, `AlreadyAccepted` once
+        /// accepted, and `NotExpired` before the deadline. Emits an `EscrowWithdrawn` event.
+        #[ink(message)]
+        pub fn withdraw(&mut self, lock_id: u64) -> Result<(), Error> {
+            self.guarded(|this| this.withdraw_inner(lock_id))
+        }
+
+        /// Locks the assets sent by the caller for `beneficiary` under `hash`, with `arbiter`
+        /// as a third party for disputes.
+        ///
+        /// Until a dispute is raised the lock behaves like one created with `lock`: the
+        /// beneficiary redeems with the preimage, and the locker can cancel within
+        /// `cancel_grace`. Once the locker or beneficiary calls `raise_dispute`, those paths
+        /// are frozen and only the arbiter can settle the lock with `resolve`.
+        ///
+        /// **Requirements:**
+        /// - Same as `lock`.
+        ///
+        /// **Effects:**
+        /// - Stores a new `LockInfo` with its `Arbitration`.
+        /// - Emits a `Locked` event.
+        #[ink(message, payable)]
+        pub fn lock_with_arbiter(
+            &mut self,
+            hash: [u8; 32],
+            beneficiary: AccountId,
+            arbiter: AccountId,
+            cancel_grace: Option<Timestamp>,
+        ) -> Result<u64, Error> {
+            let amount = self.check_deposit()?;
+            let lock = LockInfo {
+                cancel_grace,
+                arbitration: Some(Arbitration {
+                    arbiter,
+                    disputed_by: None,
+                    bond: 0,
+                }),
+                ..self.native_lock(amount, hash, Some(beneficiary))
+            };
+            Ok(self.insert_lock(lock))
+        }
+
+        /// Freezes arbitrated lock `lock_id` until its arbiter resolves it.
+        ///
+        /// Only the locker or the beneficiary may raise a dispute, and only once. The caller
+        /// must send exactly the bond `dispute_bond` sets for the locked amount
+        /// (`InvalidBond`); it goes to whoever receives the funds when the lock is settled,
+        /// so a party disputing in bad faith forfeits it to the other. Emits a
+        /// `DisputeRaised` event.
+        #[ink(message, payable)]
+        pub fn raise_dispute(&mut self, lock_id: u64) -> Result<(), Error> {
+            let caller = self.env().caller();
+            let mut lock = self.locks.get(lock_id).ok_or(Error::LockNotFound)?;
+            let mut arbitration = lock.arbitration.ok_or(Error::NotArbitrated)?;
+            if caller != lock.locker && Some(caller) != lock.beneficiary {
+                return Err(Error::NotParty);
+            }
+            if arbitration.disputed_by.is_some() {
+                return Err(Error::Disputed);
+            }
+            let bond = self.dispute_bond_on(lock.amount);
+            if self.env().transferred_value() != bond {
+                return Err(Error::InvalidBond);
+            }
+
+            arbitration.disputed_by = Some(caller);
+            arbitration.bond = bond;
+            lock.arbitration = Some(arbitration);
+            self.store_lock(lock_id, &lock);
+
+            self.env().emit_event(DisputeRaised {
+                chain_id: self.chain_id,
+                lock_id,
+                by: caller,
+            });
+
+            Ok(())
+        }
+
+        /// Settles disputed lock `lock_id`, sending its funds to the beneficiary if
+        /// `to_beneficiary`, otherwise back to the locker.
+        ///
+        /// Only the lock's arbiter may call this, and only after a dispute was raised. The
+        /// redemption fee applies. The dispute bond goes to the winner: back to the
+        /// disputer if they won, otherwise to the counterparty. Emits a `Resolved` event.
+        #[ink(message)]
+        pub fn resolve(&mut self, lock_id: u64, to_beneficiary: bool) -> Result<(), Error> {
+            self.guarded(|this| this.resolve_inner(lock_id, to_beneficiary))
+        }
+
+        /// Locks the assets sent by the caller behind `hash`, to be paid out to several
+        /// `recipients` on redemption, and returns the id of the new lock.
+        ///
+        /// Each recipient is given with its share in basis points. The locker redeems as
+        /// with `lock`, but the payout, net of the fee and including any reward, is divided
+        /// between the recipients; the rounding dust goes to the first one. If any transfer
+        /// fails, the whole redemption is undone. A `PayoutSent` event is emitted for each
+        /// recipient, after the `Redeemed` event.
+        ///
+        /// **Requirements:**
+        /// - Same as `lock`.
+        /// - Between one and `MAX_SPLIT_RECIPIENTS` recipients, with shares summing to
+        ///   exactly 10 000 basis points (`InvalidSplit`).
+        #[ink(message, payable)]
+        pub fn lock_with_split(
+            &mut self,
+            hash: [u8; 32],
+            recipients: Vec<(AccountId, u16)>,
+        ) -> Result<u64, Error> {
+            let total: u32 = recipients.iter().map(|&(_, bps)| u32::from(bps)).sum();
+            if recipients.is_empty() || recipients.len() > MAX_SPLIT_RECIPIENTS || total != 10_000 {
+                return Err(Error::InvalidSplit);
+            }
+            let amount = self.check_deposit()?;
+            Ok(self.insert_lock(LockInfo {
+                split: recipients,
+                ..self.native_lock(amount, hash, None)
+            }))
+        }
+
+        /// Locks the assets sent by the caller like `lock`, with `memo` attached, and
+        /// returns the id of the new lock.
+        ///
+        /// The memo is stored with the lock and returned by `get_lock_info`. The `Locked`
+        /// and `Redeemed` events carry its Blake2x256 hash as `memo_hash`, an indexed topic,
+        /// so indexers can filter by it.
+        ///
+        /// **Requirements:**
+        /// - Same as `lock`.
+        /// - `memo` must be at most `MAX_MEMO_LEN` bytes long (`MemoTooLong`).
+        #[ink(message, payable)]
+        pub fn lock_with_memo(
+            &mut self,
+            hash: [u8; 32],
+            beneficiary: Option<AccountId>,
+            expiry: Option<Timestamp>,
+            memo: String,
+        ) -> Result<u64, Error> {
+            if memo.len() > MAX_MEMO_LEN {
+                return Err(Error::MemoTooLong);
+            }
+            let amount = self.check_deposit()?;
+            Ok(self.insert_lock(LockInfo {
+                expiry,
+                memo: Some(memo),
+                ..self.native_lock(amount, hash, beneficiary)
+            }))
+        }
+
+        /// Locks the assets sent by the caller behind `hash`, redeemable by the locker or
+        /// any of `redeemers`, and returns the id of the new lock.
+        ///
+        /// Whoever redeems is paid; the `Redeemed` event records them as `redeemer` next
+        /// to the `locker`. With no redeemers this is the same as `lock` without a
+        /// beneficiary.
+        ///
+        /// **Requirements:**
+        /// - Same as `lock`.
+        /// - At most `MAX_REDEEMERS` redeemers (`TooManyRedeemers`).
+        ///
+        /// **Effects:**
+        /// - Stores a new `LockInfo` with its `redeemers` and emits a `Locked` event.
+        #[ink(message, payable)]
+        pub fn lock_with_redeemers(
+            &mut self,
+            hash: [u8; 32],
+            redeemers: Vec<AccountId>,
+            expiry: Option<Timestamp>,
+        ) -> Result<u64, Error> {
+            if redeemers.len() > MAX_REDEEMERS as usize {
+                return Err(Error::TooManyRedeemers);
+            }
+            let amount = self.check_deposit()?;
+            Ok(self.insert_lock(LockInfo {
+                expiry,
+                redeemers,
+                ..self.native_lock(amount, hash, None)
+            }))
+        }
+
+        /// Locks the assets sent by the caller until both `approver_a` and `approver_b`
+        /// approve their return.
+        ///
+        /// Each approver calls `approve_redeem` and may `revoke_approval` until the lock is
+        /// settled; once both approve, `execute_redeem` pays the funds back to the locker.
+        /// `redeem` does not apply to it. The locker can still `cancel` it, which discards
+        /// the approvals with the lock.
+        ///
+        /// **Requirements:**
+        /// - Same as `lock`.
+        /// - The approvers must be two different accounts (`DuplicateApprover`).
+        ///
+        /// **Effects:**
+        /// - Stores a new `LockInfo` with its `Approvals` and emits a `Locked` event.
+        #[ink(message, payable)]
+        pub fn lock_with_approvers(
+            &mut self,
+            approver_a: AccountId,
+            approver_b: AccountId,
+        ) -> Result<u64, Error> {
+            if approver_a == approver_b {
+                return Err(Error::DuplicateApprover);
+            }
+            let amount = self.check_deposit()?;
+            let lock = LockInfo {
+                approvals: Some(Approvals {
+                    approvers: [approver_a, approver_b],
+                    granted: 0,
+                }),
+                ..self.native_lock(amount, [0; 32], None)
+            };
+            Ok(self.insert_lock(lock))
+        }
+
+        /// Records the caller's approval to redeem lock `lock_id`.
+        ///
+        /// Fails with `NotApprover` unless the caller is one of the lock's approvers, and
+        /// with `AlreadyApproved` if they already approved. Emits an `ApprovalGiven` event.
+        #[ink(message)]
+        pub fn approve_redeem(&mut self, lock_id: u64) -> Result<(), Error> {
+            let (mut lock, mut approvals, bit) = self.approvals_of(lock_id)?;
+            if approvals.granted & bit != 0 {
+                return Err(Error::AlreadyApproved);
+            }
+
+            approvals.granted |= bit;
+            lock.approvals = Some(approvals);
+            self.store_lock(lock_id, &lock);
+
+            self.env().emit_event(ApprovalGiven {
+                chain_id: self.chain_id,
+                lock_id,
+                approver: self.env().caller(),
+            });
+
+            Ok(())
+        }
+
+        /// Withdraws the caller's approval to redeem lock `lock_id`.
+        ///
+        /// Fails with `NotApprover` unless the caller is one of the lock's approvers, and
+        /// with `InsufficientApprovals` if they have not approved. Emits an
+        /// `ApprovalRevoked` event.
+        #[ink(message)]
+        pub fn revoke_approval(&mut self, lock_id: u64) -> Result<(), Error> {
+            let (mut lock, mut approvals, bit) = self.approvals_of(lock_id)?;
+            if approvals.granted & bit == 0 {
+                return Err(Error::InsufficientApprovals);
+            }
+
+            approvals.granted &= !bit;
+            lock.approvals = Some(approvals);
+            self.store_lock(lock_id, &lock);
+
+            self.env().emit_event(ApprovalRevoked {
+                chain_id: self.chain_id,
+                lock_id,
+                approver: self.env().caller(),
+            });
+
+            Ok(())
+        }
+
+        /// Pays lock `lock_id` back to its locker once both approvers approve.
+        ///
+        /// Anyone may trigger it, as the funds can only go to the locker.
+        ///
+        /// **Requirements:**
+        /// - The lock must exist and have approvers (`NotApprover`).
+        /// - Both approvers must have approved (`InsufficientApprovals`).
+        ///
+        /// **Effects:**
+        /// - Transfers the locked amount to the `locker`, minus the redemption fee.
+        /// - Removes the lock from storage.
+        /// - Emits an `ApprovedRedeemed` event.
+        #[ink(message)]
+        pub fn execute_redeem(&mut self, lock_id: u64) -> Result<(), Error> {
+            self.guarded(|this| this.execute_redeem_inner(lock_id))
+        }
+
+        /// Splits the assets sent by the caller into one lock per entry of `specs`.
+        ///
+        /// Every spec is checked before any lock is created, so the batch is all or
+        /// nothing. Returns the new lock ids in the order of `specs`.
+        ///
+        /// **Requirements:**
+        /// - At most `MAX_BATCH_LOCKS` specs (`BatchTooLarge`).
+        /// - The spec amounts add up to the transferred value (`BatchAmountMismatch`).
+        /// - Each amount is non-zero and passes the same checks as `lock`.
+        ///
+        /// **Effects:**
+        /// - Stores one `LockInfo` and emits one `Locked` event per spec.
+        #[ink(message, payable)]
+        pub fn lock_batch(&mut self, specs: Vec<LockSpec>) -> Result<Vec<u64>, Error> {
+            Self::check_batch_len(specs.len(), MAX_BATCH_LOCKS)?;
+            let transferred = self.env().transferred_value();
+            if transferred == 0 {
+                return Err(Error::NoAssetsSent);
+            }
+            let total = specs
+                .iter()
+                .try_fold(0 as Balance, |total, spec| total.checked_add(spec.amount))
+                .ok_or(Error::Overflow)?;
+            if total != transferred {
+                return Err(Error::BatchAmountMismatch);
+            }
+            for spec in &specs {
+                if spec.amount == 0 {
+                    return Err(Error::ZeroAmount);
+                }
+                self.check_amount(spec.amount)?;
+            }
+
+            Ok(specs
+                .into_iter()
+                .map(|spec| {
+                    let lock = LockInfo {
+                        expiry: spec.expiry,
+                        ..self.native_lock(spec.amount, spec.hash, spec.beneficiary)
+                    };
+                    self.insert_lock(lock)
+                })
+                .collect())
+        }
+
+        /// Locks `amount` of the `pallet-assets` asset `asset_id` held by the caller.
+        ///
+        /// Behaves like `lock_token`, except the asset is pulled from the caller through the
+        /// assets chain extension, so the caller must first approve this contract for at
+        /// least `amount`. Redeeming, refunding or cancelling the lock pays out in the same
+        /// asset, and native top-ups are refused with `AssetMismatch`.
+        ///
+        /// **Requirements:**
+        /// - Same as `lock_token`.
+        /// - The contract's balance of the asset must grow by exactly `amount`, otherwise
+        ///   fails with `TokenTransferFailed`.
+        #[cfg(feature = "assets")]
+        #[ink(message)]
+        pub fn lock_asset(
+            &mut self,
+            asset_id: u32,
+            amount: Balance,
+            hash: [u8; 32],
+            beneficiary: Option<AccountId>,
+            expiry: Option<Timestamp>,
+            cancel_grace: Option<Timestamp>,
+        ) -> Result<u64, Error> {
+            let caller = self.env().caller();
+            let contract = self.env().account_id();
+
+            if amount == 0 {
+                return Err(Error::ZeroAmount);
+            }
+            if self.paused {
+                return Err(Error::ContractPaused);
+            }
+            self.ensure_allowed()?;
+            if self.is_sunset() {
+                return Err(Error::Sunset);
+            }
+
+            // Pull the asset from the caller's approval and check it arrived.
+            let before = self.env().extension().balance_of(asset_id, contract);
+            self.env()
+                .extension()
+                .transfer_approved(asset_id, caller, contract, amount)
+                .map_err(|_| Error::TokenTransferFailed)?;
+            let after = self.env().extension().balance_of(asset_id, contract);
+            if after.checked_sub(before) != Some(amount) {
+                return Err(Error::TokenTransferFailed);
+            }
+
+            Ok(self.insert_lock(LockInfo {
+                expiry,
+                cancel_grace,
+                asset_id: Some(asset_id),
+                ..self.native_lock(amount, hash, beneficiary)
+            }))
+        }
+
+        /// Adds the assets sent by the caller to lock `lock_id`.
+        ///
+        /// The lock keeps its id, secret hash, beneficiary, expiry and `locked_at`.
+        ///
+        /// **Requirements:**
+        /// - The caller must send a non-zero amount of assets.
+        /// - The contract must not be paused.
+        /// - The lock must exist, must not have expired, and the caller must be its locker.
+        /// - The lock must not be shielded, as its published commitment covers the original
+        ///   amount only, and must hold native tokens.
+        /// - The new total must not exceed the configured maximum.
+        /// - On a canary deployment, the new total must not exceed the canary ceiling and the
+        ///   sunset timestamp must not have been reached.
+        ///
+        /// **Effects:**
+        /// - Adds the amount sent to the lock's `amount`, failing with `Overflow` rather than
+        ///   wrapping.
+        /// - Emits a `ToppedUp` event.
+        #[ink(message, payable)]
+        pub fn top_up(&mut self, lock_id: u64) -> Result<(), Error> {
+            let caller = self.env().caller();
+            let transferred = self.env().transferred_value();
+
+            // Ensure some assets are being sent.
+            if transferred == 0 {
+                return Err(Error::NoAssetsSent);
+            }
+            if self.paused {
+                return Err(Error::ContractPaused);
+            }
+
+            let mut lock = self.locks.get(lock_id).ok_or(Error::LockNotFound)?;
+
+            // Ensure the caller is the locker.
+            if caller != lock.locker {
+                return Err(Error::NotLocker);
+            }
+            if lock.vesting.is_some() {
+                return Err(Error::VestingLock);
+            }
+            if lock.escrow.is_some() {
+                return Err(Error::EscrowLock);
+            }
+            if Self::is_disputed(&lock) {
+                return Err(Error::Disputed);
+            }
+            if lock.shield_salt.is_some() {
+                return Err(Error::Shielded);
+            }
+            if lock.token.is_some() {
+                return Err(Error::WrongAsset);
+            }
+            if lock.asset_id.is_some() {
+                return Err(Error::AssetMismatch);
+            }
+            if self.is_expired(&lock) {
+                return Err(Error::Expired);
+            }
+
+            let new_total = lock
+                .amount
+                .checked_add(transferred)
+                .ok_or(Error::Overflow)?;
+            if self.max_amount.is_some_and(|max| new_total > max) {
+                return Err(Error::AmountAboveMaximum);
+            }
+
+            // Enforce the canary restrictions, if any.
+            if self.is_sunset() {
+                return Err(Error::Sunset);
+            }
+            if let Some(max) = self.canary_max_amount {
+                if new_total > max {
+                    return Err(Error::CanaryLimit);
+                }
+            }
+
+            lock.amount = new_total;
+            self.store_lock(lock_id, &lock);
+            self.stats.cumulative_volume = self.stats.cumulative_volume.saturating_add(transferred);
+
+            // Emit the ToppedUp event.
+            self.env().emit_event(ToppedUp {
+                chain_id: self.chain_id,
+                lock_id,
+                locker: caller,
+                added: transferred,
+                new_total,
+            });
+
+            Ok(())
+        }
+
+        /// Commits to redeeming lock `lock_id` without revealing the preimage yet.
+        ///
+        /// `commitment` is `redeem_commitment(caller, preimage)`. Once it is
+        /// `min_commit_age_blocks` old, the caller redeems with `reveal`, so the preimage
+        /// only enters the transaction pool when a front-runner can no longer commit first.
+        /// A new commitment by the same caller for the same lock replaces the old one.
+        #[ink(message)]
+        pub fn commit(&mut self, lock_id: u64, commitment: [u8; 32]) {
+            let commitment = Commitment {
+                hash: commitment,
+                committed_at: self.env().block_number(),
+            };
+            self.commitments
+                .insert((self.env().caller(), lock_id), &commitment);
+        }
+
+        /// Redeems lock `lock_id` like `redeem`, against a matured commitment by the caller.
+        ///
+        /// **Requirements:**
+        /// - The caller committed for `lock_id` (`NoCommitment`).
+        /// - The commitment is at least `min_commit_age_blocks` old (`CommitmentTooFresh`).
+        /// - `preimage` matches the commitment (`CommitmentMismatch`).
+        /// - Everything `redeem` requires.
+        ///
+        /// **Effects:**
+        /// - Same as `redeem`, and the commitment is consumed.
+        #[ink(message)]
+        pub fn reveal(
+            &mut self,
+            lock_id: u64,
+            preimage: Vec<u8>,
+            amount: Option<Balance>,
+        ) -> Result<(), Error> {
+            let key = (self.env().caller(), lock_id);
+            let commitment = self.commitments.get(key).ok_or(Error::NoCommitment)?;
+            let matures_at = commitment
+                .committed_at
+                .saturating_add(self.min_commit_age_blocks);
+            if self.env().block_number() < matures_at {
+                return Err(Error::CommitmentTooFresh);
+            }
+            if self.redeem_commitment(key.0, preimage.clone()) != commitment.hash {
+                return Err(Error::CommitmentMismatch);
+            }
+
+            self.redeem(lock_id, preimage, amount)?;
+            self.commitments.remove(key);
+            Ok(())
+        }
+
+        /// Computes the commitment `account` publishes with `commit` before revealing
+        /// `preimage`.
+        ///
+        /// The commitment is the Blake2x256 hash of the 32 `account` bytes followed by the
+        /// preimage.
+        #[ink(message)]
+        pub fn redeem_commitment(&self, account: AccountId, preimage: Vec<u8>) -> [u8; 32] {
+            let mut input = AsRef::<[u8]>::as_ref(&account).to_vec();
+            input.extend_from_slice(&preimage);
+            self.env().hash_bytes::<ink::env::hash::Blake2x256>(&input)
+        }
+
+        /// Fully redeems each lock in `ids` with the preimage at the same position.
+        ///
+        /// Each redemption is checked like `redeem`, and one that fails a check is skipped
+        /// with its error; the outcome of each is returned in the order of `ids`. A payout
+        /// that fails once its checks have passed fails the whole batch instead, so no
+        /// transfer of an unfinished redemption is kept.
+        ///
+        /// **Requirements:**
+        /// - At most `MAX_BATCH_REDEEMS` ids (`BatchTooLarge`).
+        /// - As many preimages as ids (`BatchLengthMismatch`).
+        /// - Every payout goes through (`TransferFailed` or `TokenTransferFailed`).
+        #[ink(message)]
+        pub fn redeem_batch(
+            &mut self,
+            ids: Vec<u64>,
+            preimages: Vec<Vec<u8>>,
+        ) -> Result<Vec<Result<(), Error>>, Error> {
+            Self::check_batch_len(ids.len(), MAX_BATCH_REDEEMS)?;
+            if ids.len() != preimages.len() {
+                return Err(Error::BatchLengthMismatch);
+            }
+
+            self.guarded(|this| {
+                let caller = this.env().caller();
+                let mut results = Vec::with_capacity(ids.len());
+                for (lock_id, preimage) in ids.into_iter().zip(preimages) {
+                    match this.plan_redeem(caller, lock_id, Proof::Preimage(preimage), None) {
+                        Ok(plan) => {
+                            this.apply_redeem(lock_id, caller, plan)?;
+                            results.push(Ok(()));
+                        }
+                        Err(error) => results.push(Err(error)),
+                    }
+                }
+                Ok(results)
+            })
+        }
+
+        /// Redeems lock `lock_id` with a signature instead of the secret preimage.
+        ///
+        /// `signature` is a 65-byte recoverable ECDSA signature over
+        /// `signature_challenge(lock_id)`. Each successful call advances the lock's nonce,
+        /// so a signature authorizes a single redemption.
+        ///
+        /// **Requirements:**
+        /// - Same as `redeem`, except the lock must have a `redeemer_pubkey`
+        ///   (`NoRedeemerKey`) and the key recovered from `signature` must match it
+        ///   (`InvalidSignature`).
+        ///
+        /// **Effects:**
+        /// - Same as `redeem`, and increments the lock's `nonce` if a remainder stays locked.
+        #[ink(message)]
+        pub fn redeem_with_signature(
+            &mut self,
+            lock_id: u64,
+            signature: [u8; 65],
+            amount: Option<Balance>,
+        ) -> Result<(), Error> {
+            self.guarded(|this| this.redeem_inner(lock_id, [Proof::Signature(signature)], amount))
+        }
+
+        /// Returns the message hash to sign for `redeem_with_signature` on `lock_id`.
+        ///
+        /// This is the Blake2x256 hash of the SCALE-encoded tuple
+        /// `(contract_address, lock_id, nonce)`, or `None` if no such lock is active.
+        #[ink(message)]
+        pub fn signature_challenge(&self, lock_id: u64) -> Option<[u8; 32]> {
+            self.locks
+                .get(lock_id)
+                .map(|lock| self.challenge(lock_id, lock.nonce))
+        }
+
+        /// Cancels lock `lock_id` and returns its funds to the locker.
+        ///
+        /// **Requirements:**
+        /// - The lock must exist and the caller must be its locker.
+        /// - If the lock names a beneficiary, the current block timestamp must be before
+        ///   `locked_at + cancel_grace`; without a grace period such a lock cannot be
+        ///   cancelled.
+        ///
+        /// **Effects:**
+        /// - Transfers the locked amount back to the `locker`.
+        /// - Removes the lock from storage.
+        /// - Emits a `Cancelled` event.
+        #[ink(message)]
+        pub fn cancel(&mut self, lock_id: u64) -> Result<(), Error> {
+            self.guarded(|this| this.cancel_inner(lock_id))
+        }
+
+        /// Returns the funds of an expired lock to its locker.
+        ///
+        /// **Requirements:**
+        /// - The lock must exist and the caller must be its locker.
+        /// - The lock must have an expiry and the current block timestamp must be at or
+        ///   after it.
+        ///
+        /// **Effects:**
+        /// - Transfers the locked amount back to the `locker`.
+        /// - Removes the lock from storage.
+        /// - Emits a `Refunded` event.
+        #[ink(message)]
+        pub fn refund(&mut self, lock_id: u64) -> Result<(), Error> {
+            self.guarded(|this| this.refund_inner(lock_id))
+        }
+
+        /// Locks the assets sent by the caller behind `hash`, naming `recovery_account` as
+        /// a fallback in case the locker loses their key, and returns the id of the new lock.
+        ///
+        /// The locker redeems as with `lock`. Once the lock has gone `recovery_delay`
+        /// without a redemption, counted from its creation or its latest partial
+        /// redemption, `recovery_account` may `recover` it instead.
+        ///
+        /// **Requirements:**
+        /// - Same as `lock`.
+        #[ink(message, payable)]
+        pub fn lock_with_recovery(
+            &mut self,
+            hash: [u8; 32],
+            recovery_account: AccountId,
+            recovery_delay: Timestamp,
+        ) -> Result<u64, Error> {
+            let amount = self.check_deposit()?;
+            Ok(self.insert_lock(LockInfo {
+                recovery: Some(Recovery {
+                    account: recovery_account,
+                    delay: recovery_delay,
+                    active_since: self.env().block_timestamp(),
+                }),
+                ..self.native_lock(amount, hash, None)
+            }))
+        }
+
+        /// Sends the funds of inactive lock `lock_id` to its recovery account.
+        ///
+        /// **Requirements:**
+        /// - The lock must exist (`LockNotFound`) and have a recovery account
+        ///   (`NoRecoveryAccount`), and the caller must be that account
+        ///   (`NotRecoveryAccount`).
+        /// - The lock must have gone its recovery delay without a redemption
+        ///   (`RecoveryDelayNotElapsed`).
+        ///
+        /// **Effects:**
+        /// - Transfers the locked amount to the recovery account.
+        /// - Removes the lock from storage.
+        /// - Emits a `Recovered` event.
+        #[ink(message)]
+        pub fn recover(&mut self, lock_id: u64) -> Result<(), Error> {
+            self.guarded(|this| this.recover_inner(lock_id))
+        }
+
+        /// Turns down lock `lock_id` as its beneficiary, returning the funds to the locker.
+        ///
+        /// Lets an account named as beneficiary refuse funds it does not want to be
+        /// associated with. Neither the secret nor the expiry matter, and nothing the
+        /// locker does, such as raising a dispute, can prevent it.
+        ///
+        /// **Requirements:**
+        /// - The lock must exist (`LockNotFound`) and name a beneficiary (`NoBeneficiary`).
+        /// - The caller must be that beneficiary (`NotBeneficiary`).
+        ///
+        /// **Effects:**
+        /// - Transfers the locked amount back to the `locker`.
+        /// - Removes the lock from storage.
+        /// - Emits a `Rejected` event.
+        #[ink(message)]
+        pub fn reject(&mut self, lock_id: u64) -> Result<(), Error> {
+            self.guarded(|this| this.reject_inner(lock_id))
+        }
+
+        /// Returns the funds of a long-expired lock to its locker, on anyone's behalf.
+        ///
+        /// Without this, the funds of a locker who never comes back to `refund` would sit
+        /// in the contract indefinitely. Any caller other than the locker is paid the
+        /// `finalize_bounty` set at instantiation out of the locked funds; the locker pays
+        /// none, so for them this is the same as `refund`.
+        ///
+        /// **Requirements:**
+        /// - The lock must exist (`LockNotFound`) and must not be disputed.
+        /// - The lock must have an expiry and the current block timestamp must be at or
+        ///   after `expiry + finalize_grace` (`NotExpired`).
+        /// - The bounty must not exceed the locked amount (`BountyExceedsLock`).
+        ///
+        /// **Effects:**
+        /// - Transfers the locked amount minus the bounty to the `locker`, and the bounty
+        ///   to the caller.
+        /// - Removes the lock from storage.
+        /// - Emits an `ExpiredFinalized` event.
+        #[ink(message)]
+        pub fn finalize_expired(&mut self, lock_id: u64) -> Result<(), Error> {
+            self.guarded(|this| this.finalize_expired_inner(lock_id))
+        }
+
+        /// Schedules the redemption of lock `lock_id` for a keeper to execute from
+        /// `execute_after` on, with `tip` of the payout as the keeper's reward.
+        ///
+        /// For a beneficiary who will be offline when they want to be paid. The preimage is
+        /// checked and stored now, so it is revealed as a redemption would reveal it. A new
+        /// schedule replaces the pending one.
+        ///
+        /// **Requirements:**
+        /// - The lock must name a beneficiary (`NoBeneficiary`), and the caller must be it.
+        /// - Everything `redeem` requires of a full redemption by the caller right now.
+        /// - `execute_after` must fall before the lock's expiry, if it has one (`Expired`).
+        /// - `tip` must not exceed the payout the redemption would make now
+        ///   (`TipExceedsPayout`).
+        ///
+        /// **Effects:**
+        /// - Stores the scheduled claim.
+        /// - Emits a `ClaimScheduled` event.
+        #[ink(message)]
+        pub fn schedule_claim(
+            &mut self,
+            lock_id: u64,
+            preimage: Vec<u8>,
+            execute_after: Timestamp,
+            tip: Balance,
+        ) -> Result<(), Error> {
+            let caller = self.env().caller();
+            let lock = self.locks.get(lock_id).ok_or(Error::LockNotFound)?;
+            if lock.beneficiary.is_none() {
+                return Err(Error::NoBeneficiary);
+            }
+            let plan =
+                self.plan_redeem(caller, lock_id, Proof::Preimage(preimage.clone()), None)?;
+            if lock.expiry.is_some_and(|expiry| execute_after >= expiry) {
+                return Err(Error::Expired);
+            }
+            if tip > plan.payout {
+                return Err(Error::TipExceedsPayout);
+            }
+
+            self.scheduled_claims.insert(
+                lock_id,
+                &ScheduledClaim {
+                    execute_after,
+                    tip,
+                    preimage,
+                },
+            );
+            self.env().emit_event(ClaimScheduled {
+                chain_id: self.chain_id,
+                lock_id,
+                beneficiary: caller,
+                execute_after,
+                tip,
+            });
+            Ok(())
+        }
+
+        /// Cancels the claim scheduled for lock `lock_id`.
+        ///
+        /// Fails with `NoScheduledClaim` if there is none, and with `NotBeneficiary` unless
+        /// called by the beneficiary. Emits a `ScheduledClaimCancelled` event.
+        #[ink(message)]
+        pub fn cancel_scheduled_claim(&mut self, lock_id: u64) -> Result<(), Error> {
+            let caller = self.env().caller();
+            if !self.scheduled_claims.contains(lock_id) {
+                return Err(Error::NoScheduledClaim);
+            }
+            let lock = self.locks.get(lock_id).ok_or(Error::LockNotFound)?;
+            if lock.beneficiary != Some(caller) {
+                return Err(Error::NotBeneficiary);
+            }
+
+            self.scheduled_claims.remove(lock_id);
+            self.env().emit_event(ScheduledClaimCancelled {
+                chain_id: self.chain_id,
+                lock_id,
+                beneficiary: caller,
+            });
+            Ok(())
+        }
+
+        /// Executes the claim scheduled for lock `lock_id`, on the beneficiary's behalf.
+        ///
+        /// Anyone may call this. The whole lock is redeemed to the beneficiary as if they
+        /// had called `redeem`, except that the caller is paid the scheduled tip out of the
+        /// payout.
+        ///
+        /// **Requirements:**
+        /// - A claim must be scheduled for the lock (`NoScheduledClaim`).
+        /// - The current block timestamp must be at or after its `execute_after`
+        ///   (`ClaimNotDue`).
+        /// - Everything `redeem` requires of the beneficiary, with the scheduled preimage.
+        /// - The tip must not exceed the payout (`TipExceedsPayout`), which can happen if
+        ///   the fee was raised since scheduling.
+        ///
+        /// **Effects:**
+        /// - Same as `redeem` by the beneficiary, with the tip going to the caller.
+        /// - Emits a `ScheduledClaimExecuted` event.
+        #[ink(message)]
+        pub fn execute_scheduled_claim(&mut self, lock_id: u64) -> Result<(), Error> {
+            self.guarded(|this| this.execute_scheduled_claim_inner(lock_id))
+        }
+
+        /// Returns when the claim scheduled for `lock_id` becomes executable and its tip, or
+        /// `None` if no claim is scheduled.
+        #[ink(message)]
+        pub fn scheduled_claim(&self, lock_id: u64) -> Option<(Timestamp, Balance)> {
+            self.scheduled_claims
+                .get(lock_id)
+                .map(|claim| (claim.execute_after, claim.tip))
+        }
+
+        /// Proposes moving the expiry of lock `lock_id` to `new_expiry`.
+        ///
+        /// Either the locker or the beneficiary may propose; the expiry only moves once the
+        /// other one calls `accept_extension`. A new proposal by either party replaces the
+        /// pending one, and any redemption of the lock discards it.
+        ///
+        /// **Requirements:**
+        /// - The lock must exist and name a beneficiary, and the caller must be its locker
+        ///   or beneficiary (`NotParty`).
+        /// - The lock must not be disputed.
+        /// - The lock must have an expiry that has not been reached (`Expired`), and
+        ///   `new_expiry` must be strictly later (`InvalidExpiry`).
+        ///
+        /// **Effects:**
+        /// - Stores the proposal on the lock.
+        /// - Emits an `ExtensionProposed` event.
+        #[ink(message)]
+        pub fn propose_extension(
+            &mut self,
+            lock_id: u64,
+            new_expiry: Timestamp,
+        ) -> Result<(), Error> {
+            let caller = self.env().caller();
+            let mut lock = self.extendable(lock_id, caller)?;
+            if lock.expiry.is_none_or(|expiry| new_expiry <= expiry) {
+                return Err(Error::InvalidExpiry);
+            }
+
+            lock.extension = Some(ExtensionProposal {
+                proposer: caller,
+                new_expiry,
+            });
+            self.store_lock(lock_id, &lock);
+
+            self.env().emit_event(ExtensionProposed {
+                chain_id: self.chain_id,
+                lock_id,
+                proposer: caller,
+                new_expiry,
+            });
+
+            Ok(())
+        }
+
+        /// Accepts the pending extension of lock `lock_id`, moving its expiry.
+        ///
+        /// **Requirements:**
+        /// - Same as `propose_extension`.
+        /// - The other party must have proposed an extension (`NoExtensionProposal`).
+        ///
+        /// **Effects:**
+        /// - Sets the lock's expiry to the proposed one and clears the proposal.
+        /// - Emits an `ExtensionAccepted` event.
+        #[ink(message)]
+        pub fn accept_extension(&mut self, lock_id: u64) -> Result<(), Error> {
+            let caller = self.env().caller();
+            let mut lock = self.extendable(lock_id, caller)?;
+            let proposal = lock
+                .extension
+                .filter(|proposal| proposal.proposer != caller)
+                .ok_or(Error::NoExtensionProposal)?;
+
+            lock.expiry = Some(proposal.new_expiry);
+            lock.extension = None;
+            self.store_lock(lock_id, &lock);
+
+            self.env().emit_event(ExtensionAccepted {
+                chain_id: self.chain_id,
+                lock_id,
+                accepted_by: caller,
+                new_expiry: proposal.new_expiry,
+            });
+
+            Ok(())
+        }
+
+        /// Reports whether `caller` could redeem `amount` of `lock_id`, or all of it, with
+        /// `preimage` right now, without changing any state.
+        ///
+        /// Runs the same checks as `redeem`, so a wallet can warn the user before they sign
+        /// and pay for a transaction that would fail. On success it also reports the payout,
+        /// net of the fee and including any reward.
+        ///
+        /// A shielded lock is reported with both amounts as zero. Like `redeem`, it rejects a
+        /// partial `amount` on a shielded lock with `Shielded`.
+        #[ink(message)]
+        pub fn can_redeem(
+            &self,
+            caller: AccountId,
+            lock_id: u64,
+            preimage: Vec<u8>,
+            amount: Option<Balance>,
+        ) -> RedeemCheck {
+            let shielded = self
+                .locks
+                .get(lock_id)
+                .is_some_and(|lock| lock.shield_salt.is_some());
+            match self.plan_redeem(caller, lock_id, Proof::Preimage(preimage), amount) {
+                Ok(_) if shielded => RedeemCheck::Ok {
+                    amount: 0,
+                    payout: 0,
+                },
+                Ok(plan) => RedeemCheck::Ok {
+                    amount: plan.amount,
+                    payout: plan.payout,
+                },
+                Err(reason) => RedeemCheck::Rejected { reason },
+            }
+        }
+
+        /// Previews the full redemption of `lock_id` by `as_caller` at `at_time`, or now.
+        ///
+        /// The breakdown is worked out by the same code as `redeem`, with the reward accrued
+        /// and the expiry checked at the given time, and the fee, reward pool and contract
+        /// balance as they are now. The preimage is not checked, so a front-end can show the
+        /// breakdown before the user reveals it. Payouts are always made in the redeeming
+        /// transaction; `recipients` shows where they would go.
+        ///
+        /// Fails with whatever `redeem` would fail with at that time, other than a wrong
+        /// preimage, and with `Shielded` for a shielded lock, whose amount the breakdown
+        /// would publish.
+        #[ink(message)]
+        pub fn preview_settlement(
+            &self,
+            lock_id: u64,
+            as_caller: AccountId,
+            at_time: Option<Timestamp>,
+        ) -> Result<SettlementPreview, Error> {
+            let stored = self.locks.get(lock_id).ok_or(Error::LockNotFound)?;
+            Self::check_redeemer(&stored, as_caller)?;
+            if stored.shield_salt.is_some() {
+                return Err(Error::Shielded);
+            }
+            let at = at_time.unwrap_or_else(|| self.env().block_timestamp());
+            let plan = self.quote_redeem(stored.clone(), stored, None, at)?;
+            Ok(SettlementPreview {
+                at,
+                amount: plan.amount,
+                reward: plan.reward,
+                fee: plan.fee,
+                shortfall: plan.shortfall,
+                payout: plan.payout,
+                recipients: Self::split_shares(&plan.lock, as_caller, plan.payout),
+            })
+        }
+
+        /// Returns the largest list each message accepts, see `BatchLimits`.
+        #[ink(message)]
+        pub fn get_batch_limits(&self) -> BatchLimits {
+            BatchLimits {
+                batch_locks: MAX_BATCH_LOCKS,
+                batch_redeems: MAX_BATCH_REDEEMS,
+                split_recipients: MAX_SPLIT_RECIPIENTS as u32,
+                redeemers: MAX_REDEEMERS,
+                conditions: MAX_CONDITIONS,
+                records_per_query: MAX_RECORDS_PER_QUERY,
+            }
+        }
+
+        /// Returns the conditions of `lock_id` as `lock_with` takes them, or `None` if no
+        /// such lock is active.
+        ///
+        /// Every lock has a `Condition::MessageHash`, followed by a `Condition::Beneficiary`
+        /// and a `Condition::Timelock` if it names a beneficiary or an expiry.
+        #[ink(message)]
+        pub fn lock_conditions(&self, lock_id: u64) -> Option<Vec<Condition>> {
+            let lock = self.locks.get(lock_id)?;
+            let mut conditions = vec![Condition::MessageHash(lock.secret_hash)];
+            conditions.extend(lock.beneficiary.map(Condition::Beneficiary));
+            conditions.extend(lock.expiry.map(Condition::Timelock));
+            Some(conditions)
+        }
+
+        /// Returns the full record of `lock_id`, or `None` if no such lock is active.
+        ///
+        /// A shielded lock is returned with its amount as zero and its salt zeroed, since
+        /// the salt would open the published commitment; `shield_salt` is still `Some`.
+        #[ink(message)]
+        pub fn get_lock_info(&self, lock_id: u64) -> Option<LockInfo> {
+            self.locks.get(lock_id).map(Self::masked)
+        }
+
+        /// Returns the commitment to the immutable fields of `lock_id`, or `None` if no such
+        /// lock is active.
+        ///
+        /// The commitment is the Blake2x256 hash of the SCALE encoding of the tuple
+        /// `(locker, beneficiary, amount, secret_hash, redeemer_pubkey, token, asset_id,
+        /// shield_salt, locked_at, fee_bps)`, taken when the lock is created: `amount` is the
+        /// amount locked and `fee_bps` the redemption fee in force at the time. It is fixed
+        /// for the life of the lock, so partial redemptions, extensions and other later
+        /// changes leave it as it is. The salt of a shielded lock keeps its amount from
+        /// being guessed from the commitment.
+        #[ink(message)]
+        pub fn get_lock_commitment(&self, lock_id: u64) -> Option<[u8; 32]> {
+            self.lock_commitments.get(lock_id)
+        }
+
+        /// Returns the chain marker supplied at instantiation.
+        #[ink(message)]
+        pub fn genesis_marker(&self) -> [u8; 32] {
+            self.genesis_marker.get().unwrap_or_default()
+        }
+
+        /// Returns the display metadata of the native token.
+        #[ink(message)]
+        pub fn token_metadata(&self) -> TokenMetadata {
+            self.native_metadata.get().unwrap_or_default()
+        }
+
+        /// Returns the display metadata cached for the PSP22 `token`, or `None` if no lock
+        /// has read it yet.
+        #[ink(message)]
+        pub fn token_metadata_of(&self, token: AccountId) -> Option<TokenMetadata> {
+            self.token_metadata_cache.get(token)
+        }
+
+        /// Returns the owner of the contract, or `None` if ownership was renounced.
+        #[ink(message)]
+        pub fn owner(&self) -> Option<AccountId> {
+            self.owner
+        }
+
+        /// Transfers ownership of the contract to `new_owner`.
+        ///
+        /// Fails with `NotOwner` unless called by the current owner.
+        #[ink(message)]
+        pub fn transfer_ownership(&mut self, new_owner: AccountId) -> Result<(), Error> {
+            self.ensure_owner()?;
+            self.set_owner(Some(new_owner));
+            Ok(())
+        }
+
+        /// Gives up ownership of the contract for good.
+        ///
+        /// Fails with `NotOwner` unless called by the current owner. Afterwards every
+        /// owner-only message fails with `NotOwner`.
+        #[ink(message)]
+        pub fn renounce_ownership(&mut self) -> Result<(), Error> {
+            self.ensure_owner()?;
+            self.set_owner(None);
+            Ok(())
+        }
+
+        /// Returns `true` while lock operations are paused.
+        #[ink(message)]
+        pub fn paused(&self) -> bool {
+            self.paused
+        }
+
+        /// Returns whether the allow-list is enforced.
+        #[ink(message)]
+        pub fn allowlist_enabled(&self) -> bool {
+            self.allowlist_enabled
+        }
+
+        /// Turns enforcement of the allow-list on or off.
+        ///
+        /// While enabled, every way of creating a lock fails with `NotAllowed` for accounts
+        /// that are not on the list; existing locks are unaffected. Fails with `NotOwner`
+        /// unless called by the owner.
+        #[ink(message)]
+        pub fn set_allowlist_enabled(&mut self, enabled: bool) -> Result<(), Error> {
+            self.ensure_owner()?;
+            self.allowlist_enabled = enabled;
+            Ok(())
+        }
+
+        /// Returns whether `account` is on the allow-list, whether or not it is enforced.
+        #[ink(message)]
+        pub fn is_allowed(&self, account: AccountId) -> bool {
+            self.allowlist.contains(account)
+        }
+
+        /// Adds `account` to the allow-list and emits `AllowlistUpdated`.
+        ///
+        /// Fails with `NotOwner` unless called by the owner.
+        #[ink(message)]
+        pub fn add_to_allowlist(&mut self, account: AccountId) -> Result<(), Error> {
+            self.ensure_owner()?;
+            self.allowlist.insert(account, &());
+            self.env().emit_event(AllowlistUpdated {
+                chain_id: self.chain_id,
+                account,
+                allowed: true,
+            });
+            Ok(())
+        }
+
+        /// Removes `account` from the allow-list and emits `AllowlistUpdated`.
+        ///
+        /// Fails with `NotOwner` unless called by the owner.
+        #[ink(message)]
+        pub fn remove_from_allowlist(&mut self, account: AccountId) -> Result<(), Error> {
+            self.ensure_owner()?;
+            self.allowlist.remove(account);
+            self.env().emit_event(AllowlistUpdated {
+                chain_id: self.chain_id,
+                account,
+                allowed: false,
+            });
+            Ok(())
+        }
+
+        /// Returns the reward pool balance and the daily reward rate in basis points.
+        #[ink(message)]
+        pub fn rewards(&self) -> (Balance, u16) {
+            (self.reward_pool, self.rate_bps_per_day)
+        }
+
+        /// Adds the assets sent by the owner to the reward pool.
+        ///
+        /// Fails with `NotOwner` unless called by the owner, and with `NoAssetsSent` if
+        /// nothing is sent.
+        #[ink(message, payable)]
+        pub fn fund_rewards(&mut self) -> Result<(), Error> {
+            self.ensure_owner()?;
+            let transferred = self.env().transferred_value();
+            if transferred == 0 {
+                return Err(Error::NoAssetsSent);
+            }
+            self.reward_pool = self
+                .reward_pool
+                .checked_add(transferred)
+                .ok_or(Error::Overflow)?;
+            Ok(())
+        }
+
+        /// Sets the reward accrued per full day locked, in basis points.
+        ///
+        /// A redemption of `amount` after `days` full days earns
+        /// `amount * rate_bps_per_day * days / 10_000`, capped by the reward pool. The rate
+        /// applies to every active lock, including the time already elapsed. Fails with
+        /// `NotOwner` unless called by the owner.
+        #[ink(message)]
+        pub fn set_reward_rate(&mut self, rate_bps_per_day: u16) -> Result<(), Error> {
+            self.ensure_owner()?;
+            self.rate_bps_per_day = rate_bps_per_day;
+            Ok(())
+        }
+
+        /// Sends `amount` of the reward pool back to the owner.
+        ///
+        /// Fails with `NotOwner` unless called by the owner, and with `RewardsCommitted` if
+        /// the pool would no longer cover the rewards accrued so far by the active locks,
+        /// counting partial days pro rata.
+        #[ink(message)]
+        pub fn withdraw_rewards(&mut self, amount: Balance) -> Result<(), Error> {
+            self.guarded(|this| this.withdraw_rewards_inner(amount))
+        }
+
+        /// Returns the native balance the contract holds beyond what it owes.
+        ///
+        /// Everything above the amounts of the active native locks, the reward pool and
+        /// the existential deposit is excess, such as value sent to the contract account
+        /// directly instead of through `lock`.
+        #[ink(message)]
+        pub fn get_excess_balance(&self) -> Balance {
+            let owed = self.native_locked.saturating_add(self.reward_pool);
+            self.env()
+                .balance()
+                .saturating_sub(owed.saturating_add(self.env().minimum_balance()))
+        }
+
+        /// Sends the excess native balance to `recovery` and returns the amount sent.
+        ///
+        /// Locked principal, the reward pool and the existential deposit are never touched.
+        /// Fails with `NotOwner` unless called by the owner. Emits an `ExcessSwept` event
+        /// if anything was sent.
+        #[ink(message)]
+        pub fn sweep_excess(&mut self, recovery: AccountId) -> Result<Balance, Error> {
+            self.guarded(|this| this.sweep_excess_inner(recovery))
+        }
+
+        /// Returns the account `reconcile` sends the excess balance to.
+        ///
+        /// Defaults to the owner, or `None` once ownership is renounced and no account was
+        /// set.
+        #[ink(message)]
+        pub fn surplus_recipient(&self) -> Option<AccountId> {
+            self.surplus_recipient.get().or(self.owner)
+        }
+
+        /// Sets the account `reconcile` sends the excess balance to.
+        ///
+        /// Fails with `NotOwner` unless called by the owner.
+        #[ink(message)]
+        pub fn set_surplus_recipient(&mut self, account: AccountId) -> Result<(), Error> {
+            self.ensure_owner()?;
+            self.surplus_recipient.set(&account);
+            Ok(())
+        }
+
+        /// Sends the excess native balance to the `surplus_recipient` and returns the
+        /// amount sent.
+        ///
+        /// Like `sweep_excess`, but to the configured account, so it can be run routinely
+        /// to pick up value sent to the contract account without `lock`. Fails with
+        /// `NotOwner` unless called by the owner. Emits an `ExcessSwept` event if anything
+        /// was sent.
+        #[ink(message)]
+        pub fn reconcile(&mut self) -> Result<Balance, Error> {
+            self.guarded(|this| {
+                let recovery = this.surplus_recipient().ok_or(Error::NotOwner)?;
+                this.sweep_excess_inner(recovery)
+            })
+        }
+
+        /// Pauses lock operations in an emergency.
+        ///
+        /// While paused, every way of locking or topping up fails with `ContractPaused`;
+        /// `redeem`, `cancel` and `refund` keep working so users can always exit.
+        /// Fails with `NotOwner` unless called by the owner.
+        #[ink(message)]
+        pub fn pause(&mut self) -> Result<(), Error> {
+            self.ensure_owner()?;
+            self.paused = true;
+            self.env().emit_event(Paused {
+                chain_id: self.chain_id,
+                by: self.env().caller(),
+            });
+            Ok(())
+        }
+
+        /// Resumes lock operations.
+        ///
+        /// Fails with `NotOwner` unless called by the owner.
+        #[ink(message)]
+        pub fn unpause(&mut self) -> Result<(), Error> {
+            self.ensure_owner()?;
+            self.paused = false;
+            self.env().emit_event(Unpaused {
+                chain_id: self.chain_id,
+                by: self.env().caller(),
+            });
+            Ok(())
+        }
+
+        /// Returns the redemption fee in basis points and the account collecting it.
+        #[ink(message)]
+        pub fn fee(&self) -> (u16, AccountId) {
+            (self.fee_bps, self.fee_collector)
+        }
+
+        /// Sets the redemption fee to `bps` basis points.
+        ///
+        /// Fails with `NotOwner` unless called by the owner, and with `FeeTooHigh` if `bps`
+        /// exceeds `MAX_FEE_BPS`.
+        #[ink(message)]
+        pub fn set_fee(&mut self, bps: u16) -> Result<(), Error> {
+            self.ensure_owner()?;
+            if bps > MAX_FEE_BPS {
+                return Err(Error::FeeTooHigh);
+            }
+            self.fee_bps = bps;
+            Ok(())
+        }
+
+        /// Returns the dispute bond in basis points of the disputed amount and its floor.
+        #[ink(message)]
+        pub fn dispute_bond(&self) -> (u16, Balance) {
+            (self.dispute_bond_bps, self.dispute_bond_floor)
+        }
+
+        /// Sets the bond `raise_dispute` requires to `bps` basis points of the locked amount,
+        /// but at least `floor`.
+        ///
+        /// Applies to disputes raised from now on. Fails with `NotOwner` unless called by
+        /// the owner, and with `InvalidBond` if `bps` exceeds 10_000.
+        #[ink(message)]
+        pub fn set_dispute_bond(&mut self, bps: u16, floor: Balance) -> Result<(), Error> {
+            self.ensure_owner()?;
+            if bps > 10_000 {
+                return Err(Error::InvalidBond);
+            }
+            self.dispute_bond_bps = bps;
+            self.dispute_bond_floor = floor;
+            Ok(())
+        }
+
+        /// Sets the account receiving the redemption fees.
+        ///
+        /// Fails with `NotOwner` unless called by the owner.
+        #[ink(message)]
+        pub fn set_fee_collector(&mut self, account: AccountId) -> Result<(), Error> {
+            self.ensure_owner()?;
+            self.fee_collector = account;
+            Ok(())
+        }
+
+        /// Returns the number of settlements recorded so far, including pruned ones.
+        #[ink(message)]
+        pub fn history_len(&self) -> u64 {
+            self.history_len
+        }
+
+        /// Returns the settlement record at `index`, or `None` if it does not exist or was
+        /// pruned.
+        #[ink(message)]
+        pub fn get_record(&self, index: u64) -> Option<LockRecord> {
+            self.history.get(index)
+        }
+
+        /// Returns up to `limit` records starting at index `from`, skipping pruned ones.
+        ///
+        /// `limit` is capped at `MAX_RECORDS_PER_QUERY` to bound the cost of the call.
+        #[ink(message)]
+        pub fn get_records(&self, from: u64, limit: u32) -> Vec<LockRecord> {
+            let limit = limit.min(MAX_RECORDS_PER_QUERY);
+            let end = from.saturating_add(u64::from(limit)).min(self.history_len);
+            (from.max(self.history_start)..end)
+                .filter_map(|index| self.history.get(index))
+                .collect()
+        }
+
+        /// Deletes every history record with an index below `before_index`.
+        ///
+        /// Each call removes at most `MAX_RECORDS_PER_QUERY` records, so pruning a long
+        /// history takes several calls. Fails with `NotOwner` unless called by the owner.
+        #[ink(message)]
+        pub fn prune_history(&mut self, before_index: u64) -> Result<(), Error> {
+            self.ensure_owner()?;
+            let end = before_index
+                .min(self.history_len)
+                .min(self.history_start + u64::from(MAX_RECORDS_PER_QUERY));
+            for index in self.history_start..end {
+                self.history.remove(index);
+            }
+            self.history_start = self.history_start.max(end);
+            Ok(())
+        }
+
+        /// Returns the minimum and maximum amount of a native lock.
+        #[ink(message)]
+        pub fn limits(&self) -> (Balance, Option<Balance>) {
+            (self.min_amount, self.max_amount)
+        }
+
+        /// Sets the minimum and maximum amount of a native lock.
+        ///
+        /// Fails with `NotOwner` unless called by the owner, and with `AssetsAlreadyLocked`
+        /// while any lock is active, so limits never change under an existing lock.
+        #[ink(message)]
+        pub fn set_limits(
+            &mut self,
+            min_amount: Balance,
+            max_amount: Option<Balance>,
+        ) -> Result<(), Error> {
+            self.ensure_owner()?;
+            if self.active_locks > 0 {
+                return Err(Error::AssetsAlreadyLocked);
+            }
+            self.min_amount = min_amount;
+            self.max_amount = max_amount;
+            Ok(())
+        }
+
+        /// Returns how long past its expiry a lock must be before `terminate` refunds it.
+        #[ink(message)]
+        pub fn termination_grace(&self) -> Timestamp {
+            self.termination_grace
+                .get()
+                .unwrap_or(DEFAULT_TERMINATION_GRACE)
+        }
+
+        /// Sets how long past its expiry a lock must be before `terminate` refunds it.
+        ///
+        /// Fails with `NotOwner` unless called by the owner.
+        #[ink(message)]
+        pub fn set_termination_grace(&mut self, grace: Timestamp) -> Result<(), Error> {
+            self.ensure_owner()?;
+            self.termination_grace.set(&grace);
+            Ok(())
+        }
+
+        /// Removes the contract and sends its remaining balance to the owner.
+        ///
+        /// Every active lock must have expired more than `termination_grace` ago; those
+        /// locks are first refunded to their lockers, each emitting a `Refunded` event.
+        /// Does not return on success.
+        ///
+        /// **Requirements:**
+        /// - Caller must be the owner (`NotOwner`).
+        /// - No lock is active, or all of them are past the grace period (`ActiveLockExists`).
+        #[ink(message)]
+        pub fn terminate(&mut self) -> Result<(), Error> {
+            self.guarded(|this| this.terminate_inner())
+        }
+
+        /// Returns the lifetime usage counters of this deployment.
+        ///
+        /// Counters only move on successful locks, top-ups and settlements.
+        #[ink(message)]
+        pub fn stats(&self) -> ContractStats {
+            self.stats
+        }
+
+        /// Replaces the code of this contract with the uploaded code `code_hash`.
+        ///
+        /// Storage is kept as is; if the new code expects a newer layout, the owner calls
+        /// `migrate` next. Fails with `NotOwner` unless called by the owner, and with
+        /// `UpgradeFailed` if the code cannot be set. Emits an `Upgraded` event.
+        #[ink(message)]
+        pub fn upgrade(&mut self, code_hash: Hash) -> Result<(), Error> {
+            self.ensure_owner()?;
+            self.env()
+                .set_code_hash(&code_hash)
+                .map_err(|_| Error::UpgradeFailed)?;
+            self.env().emit_event(Upgraded {
+                chain_id: self.chain_id,
+                code_hash,
+            });
+            Ok(())
+        }
+
+        /// Returns the layout version of the stored data.
+        #[ink(message)]
+        pub fn storage_version(&self) -> u32 {
+            self.storage_version
+        }
+
+        /// Brings the storage up to `STORAGE_VERSION` after an upgrade.
+        ///
+        /// Runs once per version bump: fails with `AlreadyMigrated` once the storage is at
+        /// the version of the running code. Future versions transform the stored data here
+        /// before recording the new version. Fails with `NotOwner` unless called by the
+        /// owner. Emits a `Migrated` event.
+        #[ink(message)]
+        pub fn migrate(&mut self) -> Result<(), Error> {
+            self.ensure_owner()?;
+            let from = self.storage_version;
+            if from >= STORAGE_VERSION {
+                return Err(Error::AlreadyMigrated);
+            }
+
+            self.storage_version = STORAGE_VERSION;
+            self.env().emit_event(Migrated {
+                chain_id: self.chain_id,
+                from,
+                to: STORAGE_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Returns the version of the contract interface, see `CONTRACT_VERSION`.
+        #[ink(message)]
+        pub fn version(&self) -> u32 {
+            CONTRACT_VERSION
+        }
+
+        /// Returns the semantic version of the contract, see `CONTRACT_SEMVER`.
+        #[ink(message)]
+        pub fn contract_version(&self) -> (u16, u16, u16) {
+            CONTRACT_SEMVER
+        }
+
+        /// Returns the features of this deployment as a bitmask of the `CAP_*` constants.
+        ///
+        /// Bits reflect both what the code was compiled with and how the deployment is
+        /// configured right now: `CAP_FEES` is only set while a fee is charged, for instance,
+        /// and `CAP_LOCKING` clears while the contract is paused.
+        #[ink(message)]
+        pub fn capabilities(&self) -> u32 {
+            let mut capabilities = CAP_EXPIRY | CAP_PSP22;
+            if cfg!(feature = "assets") {
+                capabilities |= CAP_ASSETS;
+            }
+            if self.fee_bps > 0 {
+                capabilities |= CAP_FEES;
+            }
+            if self.rate_bps_per_day > 0 {
+                capabilities |= CAP_REWARDS;
+            }
+            if self.allowlist_enabled {
+                capabilities |= CAP_ALLOWLIST;
+            }
+            if self.canary_max_amount.is_some() || self.sunset_at.is_some() {
+                capabilities |= CAP_CANARY;
+            }
+            if !self.paused && !self.is_sunset() {
+                capabilities |= CAP_LOCKING;
+            }
+            capabilities
+        }
+
+        /// Returns the canary configuration and chain identifier of this deployment.
+        #[ink(message)]
+        pub fn contract_info(&self) -> ContractInfo {
+            ContractInfo {
+                canary: self.canary_max_amount.is_some() || self.sunset_at.is_some(),
+                canary_max_amount: self.canary_max_amount,
+                sunset_at: self.sunset_at,
+                sunset: self.is_sunset(),
+                chain_id: self.chain_id,
+            }
+        }
+
+        /// Computes the commitment published for a shielded lock.
+        ///
+        /// The commitment is the Blake2x256 hash of the SCALE-encoded `amount` followed by
+        /// the 32 `salt` bytes.
+        #[ink(message)]
+        pub fn amount_commitment(&self, amount: Balance, salt: [u8; 32]) -> [u8; 32] {
+            let mut input = scale::Encode::encode(&amount);
+            input.extend_from_slice(&salt);
+            self.env().hash_bytes::<ink::env::hash::Blake2x256>(&input)
+        }
+
+        //----------------------------------
+        // Internal Helpers
+        //----------------------------------
+
+        /// Body of `lock` and `lock_shielded`; `salt` is set for shielded locks.
+        fn lock_inner(
+            &mut self,
+            hash: [u8; 32],
+            beneficiary: Option<AccountId>,
+            expiry: Option<Timestamp>,
+            cancel_grace: Option<Timestamp>,
+            redeemer_pubkey: Option<[u8; 33]>,
+            salt: Option<[u8; 32]>,
+        ) -> Result<u64, Error> {
+            let amount = self.check_deposit()?;
+            Ok(self.insert_lock(LockInfo {
+                shield_salt: salt,
+                expiry,
+                cancel_grace,
+                redeemer_pubkey,
+                ..self.native_lock(amount, hash, beneficiary)
+            }))
+        }
+
+        /// Checks that the value sent by the caller may be locked and returns it.
+        fn check_deposit(&self) -> Result<Balance, Error> {
+            let transferred = self.env().transferred_value();
+
+            // Ensure some assets are being sent to lock.
+            if transferred == 0 {
+                return Err(Error::NoAssetsSent);
+            }
+            self.check_amount(transferred)?;
+
+            Ok(transferred)
+        }
+
+        /// Checks a non-zero native `amount` against the pause switch, the allow-list,
+        /// the configured limits and the canary restrictions.
+        fn check_amount(&self, amount: Balance) -> Result<(), Error> {
+            if self.paused {
+                return Err(Error::ContractPaused);
+            }
+            self.ensure_allowed()?;
+
+            // Enforce the configured amount limits.
+            if amount < self.min_amount {
+                return Err(Error::AmountBelowMinimum);
+            }
+            if self.max_amount.is_some_and(|max| amount > max) {
+                return Err(Error::AmountAboveMaximum);
+            }
+
+            // Enforce the canary restrictions, if any.
+            if self.is_sunset() {
+                return Err(Error::Sunset);
+            }
+            if let Some(max) = self.canary_max_amount {
+                if amount > max {
+                    return Err(Error::CanaryLimit);
+                }
+            }
+
+            Ok(())
+        }
+
+        /// Builds a plain native lock of `amount` by the caller, locked now.
+        fn native_lock(
+            &self,
+            amount: Balance,
+            hash: [u8; 32],
+            beneficiary: Option<AccountId>,
+        ) -> LockInfo {
+            LockInfo {
+                locker: self.env().caller(),
+                amount,
+                secret_hash: hash,
+                beneficiary,
+                shield_salt: None,
+                expiry: None,
+                locked_at: self.env().block_timestamp(),
+                cancel_grace: None,
+                token: None,
+                token_metadata: None,
+                asset_id: None,
+                redeemer_pubkey: None,
+                nonce: 0,
+                vesting: None,
+                escrow: None,
+                arbitration: None,
+                extension: None,
+                approvals: None,
+                redeemers: Vec::new(),
+                memo: None,
+                split: Vec::new(),
+                recovery: None,
+            }
+        }
+
+        /// Builds a native lock of `amount` by the caller from `lock_with` conditions.
+        fn conditional_lock(
+            &self,
+            amount: Balance,
+            conditions: &[Condition],
+        ) -> Result<LockInfo, Error> {
+            let mut hash = None;
+            let mut beneficiary = None;
+            let mut expiry = None;
+
+            // Walk the conditions depth-first, stopping as soon as a bound is exceeded.
+            let mut count = 0;
+            let mut pending: Vec<(&Condition, u32)> = conditions
+                .iter()
+                .rev()
+                .map(|condition| (condition, 1))
+                .collect();
+            while let Some((condition, depth)) = pending.pop() {
+                count += 1;
+                if count > MAX_CONDITIONS || depth > MAX_CONDITION_DEPTH {
+                    return Err(Error::TooManyConditions);
+                }
+                let repeated = match condition {
+                    Condition::MessageHash(value) => hash.replace(*value).is_some(),
+                    Condition::Beneficiary(value) => beneficiary.replace(*value).is_some(),
+                    Condition::Timelock(value) => expiry.replace(*value).is_some(),
+                    Condition::And(nested) => {
+                        pending.extend(nested.iter().rev().map(|condition| (condition, depth + 1)));
+                        false
+                    }
+                };
+                if repeated {
+                    return Err(Error::InvalidConditions);
+                }
+            }
+
+            let hash = hash.ok_or(Error::InvalidConditions)?;
+            Ok(LockInfo {
+                expiry,
+                ..self.native_lock(amount, hash, beneficiary)
+            })
+        }
+
+        /// Body of `lock_token`, executed under the reentrancy guard.
+        fn lock_token_inner(
+            &mut self,
+            token: AccountId,
+            amount: Balance,
+            hash: [u8; 32],
+            beneficiary: Option<AccountId>,
+            expiry: Option<Timestamp>,
+            cancel_grace: Option<Timestamp>,
+        ) -> Result<u64, Error> {
+            let caller = self.env().caller();
+
+            if amount == 0 {
+                return Err(Error::ZeroAmount);
+            }
+            if self.paused {
+                return Err(Error::ContractPaused);
+            }
+            self.ensure_allowed()?;
+            if self.is_sunset() {
+                return Err(Error::Sunset);
+            }
+
+            // Pull the tokens from the caller's allowance.
+            let mut psp22: ink::contract_ref!(PSP22) = token.into();
+            match psp22
+                .call_mut()
+                .transfer_from(caller, self.env().account_id(), amount, Vec::new())
+                .call_flags(TOKEN_CALL_FLAGS)
+                .try_invoke()
+            {
+                Ok(Ok(Ok(()))) => {}
+                _ => return Err(Error::TokenTransferFailed),
+            }
+            let token_metadata = self.psp22_metadata(token);
+
+            Ok(self.insert_lock(LockInfo {
+                locker: caller,
+                amount,
+                secret_hash: hash,
+                beneficiary,
+                shield_salt: None,
+                expiry,
+                locked_at: self.env().block_timestamp(),
+                cancel_grace,
+                token: Some(token),
+                token_metadata: Some(token_metadata),
+                asset_id: None,
+                redeemer_pubkey: None,
+                nonce: 0,
+                vesting: None,
+                escrow: None,
+                arbitration: None,
+                extension: None,
+                approvals: None,
+                redeemers: Vec::new(),
+                memo: None,
+                split: Vec::new(),
+                recovery: None,
+            }))
+        }
+
+        /// Returns the display metadata of the PSP22 `token`, reading and caching it on
+        /// first use.
+        ///
+        /// A token that does not implement the metadata extension gets the native
+        /// metadata, which is not cached so the token is asked again on its next lock.
+        fn psp22_metadata(&mut self, token: AccountId) -> TokenMetadata {
+            if let Some(metadata) = self.token_metadata_cache.get(token) {
+                return metadata;
+            }
+            let psp22: ink::contract_ref!(PSP22Metadata) = token.into();
+            let decimals = psp22
+                .call()
+                .token_decimals()
+                .call_flags(TOKEN_CALL_FLAGS)
+                .try_invoke();
+            let symbol = psp22
+                .call()
+                .token_symbol()
+                .call_flags(TOKEN_CALL_FLAGS)
+                .try_invoke();
+            match (decimals, symbol) {
+                (Ok(Ok(decimals)), Ok(Ok(symbol))) => {
+                    let metadata = TokenMetadata {
+                        decimals,
+                        symbol: symbol.unwrap_or_default().into_bytes(),
+                    };
+                    self.token_metadata_cache.insert(token, &metadata);
+                    metadata
+                }
+                _ => self.token_metadata(),
+            }
+        }
+
+        /// Stores `lock` under the next id, emits `Locked` and returns the id.
+        fn insert_lock(&mut self, lock: LockInfo) -> u64 {
+            let lock_id = self.next_lock_id;
+            self.next_lock_id += 1;
+            self.store_lock(lock_id, &lock);
+            let commitment = self.lock_commitment(&lock);
+            self.lock_commitments.insert(lock_id, &commitment);
+            self.stats.total_locks = self.stats.total_locks.saturating_add(1);
+            if lock.token.is_none() && lock.asset_id.is_none() && lock.shield_salt.is_none() {
+                self.stats.cumulative_volume =
+                    self.stats.cumulative_volume.saturating_add(lock.amount);
+            }
+
+            // Emit the Locked event, hiding the amount of a shielded lock.
+            let (amount, amount_commitment) = match lock.shield_salt {
+                Some(salt) => (None, Some(self.amount_commitment(lock.amount, salt))),
+                None => (Some(lock.amount), None),
+            };
+            self.env().emit_event(Locked {
+                chain_id: self.chain_id,
+                lock_id,
+                locker: lock.locker,
+                amount,
+                amount_commitment,
+                hash: lock.secret_hash,
+                beneficiary: lock.beneficiary,
+                expiry: lock.expiry,
+                token: lock.token,
+                asset_id: lock.asset_id,
+                funded_by: self.env().caller(),
+                timestamp: self.env().block_timestamp(),
+                block_number: self.env().block_number(),
+                memo_hash: self.memo_hash(&lock),
+                commitment,
+            });
+
+            lock_id
+        }
+
+        /// Computes the commitment to the immutable fields of the new `lock`, see
+        /// `get_lock_commitment`.
+        fn lock_commitment(&self, lock: &LockInfo) -> [u8; 32] {
+            let fields = (
+                lock.locker,
+                lock.beneficiary,
+                lock.amount,
+                lock.secret_hash,
+                lock.redeemer_pubkey,
+                lock.token,
+                lock.asset_id,
+                lock.shield_salt,
+                lock.locked_at,
+                self.fee_bps,
+            );
+            self.env()
+                .hash_bytes::<ink::env::hash::Blake2x256>(&scale::Encode::encode(&fields))
+        }
+
+        /// Stores `lock` under `lock_id`, keeping the open-lock index and the running
+        /// totals in step.
+        fn store_lock(&mut self, lock_id: u64, lock: &LockInfo) {
+            match self.locks.get(lock_id) {
+                Some(previous) => self.untrack(&previous),
+                None => {
+                    self.open_locks.insert(self.active_locks, &lock_id);
+                    self.open_position.insert(lock_id, &self.active_locks);
+                    self.active_locks += 1;
+                }
+            }
+            self.track(lock);
+            self.locks.insert(lock_id, lock);
+        }
+
+        /// Removes lock `lock_id`, if stored, from storage, the open-lock index and the
+        /// running totals.
+        fn remove_lock(&mut self, lock_id: u64) {
+            let Some(lock) = self.locks.take(lock_id) else {
+                return;
+            };
+            self.untrack(&lock);
+            self.scheduled_claims.remove(lock_id);
+            self.lock_commitments.remove(lock_id);
+            // Move the last open lock into the freed position.
+            let position = self.open_position.take(lock_id).unwrap_or_default();
+            self.active_locks -= 1;
+            let last = self.open_locks.take(self.active_locks).unwrap_or_default();
+            if position != self.active_locks {
+                self.open_locks.insert(position, &last);
+                self.open_position.insert(last, &position);
+            }
+        }
+
+        /// Adds `lock` to the running totals.
+        fn track(&mut self, lock: &LockInfo) {
+            if lock.token.is_none() && lock.asset_id.is_none() {
+                self.native_locked = self.native_locked.saturating_add(lock.amount);
+            }
+            if let Some(arbitration) = lock.arbitration {
+                self.native_locked = self.native_locked.saturating_add(arbitration.bond);
+            }
+            if Self::earns_rewards(lock) {
+                self.earning_locked = self.earning_locked.saturating_add(lock.amount);
+                self.earning_weight = self
+                    .earning_weight
+                    .saturating_add(lock.amount.saturating_mul(Self::weight_secs(lock)));
+            }
+        }
+
+        /// Takes `lock` out of the running totals.
+        fn untrack(&mut self, lock: &LockInfo) {
+            if lock.token.is_none() && lock.asset_id.is_none() {
+                self.native_locked = self.native_locked.saturating_sub(lock.amount);
+            }
+            if let Some(arbitration) = lock.arbitration {
+                self.native_locked = self.native_locked.saturating_sub(arbitration.bond);
+            }
+            if Self::earns_rewards(lock) {
+                self.earning_locked = self.earning_locked.saturating_sub(lock.amount);
+                self.earning_weight = self
+                    .earning_weight
+                    .saturating_sub(lock.amount.saturating_mul(Self::weight_secs(lock)));
+            }
+        }
+
+        /// `locked_at` of `lock` in whole seconds, as counted in `earning_weight`.
+        fn weight_secs(lock: &LockInfo) -> Balance {
+            Balance::from(lock.locked_at / 1_000)
+        }
+
+        /// Runs `payout` once the lock's new state is stored, putting `stored` back under
+        /// `lock_id` if it fails so the user can retry.
+        ///
+        /// Settlements update storage before transferring (checks-effects-interactions), so
+        /// a reentrant or later call never sees funds that have already left. A payout of
+        /// several transfers can fail after some of them went through; those are only
+        /// reverted if the returned error is propagated out of the message, so callers
+        /// must never swallow it.
+        fn settle(
+            &mut self,
+            lock_id: u64,
+            stored: &LockInfo,
+            payout: impl FnOnce(&mut Self) -> Result<(), Error>,
+        ) -> Result<(), Error> {
+            let result = payout(self);
+            if result.is_err() {
+                self.store_lock(lock_id, stored);
+            }
+            result
+        }
+
+        /// Appends a settlement of `amount` from `lock` to the history.
+        fn record(&mut self, lock_id: u64, lock: &LockInfo, amount: Balance, outcome: Settlement) {
+            self.history.insert(
+                self.history_len,
+                &LockRecord {
+                    lock_id,
+                    locker: lock.locker,
+                    amount,
+                    locked_at: lock.locked_at,
+                    completed_at: self.env().block_timestamp(),
+                    outcome,
+                },
+            );
+            self.history_len += 1;
+
+            let counter = match outcome {
+                Settlement::Redeemed
+                | Settlement::Claimed
+                | Settlement::Released
+                | Settlement::Resolved => &mut self.stats.total_redeemed,
+                Settlement::Refunded
+                | Settlement::Withdrawn
+                | Settlement::Rejected
+                | Settlement::Recovered => &mut self.stats.total_refunded,
+                Settlement::Cancelled => &mut self.stats.total_cancelled,
+            };
+            *counter = counter.saturating_add(1);
+            if lock.shield_salt.is_some() {
+                self.stats.cumulative_volume = self.stats.cumulative_volume.saturating_add(amount);
+            }
+        }
+
+        /// Sends `amount` of the asset held by `lock` to `to`.
+        fn pay_out(
+            &mut self,
+            lock: &LockInfo,
+            to: AccountId,
+            amount: Balance,
+        ) -> Result<(), Error> {
+            #[cfg(feature = "assets")]
+            if let Some(asset_id) = lock.asset_id {
+                return self
+                    .env()
+                    .extension()
+                    .transfer(asset_id, to, amount)
+                    .map_err(|_| Error::TokenTransferFailed);
+            }
+            match lock.token {
+                None => self
+                    .env()
+                    .transfer(to, amount)
+                    .map_err(|_| Error::TransferFailed),
+                Some(token) => {
+                    let mut psp22: ink::contract_ref!(PSP22) = token.into();
+                    match psp22
+                        .call_mut()
+                        .transfer(to, amount, Vec::new())
+                        .call_flags(TOKEN_CALL_FLAGS)
+                        .try_invoke()
+                    {
+                        Ok(Ok(Ok(()))) => Ok(()),
+                        _ => Err(Error::TokenTransferFailed),
+                    }
+                }
+            }
+        }
+
+        /// Returns `lock` as queries may show it: a shielded lock has its amount and salt
+        /// zeroed.
+        fn masked(mut lock: LockInfo) -> LockInfo {
+            if lock.shield_salt.is_some() {
+                lock.amount = 0;
+                lock.shield_salt = Some([0; 32]);
+            }
+            lock
+        }
+
+        /// Computes the challenge signed for a signature redemption of `lock_id`.
+        fn challenge(&self, lock_id: u64, nonce: u64) -> [u8; 32] {
+            let input = scale::Encode::encode(&(self.env().account_id(), lock_id, nonce));
+            self.env().hash_bytes::<ink::env::hash::Blake2x256>(&input)
+        }
+
+        /// Fails with `NotOwner` unless the caller is the current owner.
+        fn ensure_owner(&self) -> Result<(), Error> {
+            if self.owner != Some(self.env().caller()) {
+                return Err(Error::NotOwner);
+            }
+            Ok(())
+        }
+
+        /// Describes lock `lock_id`, just created from the transferred value, for the caller.
+        fn receipt(&self, lock_id: u64) -> LockReceipt {
+            LockReceipt {
+                lock_id,
+                amount: self.env().transferred_value(),
+                timestamp: self.env().block_timestamp(),
+                block_number: self.env().block_number(),
+            }
+        }
+
+        /// Fails with `NotAllowed` if the allow-list is enforced and the caller is not on it.
+        fn ensure_allowed(&self) -> Result<(), Error> {
+            if self.allowlist_enabled && !self.allowlist.contains(self.env().caller()) {
+                return Err(Error::NotAllowed);
+            }
+            Ok(())
+        }
+
+        /// Replaces the owner and emits `OwnershipTransferred`.
+        fn set_owner(&mut self, new: Option<AccountId>) {
+            let previous = core::mem::replace(&mut self.owner, new);
+            self.env().emit_event(OwnershipTransferred {
+                chain_id: self.chain_id,
+                previous,
+                new,
+            });
+        }
+
+        /// Returns how much of `lock` has vested but not been claimed yet.
+        ///
+        /// The vested amount is the exact floor of `total * elapsed / duration`, computed
+        /// without overflow, so claims never exceed the total; from `start + duration` on,
+        /// everything left is claimable, which sweeps any rounding dust.
+        fn claimable_of(&self, lock: &LockInfo) -> Balance {
+            let Some(vesting) = lock.vesting else {
+                return 0;
+            };
+            let elapsed = self.env().block_timestamp().saturating_sub(vesting.start);
+            if elapsed >= vesting.duration {
+                return lock.amount;
+            }
+            let (elapsed, duration) = (Balance::from(elapsed), Balance::from(vesting.duration));
+            let vested =
+                vesting.total / duration * elapsed + vesting.total % duration * elapsed / duration;
+            let claimed = vesting.total - lock.amount;
+            vested.saturating_sub(claimed)
+        }
+
+        /// Computes the redemption fee on `amount`, rounded down in favor of the redeemer.
+        ///
+        /// Splitting `amount` at 10 000 gives the exact floor of `amount * fee_bps / 10 000`
+        /// without the multiplication overflowing for large amounts.
+        fn fee_on(&self, amount: Balance) -> Balance {
+            let bps = Balance::from(self.fee_bps);
+            amount / 10_000 * bps + amount % 10_000 * bps / 10_000
+        }
+
+        /// Returns the bond `raise_dispute` requires on a lock of `amount`.
+        fn dispute_bond_on(&self, amount: Balance) -> Balance {
+            let bps = Balance::from(self.dispute_bond_bps);
+            let bond = amount / 10_000 * bps + amount % 10_000 * bps / 10_000;
+            bond.max(self.dispute_bond_floor)
+        }
+
+        /// Pays the dispute bond held by `lock`, if any, to `to`.
+        ///
+        /// Bonds are posted in native tokens, whatever the lock holds.
+        fn pay_bond(&mut self, lock: &LockInfo, to: AccountId) -> Result<(), Error> {
+            let bond = lock.arbitration.map_or(0, |arbitration| arbitration.bond);
+            if bond > 0 {
+                self.env()
+                    .transfer(to, bond)
+                    .map_err(|_| Error::TransferFailed)?;
+            }
+            Ok(())
+        }
+
+        /// Returns `true` if `lock` has an expiry that has been reached.
+        fn is_expired(&self, lock: &LockInfo) -> bool {
+            lock.expiry
+                .is_some_and(|expiry| self.env().block_timestamp() >= expiry)
+        }
+
+        /// Returns how much of a native payout of `amount` from `lock` would take the
+        /// contract below the existential deposit.
+        ///
+        /// Fails with `WouldReapContract` if there is any and the reap policy is `Fail`.
+        fn reap_shortfall(&self, lock: &LockInfo, amount: Balance) -> Result<Balance, Error> {
+            if lock.token.is_some() || lock.asset_id.is_some() {
+                return Ok(0);
+            }
+            let available = self
+                .env()
+                .balance()
+                .saturating_sub(self.env().minimum_balance());
+            let shortfall = amount.saturating_sub(available);
+            if shortfall > 0 && self.reap_policy == ReapPolicy::Fail {
+                return Err(Error::WouldReapContract);
+            }
+            Ok(shortfall)
+        }
+
+        /// Loads lock `lock_id` for `caller` to propose or accept an extension.
+        fn extendable(&self, lock_id: u64, caller: AccountId) -> Result<LockInfo, Error> {
+            let lock = self.locks.get(lock_id).ok_or(Error::LockNotFound)?;
+            if lock.beneficiary.is_none()
+                || (caller != lock.locker && Some(caller) != lock.beneficiary)
+            {
+                return Err(Error::NotParty);
+            }
+            if Self::is_disputed(&lock) {
+                return Err(Error::Disputed);
+            }
+            if self.is_expired(&lock) {
+                return Err(Error::Expired);
+            }
+            Ok(lock)
+        }
+
+        /// Returns `true` once the sunset timestamp, if any, has been reached.
+        fn is_sunset(&self) -> bool {
+            self.sunset_at
+                .is_some_and(|sunset_at| self.env().block_timestamp() >= sunset_at)
+        }
+
+        /// Runs `f` with the reentrancy flag held.
+        ///
+        /// Every settlement message goes through this wrapper so that a nested call back
+        /// into the contract while funds are moving is rejected with `ReentrantCall`.
+        /// Token calls are made with `TOKEN_CALL_FLAGS` as well, so on-chain the runtime
+        /// already refuses a token that calls back in.
+        fn guarded<T>(
+            &mut self,
+            f: impl FnOnce(&mut Self) -> Result<T, Error>,
+        ) -> Result<T, Error> {
+            if self.entered.get().unwrap_or(false) {
+                return Err(Error::ReentrantCall);
+            }
+            self.entered.set(&true);
+            let result = f(self);
+            self.entered.set(&false);
+            result
+        }
+
+        /// Body of `redeem`, executed under the reentrancy guard.
+        ///
+        /// The first of `proofs` that satisfies the lock is used; if none does, the error
+        /// for the last one is returned.
+        fn redeem_inner(
+            &mut self,
+            lock_id: u64,
+            proofs: impl IntoIterator<Item = Proof>,
+            amount: Option<Balance>,
+        ) -> Result<(), Error> {
+            let caller = self.env().caller();
+            let mut planned = Err(Error::InvalidPreimage);
+            for proof in proofs {
+                planned = self.plan_redeem(caller, lock_id, proof, amount);
+                if planned.is_ok() {
+                    break;
+                }
+            }
+            self.apply_redeem(lock_id, caller, planned?)
+        }
+
+        /// Carries out the redemption `plan` of lock `lock_id` for `caller`.
+        ///
+        /// Every check is done by `plan_redeem`, so only a payout can still fail here.
+        /// Its error must be returned from the message, which reverts the transfers
+        /// already made, rather than being collected into a per-item result.
+        fn apply_redeem(
+            &mut self,
+            lock_id: u64,
+            caller: AccountId,
+            plan: RedeemPlan,
+        ) -> Result<(), Error> {
+            let RedeemPlan {
+                stored,
+                mut lock,
+                amount,
+                remaining,
+                reward,
+                fee,
+                payout,
+                shortfall,
+                tip,
+            } = plan;
+
+            // Remove the lock once drained, otherwise keep the remainder locked. Either way
+            // a pending extension proposal is discarded, and a recovery window restarts.
+            if remaining == 0 {
+                self.remove_lock(lock_id);
+            } else {
+                lock.amount = remaining;
+                lock.extension = None;
+                if let Some(recovery) = lock.recovery.as_mut() {
+                    recovery.active_since = self.env().block_timestamp();
+                }
+                self.store_lock(lock_id, &lock);
+            }
+
+            self.reward_pool -= reward;
+
+            // Transfer the assets and the reward to the caller, or to the split recipients,
+            // minus the fee.
+            let shares = Self::split_shares(&lock, caller, payout);
+            let paid = self.settle(lock_id, &stored, |this| {
+                for &(to, share) in &shares {
+                    this.pay_out(&lock, to, share)?;
+                }
+                if let Some((keeper, tip)) = tip.filter(|&(_, tip)| tip > 0) {
+                    this.pay_out(&lock, keeper, tip)?;
+                }
+                if fee > 0 {
+                    this.pay_out(&lock, this.fee_collector, fee)?;
+                }
+                Ok(())
+            });
+            if let Err(error) = paid {
+                self.reward_pool += reward;
+                return Err(error);
+            }
+            self.record(lock_id, &lock, amount, Settlement::Redeemed);
+
+            // Emit the Redeemed event.
+            self.env().emit_event(Redeemed {
+                chain_id: self.chain_id,
+                lock_id,
+                locker: lock.locker,
+                beneficiary: lock.beneficiary,
+                redeemer: caller,
+                amount,
+                salt: lock.shield_salt,
+                remaining,
+                fee,
+                timestamp: self.env().block_timestamp(),
+                block_number: self.env().block_number(),
+                reward,
+                shortfall,
+                memo_hash: self.memo_hash(&lock),
+            });
+            if !lock.split.is_empty() {
+                for (to, amount) in shares {
+                    self.env().emit_event(PayoutSent {
+                        chain_id: self.chain_id,
+                        lock_id,
+                        to,
+                        amount,
+                    });
+                }
+            }
+
+            Ok(())
+        }
+
+        /// Fails with `BatchTooLarge` if a batch of `len` entries exceeds `max`.
+        fn check_batch_len(len: usize, max: u32) -> Result<(), Error> {
+            let got = u32::try_from(len).unwrap_or(u32::MAX);
+            if got > max {
+                return Err(Error::BatchTooLarge { max, got });
+            }
+            Ok(())
+        }
+
+        /// Divides `payout` between the split recipients of `lock`, with the rounding dust
+        /// going to the first one, or assigns all of it to `caller` if there is no split.
+        fn split_shares(
+            lock: &LockInfo,
+            caller: AccountId,
+            payout: Balance,
+        ) -> Vec<(AccountId, Balance)> {
+            if lock.split.is_empty() {
+                return vec![(caller, payout)];
+            }
+            let mut shares: Vec<(AccountId, Balance)> = lock
+                .split
+                .iter()
+                .map(|&(to, bps)| {
+                    let bps = Balance::from(bps);
+                    (to, payout / 10_000 * bps + payout % 10_000 * bps / 10_000)
+                })
+                .collect();
+            let paid: Balance = shares.iter().map(|&(_, share)| share).sum();
+            shares[0].1 += payout - paid;
+            shares
+        }
+
+        /// Checks a redemption of `lock_id` by `caller` and works out its amounts, without
+        /// touching storage. Shared by `redeem` and `can_redeem` so the two cannot disagree.
+        fn plan_redeem(
+            &self,
+            caller: AccountId,
+            lock_id: u64,
+            proof: Proof,
+            amount: Option<Balance>,
+        ) -> Result<RedeemPlan, Error> {
+            let stored = self.locks.get(lock_id).ok_or(Error::LockNotFound)?;
+            let mut lock = stored.clone();
+            Self::check_redeemer(&lock, caller)?;
+
+            match proof {
+                // Verify the preimage hashes to the committed secret.
+                Proof::Preimage(preimage) => {
+                    if self
+                        .env()
+                        .hash_bytes::<ink::env::hash::Blake2x256>(&preimage)
+                        != lock.secret_hash
+                    {
+                        return Err(Error::InvalidPreimage);
+                    }
+                }
+                // Verify the signature recovers to the redeemer key, then advance the nonce
+                // so it cannot be replayed.
+                Proof::Signature(signature) => {
+                    let pubkey = lock.redeemer_pubkey.ok_or(Error::NoRedeemerKey)?;
+                    let recovered = self
+                        .env()
+                        .ecdsa_recover(&signature, &self.challenge(lock_id, lock.nonce))
+                        .map_err(|_| Error::InvalidSignature)?;
+                    if recovered != pubkey {
+                        return Err(Error::InvalidSignature);
+                    }
+                    lock.nonce += 1;
+                }
+            }
+
+            self.quote_redeem(stored, lock, amount, self.env().block_timestamp())
+        }
+
+        /// Checks that `lock` is redeemed with `redeem` and that `caller` may redeem it.
+        fn check_redeemer(lock: &LockInfo, caller: AccountId) -> Result<(), Error> {
+            if lock.vesting.is_some() {
+                return Err(Error::VestingLock);
+            }
+            if lock.escrow.is_some() {
+                return Err(Error::EscrowLock);
+            }
+            if lock.approvals.is_some() {
+                return Err(Error::ApprovalLock);
+            }
+            if Self::is_disputed(lock) {
+                return Err(Error::Disputed);
+            }
+
+            // Ensure the caller is entitled to redeem: the beneficiary if one was named,
+            // the locker or one of the listed redeemers otherwise.
+            match lock.beneficiary {
+                Some(beneficiary) if caller != beneficiary => Err(Error::NotBeneficiary),
+                None if caller != lock.locker && !lock.redeemers.contains(&caller) => {
+                    Err(Error::NotLocker)
+                }
+                _ => Ok(()),
+            }
+        }
+
+        /// Works out the amounts of redeeming `amount` of `lock`, or all of it, at `now`.
+        ///
+        /// `stored` is the lock as stored and `lock` the one to keep, which differs in its
+        /// nonce after a signature. This is the fee pipeline of `redeem`, shared with
+        /// `preview_settlement` so a preview cannot drift from the real settlement.
+        fn quote_redeem(
+            &self,
+            stored: LockInfo,
+            lock: LockInfo,
+            amount: Option<Balance>,
+            now: Timestamp,
+        ) -> Result<RedeemPlan, Error> {
+            // Once expired, the lock can only be refunded.
+            if lock.expiry.is_some_and(|expiry| now >= expiry) {
+                return Err(Error::Expired);
+            }
+
+            // A shielded lock is settled whole, or a partial settlement would publish it.
+            if lock.shield_salt.is_some() && amount.is_some_and(|amount| amount != lock.amount) {
+                return Err(Error::Shielded);
+            }
+
+            // Determine the amount to withdraw and what remains locked.
+            let amount = amount.unwrap_or(lock.amount);
+            if amount == 0 {
+                return Err(Error::ZeroAmount);
+            }
+            let remaining = lock
+                .amount
+                .checked_sub(amount)
+                .ok_or(Error::InsufficientLockedBalance)?;
+
+            // Take the accrued reward from the pool, as far as it reaches, and withhold
+            // whatever would reap the contract from the fee first, then from the caller.
+            let reward = self.accrued_on(&stored, amount, now).min(self.reward_pool);
+            let mut fee = self.fee_on(amount);
+            let mut payout = (amount - fee).checked_add(reward).ok_or(Error::Overflow)?;
+            let total = payout.checked_add(fee).ok_or(Error::Overflow)?;
+            let shortfall = self.reap_shortfall(&lock, total)?;
+            let withheld = shortfall.min(fee);
+            fee -= withheld;
+            payout -= shortfall - withheld;
+
+            Ok(RedeemPlan {
+                stored,
+                lock,
+                amount,
+                remaining,
+                reward,
+                fee,
+                payout,
+                shortfall,
+                tip: None,
+            })
+        }
+
+        /// Body of `claim`, executed under the reentrancy guard.
+        fn claim_inner(&mut self, lock_id: u64) -> Result<(), Error> {
+            let caller = self.env().caller();
+            let stored = self.locks.get(lock_id).ok_or(Error::LockNotFound)?;
+            let vesting = stored.vesting.ok_or(Error::NotVesting)?;
+
+            // Ensure the caller is entitled to claim.
+            match stored.beneficiary {
+                Some(beneficiary) if caller != beneficiary => return Err(Error::NotBeneficiary),
+                None if caller != stored.locker => return Err(Error::NotLocker),
+                _ => {}
+            }
+
+            let amount = self.claimable_of(&stored);
+            if amount == 0 {
+                return Err(Error::NothingToClaim);
+            }
+            let remaining = stored.amount - amount;
+
+            // Update the lock, then transfer the claimed assets minus the fee.
+            let mut lock = stored.clone();
+            if remaining == 0 {
+                self.remove_lock(lock_id);
+            } else {
+                lock.amount = remaining;
+                self.store_lock(lock_id, &lock);
+            }
+            let fee = self.fee_on(amount);
+            self.settle(lock_id, &stored, |this| {
+                this.pay_out(&lock, caller, amount - fee)?;
+                if fee > 0 {
+                    this.pay_out(&lock, this.fee_collector, fee)?;
+                }
+                Ok(())
+            })?;
+            self.record(lock_id, &lock, amount, Settlement::Claimed);
+
+            // Emit the StreamWithdrawn event for streams, the Claimed event otherwise.
+            if vesting.stream {
+                self.env().emit_event(StreamWithdrawn {
+                    chain_id: self.chain_id,
+                    lock_id,
+                    beneficiary: caller,
+                    amount,
+                    withdrawn: vesting.total - remaining,
+                    remaining,
+                    fee,
+                });
+            } else {
+                self.env().emit_event(Claimed {
+                    chain_id: self.chain_id,
+                    lock_id,
+                    recipient: caller,
+                    amount,
+                    remaining,
+                    fee,
+                });
+            }
+
+            Ok(())
+        }
+
+        /// Returns stream `lock_id`, or `NotStream` for other locks.
+        fn stream_of(&self, lock_id: u64) -> Result<LockInfo, Error> {
+            let lock = self.locks.get(lock_id).ok_or(Error::LockNotFound)?;
+            if !lock.vesting.is_some_and(|vesting| vesting.stream) {
+                return Err(Error::NotStream);
+            }
+            Ok(lock)
+        }
+
+        /// Body of `cancel_stream`, executed under the reentrancy guard.
+        fn cancel_stream_inner(&mut self, lock_id: u64) -> Result<(), Error> {
+            let lock = self.stream_of(lock_id)?;
+            if self.env().caller() != lock.locker {
+                return Err(Error::NotLocker);
+            }
+            let beneficiary = lock.beneficiary.ok_or(Error::NotBeneficiary)?;
+
+            // Split the balance: what has streamed goes to the beneficiary, the rest back.
+            let streamed = self.claimable_of(&lock);
+            let returned = lock.amount - streamed;
+            let fee = self.fee_on(streamed);
+
+            // Remove the lock, then transfer both parts.
+            self.remove_lock(lock_id);
+            self.settle(lock_id, &lock, |this| {
+                if streamed > fee {
+                    this.pay_out(&lock, beneficiary, streamed - fee)?;
+                }
+                if fee > 0 {
+                    this.pay_out(&lock, this.fee_collector, fee)?;
+                }
+                if returned > 0 {
+                    this.pay_out(&lock, lock.locker, returned)?;
+                }
+                Ok(())
+            })?;
+            if streamed > 0 {
+                self.record(lock_id, &lock, streamed, Settlement::Claimed);
+            }
+            if returned > 0 {
+                self.record(lock_id, &lock, returned, Settlement::Cancelled);
+            }
+
+            // Emit the StreamCancelled event.
+            self.env().emit_event(StreamCancelled {
+                chain_id: self.chain_id,
+                lock_id,
+                beneficiary,
+                streamed,
+                fee,
+                returned,
+            });
+
+            Ok(())
+        }
+
+        /// Returns escrow lock `lock_id` with its terms, or `NotEscrow` for other locks.
+        fn escrow_of(&self, lock_id: u64) -> Result<(LockInfo, Escrow), Error> {
+            let lock = self.locks.get(lock_id).ok_or(Error::LockNotFound)?;
+            let escrow = lock.escrow.ok_or(Error::NotEscrow)?;
+            Ok((lock, escrow))
+        }
+
+        /// Returns lock `lock_id` with its approvals and the caller's approval bit, or
+        /// `NotApprover` unless the caller is one of its approvers.
+        fn approvals_of(&self, lock_id: u64) -> Result<(LockInfo, Approvals, u8), Error> {
+            let lock = self.locks.get(lock_id).ok_or(Error::LockNotFound)?;
+            let approvals = lock.approvals.ok_or(Error::NotApprover)?;
+            let caller = self.env().caller();
+            let index = approvals
+                .approvers
+                .iter()
+                .position(|approver| *approver == caller)
+                .ok_or(Error::NotApprover)?;
+            Ok((lock, approvals, 1 << index))
+        }
+
+        /// Returns the Blake2x256 hash of the memo of `lock`, if it has one.
+        fn memo_hash(&self, lock: &LockInfo) -> Option<[u8; 32]> {
+            lock.memo.as_ref().map(|memo| {
+                self.env()
+                    .hash_bytes::<ink::env::hash::Blake2x256>(memo.as_bytes())
+            })
+        }
+
+        /// Whether lock `lock` is frozen by a dispute awaiting its arbiter.
+        fn is_disputed(lock: &LockInfo) -> bool {
+            lock.arbitration
+                .is_some_and(|arbitration| arbitration.disputed_by.is_some())
+        }
+
+        /// Returns every stored lock with its id, in no particular order.
+        fn active_lock_list(&self) -> Vec<(u64, LockInfo)> {
+            (0..self.active_locks)
+                .filter_map(|position| self.open_locks.get(position))
+                .filter_map(|lock_id| self.locks.get(lock_id).map(|lock| (lock_id, lock)))
+                .collect()
+        }
+
+        /// Whether `lock` earns a reward when redeemed: only native locks settled with
+        /// `redeem` do.
+        fn earns_rewards(lock: &LockInfo) -> bool {
+            lock.token.is_none()
+                && lock.asset_id.is_none()
+                && lock.vesting.is_none()
+                && lock.escrow.is_none()
+        }
+
+        /// Returns an upper bound on the rewards accrued so far by all stored locks.
+        ///
+        /// Computed from the running totals rather than lock by lock, so time locked counts
+        /// to the second instead of in whole days, and rounding errs toward keeping the
+        /// pool.
+        fn committed_rewards(&self) -> Balance {
+            let now_secs = Balance::from(self.env().block_timestamp().div_ceil(1_000));
+            let amount_secs = now_secs
+                .saturating_mul(self.earning_locked)
+                .saturating_sub(self.earning_weight);
+            let amount_days = amount_secs.div_ceil(MILLIS_PER_DAY as Balance / 1_000);
+            let rate = Balance::from(self.rate_bps_per_day);
+            (amount_days / 10_000)
+                .saturating_mul(rate)
+                .saturating_add(((amount_days % 10_000).saturating_mul(rate)).div_ceil(10_000))
+        }
+
+        /// Returns the reward accrued by `now` on redeeming `amount` from `lock`.
+        ///
+        /// Only native locks settled with `redeem` earn rewards.
+        fn accrued_on(&self, lock: &LockInfo, amount: Balance, now: Timestamp) -> Balance {
+            if !Self::earns_rewards(lock) {
+                return 0;
+            }
+            let days = now.saturating_sub(lock.locked_at) / MILLIS_PER_DAY;
+            let rate = Balance::from(self.rate_bps_per_day).saturating_mul(Balance::from(days));
+            (amount / 10_000)
+                .saturating_mul(rate)
+                .saturating_add((amount % 10_000).saturating_mul(rate) / 10_000)
+        }
+
+        /// Body of `sweep_excess`, executed under the reentrancy guard.
+        fn sweep_excess_inner(&mut self, recovery: AccountId) -> Result<Balance, Error> {
+            self.ensure_owner()?;
+            let amount = self.get_excess_balance();
+            if amount > 0 {
+                self.env()
+                    .transfer(recovery, amount)
+                    .map_err(|_| Error::TransferFailed)?;
+                self.env().emit_event(ExcessSwept {
+                    chain_id: self.chain_id,
+                    recovery,
+                    amount,
+                });
+            }
+            Ok(amount)
+        }
+
+        /// Body of `withdraw_rewards`, executed under the reentrancy guard.
+        fn withdraw_rewards_inner(&mut self, amount: Balance) -> Result<(), Error> {
+            self.ensure_owner()?;
+            let owner = self.owner.ok_or(Error::NotOwner)?;
+            let available = self.reward_pool.saturating_sub(self.committed_rewards());
+            if amount > available {
+                return Err(Error::RewardsCommitted);
+            }
+
+            self.reward_pool -= amount;
+            if self.env().transfer(owner, amount).is_err() {
+                self.reward_pool += amount;
+                return Err(Error::TransferFailed);
+            }
+            Ok(())
+        }
+
+        /// Body of `terminate`, executed under the reentrancy guard.
+        fn terminate_inner(&mut self) -> Result<(), Error> {
+            self.ensure_owner()?;
+            let now = self.env().block_timestamp();
+
+            // All active locks must be past the grace period.
+            let grace = self.termination_grace();
+            let stale = self.active_lock_list();
+            for (_, lock) in &stale {
+                let abandoned = lock
+                    .expiry
+                    .is_some_and(|expiry| now > expiry.saturating_add(grace));
+                if !abandoned {
+                    return Err(Error::ActiveLockExists);
+                }
+            }
+
+            // Refund each abandoned lock to its locker before terminating.
+            for (lock_id, lock) in stale {
+                self.remove_lock(lock_id);
+                self.settle(lock_id, &lock, |this| {
+                    this.pay_out(&lock, lock.locker, lock.amount)?;
+                    this.pay_bond(&lock, lock.locker)
+                })?;
+                self.record(lock_id, &lock, lock.amount, Settlement::Refunded);
+                self.env().emit_event(Refunded {
+                    chain_id: self.chain_id,
+                    lock_id,
+                    locker: lock.locker,
+                    amount: lock.amount,
+                });
+            }
+
+            let owner = self.owner.ok_or(Error::NotOwner)?;
+            self.env().terminate_contract(owner)
+        }
+
+        /// Body of `resolve`, executed under the reentrancy guard.
+        fn resolve_inner(&mut self, lock_id: u64, to_beneficiary: bool) -> Result<(), Error> {
+            let lock = self.locks.get(lock_id).ok_or(Error::LockNotFound)?;
+            let arbitration = lock.arbitration.ok_or(Error::NotArbitrated)?;
+            if self.env().caller() != arbitration.arbiter {
+                return Err(Error::NotArbiter);
+            }
+            let Some(disputer) = arbitration.disputed_by else {
+                return Err(Error::NotDisputed);
+            };
+
+            let winner = match lock.beneficiary {
+                Some(beneficiary) if to_beneficiary => beneficiary,
+                _ => lock.locker,
+            };
+
+            // Remove the lock, then transfer the assets minus the fee and the bond to the
+            // winner.
+            self.remove_lock(lock_id);
+            let fee = self.fee_on(lock.amount);
+            self.settle(lock_id, &lock, |this| {
+                this.pay_out(&lock, winner, lock.amount - fee)?;
+                if fee > 0 {
+                    this.pay_out(&lock, this.fee_collector, fee)?;
+                }
+                this.pay_bond(&lock, winner)
+            })?;
+            self.record(lock_id, &lock, lock.amount, Settlement::Resolved);
+
+            // Emit the Resolved event.
+            self.env().emit_event(Resolved {
+                chain_id: self.chain_id,
+                lock_id,
+                winner,
+                amount: lock.amount,
+                fee,
+                bond: arbitration.bond,
+                bond_returned: winner == disputer,
+            });
+
+            Ok(())
+        }
+
+        /// Body of `execute_redeem`, executed under the reentrancy guard.
+        fn execute_redeem_inner(&mut self, lock_id: u64) -> Result<(), Error> {
+            let lock = self.locks.get(lock_id).ok_or(Error::LockNotFound)?;
+            let approvals = lock.approvals.ok_or(Error::NotApprover)?;
+            if approvals.granted != 0b11 {
+                return Err(Error::InsufficientApprovals);
+            }
+
+            // Remove the lock, then transfer the assets minus the fee to the locker.
+            self.remove_lock(lock_id);
+            let fee = self.fee_on(lock.amount);
+            self.settle(lock_id, &lock, |this| {
+                this.pay_out(&lock, lock.locker, lock.amount - fee)?;
+                if fee > 0 {
+                    this.pay_out(&lock, this.fee_collector, fee)?;
+                }
+                Ok(())
+            })?;
+            self.record(lock_id, &lock, lock.amount, Settlement::Redeemed);
+
+            // Emit the ApprovedRedeemed event.
+            self.env().emit_event(ApprovedRedeemed {
+                chain_id: self.chain_id,
+                lock_id,
+                locker: lock.locker,
+                amount: lock.amount,
+                fee,
+            });
+
+            Ok(())
+        }
+
+        /// Body of `release`, executed under the reentrancy guard.
+        fn release_inner(&mut self, lock_id: u64) -> Result<(), Error> {
+            let (lock, escrow) = self.escrow_of(lock_id)?;
+            if !escrow.accepted {
+                return Err(Error::NotAccepted);
+            }
+
+            // The party that does not receive the funds releases them.
+            let (releaser, recipient, wrong_party) = match escrow.direction {
+                EscrowDirection::ToLocker => {
+                    (escrow.counterparty, lock.locker, Error::NotCounterparty)
+                }
+                EscrowDirection::ToCounterparty => {
+                    (lock.locker, escrow.counterparty, Error::NotLocker)
+                }
+            };
+            if self.env().caller() != releaser {
+                return Err(wrong_party);
+            }
+
+            // Remove the lock, then transfer the assets minus the fee.
+            self.remove_lock(lock_id);
+            let fee = self.fee_on(lock.amount);
+            self.settle(lock_id, &lock, |this| {
+                this.pay_out(&lock, recipient, lock.amount - fee)?;
+                if fee > 0 {
+                    this.pay_out(&lock, this.fee_collector, fee)?;
+                }
+                Ok(())
+            })?;
+            self.record(lock_id, &lock, lock.amount, Settlement::Released);
+
+            // Emit the EscrowReleased event.
+            self.env().emit_event(EscrowReleased {
+                chain_id: self.chain_id,
+                lock_id,
+                recipient,
+                amount: lock.amount,
+                fee,
+            });
+
+            Ok(())
+        }
+
+        /// Body of `withdraw`, executed under the reentrancy guard.
+        fn withdraw_inner(&mut self, lock_id: u64) -> Result<(), Error> {
+            let (lock, escrow) = self.escrow_of(lock_id)?;
+            if self.env().caller() != lock.locker {
+                return Err(Error::NotLocker);
+            }
+            if escrow.accepted {
+                return Err(Error::AlreadyAccepted);
+            }
+            if self.env().block_timestamp() < escrow.deadline {
+                return Err(Error::NotExpired);
+            }
+
+            // Remove the lock, then transfer the locked assets back to the locker.
+            self.remove_lock(lock_id);
+            self.settle(lock_id, &lock, |this| {
+                this.pay_out(&lock, lock.locker, lock.amount)
+            })?;
+            self.record(lock_id, &lock, lock.amount, Settlement::Withdrawn);
+
+            // Emit the EscrowWithdrawn event.
+            self.env().emit_event(EscrowWithdrawn {
+                chain_id: self.chain_id,
+                lock_id,
+                locker: lock.locker,
+                amount: lock.amount,
+            });
+
+            Ok(())
+        }
+
+        /// Body of `cancel`, executed under the reentrancy guard.
+        fn cancel_inner(&mut self, lock_id: u64) -> Result<(), Error> {
+            let caller = self.env().caller();
+            let lock = self.locks.get(lock_id).ok_or(Error::LockNotFound)?;
+            if lock.escrow.is_some() {
+                return Err(Error::EscrowLock);
+            }
+            if Self::is_disputed(&lock) {
+                return Err(Error::Disputed);
+            }
+
+            // Ensure the caller is the locker.
+            if caller != lock.locker {
+                return Err(Error::NotLocker);
+            }
+
+            // A lock naming a beneficiary can only be cancelled within its grace period.
+            if lock.beneficiary.is_some() {
+                let deadline = lock
+                    .cancel_grace
+                    .map(|grace| lock.locked_at.saturating_add(grace));
+                if deadline.is_none_or(|deadline| self.env().block_timestamp() >= deadline) {
+                    return Err(Error::CancelWindowClosed);
+                }
+            }
+
+            // Remove the lock, then transfer the locked assets back to the locker.
+            self.remove_lock(lock_id);
+            self.settle(lock_id, &lock, |this| {
+                this.pay_out(&lock, lock.locker, lock.amount)
+            })?;
+            self.record(lock_id, &lock, lock.amount, Settlement::Cancelled);
+
+            // Emit the Cancelled event.
+            self.env().emit_event(Cancelled {
+                chain_id: self.chain_id,
+                lock_id,
+                locker: lock.locker,
+                amount: lock.amount,
+            });
+
+            Ok(())
+        }
+
+        /// Body of `refund`, executed under the reentrancy guard.
+        fn refund_inner(&mut self, lock_id: u64) -> Result<(), Error> {
+            let caller = self.env().caller();
+            let lock = self.locks.get(lock_id).ok_or(Error::LockNotFound)?;
+            if Self::is_disputed(&lock) {
+                return Err(Error::Disputed);
+            }
+
+            // Ensure the caller is the locker.
+            if caller != lock.locker {
+                return Err(Error::NotLocker);
+            }
+
+            // Ensure the lock has expired.
+            if !self.is_expired(&lock) {
+                return Err(Error::NotExpired);
+            }
+
+            // Remove the lock, then transfer the locked assets back to the locker.
+            self.remove_lock(lock_id);
+            self.settle(lock_id, &lock, |this| {
+                this.pay_out(&lock, lock.locker, lock.amount)
+            })?;
+            self.record(lock_id, &lock, lock.amount, Settlement::Refunded);
+
+            // Emit the Refunded event.
+            self.env().emit_event(Refunded {
+                chain_id: self.chain_id,
+                lock_id,
+                locker: lock.locker,
+                amount: lock.amount,
+            });
+
+            Ok(())
+        }
+
+        /// Body of `recover`, executed under the reentrancy guard.
+        fn recover_inner(&mut self, lock_id: u64) -> Result<(), Error> {
+            let lock = self.locks.get(lock_id).ok_or(Error::LockNotFound)?;
+            let recovery = lock.recovery.ok_or(Error::NoRecoveryAccount)?;
+            if self.env().caller() != recovery.account {
+                return Err(Error::NotRecoveryAccount);
+            }
+            if self.env().block_timestamp() < recovery.active_since.saturating_add(recovery.delay) {
+                return Err(Error::RecoveryDelayNotElapsed);
+            }
+
+            // Remove the lock, then transfer the locked assets to the recovery account.
+            self.remove_lock(lock_id);
+            self.settle(lock_id, &lock, |this| {
+                this.pay_out(&lock, recovery.account, lock.amount)
+            })?;
+            self.record(lock_id, &lock, lock.amount, Settlement::Recovered);
+
+            self.env().emit_event(Recovered {
+                chain_id: self.chain_id,
+                lock_id,
+                original_locker: lock.locker,
+                recovery_account: recovery.account,
+                amount: lock.amount,
+            });
+
+            Ok(())
+        }
+
+        /// Body of `reject`, executed under the reentrancy guard.
+        fn reject_inner(&mut self, lock_id: u64) -> Result<(), Error> {
+            let lock = self.locks.get(lock_id).ok_or(Error::LockNotFound)?;
+            let beneficiary = lock.beneficiary.ok_or(Error::NoBeneficiary)?;
+            if self.env().caller() != beneficiary {
+                return Err(Error::NotBeneficiary);
+            }
+
+            // Remove the lock, then transfer the locked assets, and any dispute bond, back to
+            // the locker.
+            self.remove_lock(lock_id);
+            self.settle(lock_id, &lock, |this| {
+                this.pay_out(&lock, lock.locker, lock.amount)?;
+                this.pay_bond(&lock, lock.locker)
+            })?;
+            self.record(lock_id, &lock, lock.amount, Settlement::Rejected);
+
+            self.env().emit_event(Rejected {
+                chain_id: self.chain_id,
+                lock_id,
+                locker: lock.locker,
+                beneficiary,
+                amount: lock.amount,
+            });
+
+            Ok(())
+        }
+
+        /// Body of `execute_scheduled_claim`, executed under the reentrancy guard.
+        fn execute_scheduled_claim_inner(&mut self, lock_id: u64) -> Result<(), Error> {
+            let keeper = self.env().caller();
+            let claim = self
+                .scheduled_claims
+                .get(lock_id)
+                .ok_or(Error::NoScheduledClaim)?;
+            if self.env().block_timestamp() < claim.execute_after {
+                return Err(Error::ClaimNotDue);
+            }
+            let beneficiary = self
+                .locks
+                .get(lock_id)
+                .and_then(|lock| lock.beneficiary)
+                .ok_or(Error::LockNotFound)?;
+
+            // Redeem as the beneficiary, taking the tip out of their payout.
+            let mut plan =
+                self.plan_redeem(beneficiary, lock_id, Proof::Preimage(claim.preimage), None)?;
+            plan.payout = plan
+                .payout
+                .checked_sub(claim.tip)
+                .ok_or(Error::TipExceedsPayout)?;
+            plan.tip = Some((keeper, claim.tip));
+            self.apply_redeem(lock_id, beneficiary, plan)?;
+
+            self.env().emit_event(ScheduledClaimExecuted {
+                chain_id: self.chain_id,
+                lock_id,
+                keeper,
+                tip: claim.tip,
+            });
+            Ok(())
+        }
+
+        /// Body of `finalize_expired`, executed under the reentrancy guard.
+        fn finalize_expired_inner(&mut self, lock_id: u64) -> Result<(), Error> {
+            let caller = self.env().caller();
+            let lock = self.locks.get(lock_id).ok_or(Error::LockNotFound)?;
+            if Self::is_disputed(&lock) {
+                return Err(Error::Disputed);
+            }
+
+            // Ensure the lock has been expired for the whole grace period.
+            let now = self.env().block_timestamp();
+            let grace = self.finalize_grace.get().unwrap_or(DEFAULT_FINALIZE_GRACE);
+            if lock
+                .expiry
+                .is_none_or(|expiry| now < expiry.saturating_add(grace))
+            {
+                return Err(Error::NotExpired);
+            }
+
+            // The locker finalizing their own lock earns nothing.
+            let bounty = if caller == lock.locker {
+                0
+            } else {
+                match self
+                    .finalize_bounty
+                    .get()
+                    .unwrap_or(FinalizeBounty::Fixed(0))
+                {
+                    FinalizeBounty::Fixed(bounty) => bounty,
+                    FinalizeBounty::Bps(bps) => {
+                        let bps = Balance::from(bps);
+                        lock.amount / 10_000 * bps + lock.amount % 10_000 * bps / 10_000
+                    }
+                }
+            };
+            let amount = lock
+                .amount
+                .checked_sub(bounty)
+                .ok_or(Error::BountyExceedsLock)?;
+
+            // Remove the lock, then return the principal to the locker and pay the bounty.
+            self.remove_lock(lock_id);
+            self.settle(lock_id, &lock, |this| {
+                this.pay_out(&lock, lock.locker, amount)?;
+                if bounty > 0 {
+                    this.pay_out(&lock, caller, bounty)?;
+                }
+                Ok(())
+            })?;
+            self.record(lock_id, &lock, lock.amount, Settlement::Refunded);
+
+            self.env().emit_event(ExpiredFinalized {
+                chain_id: self.chain_id,
+                lock_id,
+                locker: lock.locker,
+                amount,
+                finalizer: caller,
+                bounty,
+            });
+
+            Ok(())
+        }
+    }
+
+    impl LockUnlock for LockUnlockSmartContract {
+        /// Locks the assets sent by the caller behind the secret whose Blake2x256 hash is `hash`
+        /// and returns the id of the new lock.
+        ///
+        /// If `beneficiary` is set, only that account can redeem the lock and receives the
+        /// funds; otherwise the locker redeems their own assets. If `expiry` is set, the lock
+        /// can no longer be redeemed from that timestamp on, and the locker may `refund` it
+        /// instead. `cancel_grace` is how long after locking the locker may still `cancel` a
+        /// lock that names a beneficiary. If `redeemer_pubkey` is set, the lock can also be
+        /// redeemed with `redeem_with_signature`.
+        ///
+        /// **Requirements:**
+        /// - The caller must send a non-zero amount of assets.
+        /// - The contract must not be paused.
+        /// - The amount must be within the configured `limits`.
+        /// - On a canary deployment, the amount must not exceed the canary ceiling and the
+        ///   sunset timestamp must not have been reached.
+        ///
+        /// **Effects:**
+        /// - Stores a new `LockInfo` under `next_lock_id` with the caller as `locker`, the
+        ///   amount sent, `hash` as the `secret_hash`, the `beneficiary`, the `expiry`, the
+        ///   `cancel_grace` and the `redeemer_pubkey`.
+        /// - Increments `next_lock_id`.
+        /// - Emits a `Locked` event.
+        /// - Returns a `LockReceipt` with the lock id, the amount and the current block.
+        #[ink(message, payable)]
+        fn lock(
+            &mut self,
+            hash: [u8; 32],
+            beneficiary: Option<AccountId>,
+            expiry: Option<Timestamp>,
+            cancel_grace: Option<Timestamp>,
+            redeemer_pubkey: Option<[u8; 33]>,
+        ) -> Result<LockReceipt, Error> {
+            let lock_id = self.lock_inner(
+                hash,
+                beneficiary,
+                expiry,
+                cancel_grace,
+                redeemer_pubkey,
+                None,
+            )?;
+            Ok(self.receipt(lock_id))
+        }
+
+        /// Redeems lock `lock_id` if the preimage of its secret hash is provided.
+        ///
+        /// `amount` selects how much to withdraw; `None` withdraws everything. A partial
+        /// redemption leaves the remainder locked under the same conditions.
+        ///
+        /// **Requirements:**
+        /// - The lock must exist.
+        /// - The caller must be the beneficiary if the lock names one, otherwise the same
+        ///   account that locked the assets.
+        /// - The Blake2x256 hash of `preimage` must equal the `secret_hash` given at lock time.
+        /// - The lock must not have expired.
+        /// - `amount`, if given, must be non-zero and must not exceed the locked amount.
+        /// - A native payout must leave the contract at or above the existential deposit
+        ///   (`WouldReapContract`), unless the contract was instantiated with
+        ///   `ReapPolicy::PayAvailable`, which pays out what it can spare instead.
+        ///
+        /// **Effects:**
+        /// - Transfers the requested amount to the beneficiary, or back to the `locker`, minus
+        ///   the redemption fee, which goes to the fee collector.
+        /// - Removes the lock from storage once nothing remains.
+        /// - Emits a `Redeemed` event.
+        ///
+        /// Fails with `ReentrantCall` if invoked while another settlement is in progress.
+        #[ink(message)]
+        fn redeem(
+            &mut self,
+            lock_id: u64,
+            preimage: Vec<u8>,
+            amount: Option<Balance>,
+        ) -> Result<(), Error> {
+            self.guarded(|this| this.redeem_inner(lock_id, [Proof::Preimage(preimage)], amount))
+        }
+
+        /// Returns the locker of `lock_id`, or `None` if no such lock is active.
+        #[ink(message)]
+        fn get_locker(&self, lock_id: u64) -> Option<AccountId> {
+            self.locks.get(lock_id).map(|lock| lock.locker)
+        }
+
+        /// Returns the amount held by `lock_id`, or zero if no such lock is active.
+        ///
+        /// A shielded lock reports zero until its amount is revealed at settlement.
+        #[ink(message)]
+        fn get_locked_amount(&self, lock_id: u64) -> Balance {
+            self.locks
+                .get(lock_id)
+                .map_or(0, |lock| Self::masked(lock).amount)
+        }
+    }
+
+    //----------------------------------
+    // Test Fixtures
+    //----------------------------------
+
+    /// Builder-style helpers shared by the unit tests.
+    ///
+    /// `harness()` deploys a fresh contract and each `with_*` call configures the
+    /// off-chain environment before the next action, so a scenario reads as a chain:
+    /// `harness().with_caller(alice).with_value(100).locked().build()`.
+    #[cfg(test)]
+    mod fixtures {
+        use super::*;
+        use ink::env::{event::Event, test, DefaultEnvironment};
+
+        pub type Accounts = test::DefaultAccounts<DefaultEnvironment>;
+
+        pub const CHAIN_ID: u32 = 42;
+        pub const GENESIS_MARKER: [u8; 32] = [7; 32];
+        pub const NATIVE_DECIMALS: u8 = 12;
+        pub const NATIVE_SYMBOL: &[u8] = b"UNIT";
+        pub const SECRET: &[u8] = b"correct horse battery staple";
+        /// Id assigned to the first lock of a fresh deployment.
+        pub const FIRST_LOCK_ID: u64 = 1;
+
+        /// Returns `n` days expressed in milliseconds.
+        pub const fn days(n: u64) -> Timestamp {
+            n * 24 * 60 * 60 * 1_000
+        }
+
+        /// Returns the default test accounts.
+        pub fn accounts() -> Accounts {
+            test::default_accounts::<DefaultEnvironment>()
+        }
+
+        /// Returns the Blake2x256 hash of `preimage`.
+        pub fn hash_of(preimage: &[u8]) -> [u8; 32] {
+            let mut output = [0; 32];
+            ink::env::hash_bytes::<ink::env::hash::Blake2x256>(preimage, &mut output);
+            output
+        }
+
+        /// Returns the hash of the default test secret.
+        pub fn secret_hash() -> [u8; 32] {
+            hash_of(SECRET)
+        }
+
+        /// A `lock_batch` entry for `amount` under the test secret.
+        pub fn spec(amount: Balance) -> LockSpec {
+            LockSpec {
+                amount,
+                hash: secret_hash(),
+                beneficiary: None,
+                expiry: None,
+            }
+        }
+
+        /// Sets the caller of the next message.
+        pub fn set_caller(caller: AccountId) {
+            test::set_caller::<DefaultEnvironment>(caller);
+        }
+
+        /// Sets the value transferred with the next message.
+        pub fn set_value(value: Balance) {
+            test::set_value_transferred::<DefaultEnvironment>(value);
+        }
+
+        /// Sets the current block timestamp.
+        pub fn set_time(timestamp: Timestamp) {
+            test::set_block_timestamp::<DefaultEnvironment>(timestamp);
+        }
+
+        /// Returns the current block timestamp.
+        pub fn now() -> Timestamp {
+            ink::env::block_timestamp::<DefaultEnvironment>()
+        }
+
+        /// Returns the off-chain balance of `account`.
+        pub fn balance_of(account: AccountId) -> Balance {
+            test::get_account_balance::<DefaultEnvironment>(account).expect("unknown account")
+        }
+
+        /// Returns the account of the contract under test.
+        pub fn contract_account() -> AccountId {
+            test::callee::<DefaultEnvironment>()
+        }
+
+        /// Returns the fixed ECDSA key `seed` used to sign redemptions.
+        pub fn signing_key(seed: u8) -> secp256k1::SecretKey {
+            secp256k1::SecretKey::from_slice(&[seed; 32]).expect("valid secret key")
+        }
+
+        /// Returns the compressed public key of `signing_key(seed)`.
+        pub fn redeemer_pubkey(seed: u8) -> [u8; 33] {
+            signing_key(seed)
+                .public_key(secp256k1::SECP256K1)
+                .serialize()
+        }
+
+        /// Signs `challenge` with `signing_key(seed)` as a 65-byte recoverable signature.
+        pub fn sign(seed: u8, challenge: [u8; 32]) -> [u8; 65] {
+            let message = secp256k1::Message::from_digest(challenge);
+            let (recovery_id, compact) = secp256k1::SECP256K1
+                .sign_ecdsa_recoverable(&message, &signing_key(seed))
+                .serialize_compact();
+            let mut signature = [0; 65];
+            signature[..64].copy_from_slice(&compact);
+            signature[64] = recovery_id.to_i32() as u8;
+            signature
+        }
+
+        /// Moves the contract under test to an account without a balance, so its transfers
+        /// fail. Must be called before deploying, as storage belongs to the callee.
+        pub fn use_unfunded_contract_account() {
+            test::set_callee::<DefaultEnvironment>(AccountId::from([0xAA; 32]));
+        }
+
+        /// Returns the existential deposit of the off-chain environment.
+        pub fn existential_deposit() -> Balance {
+            ink::env::minimum_balance::<DefaultEnvironment>()
+        }
+
+        /// Sets the balance of the contract under test.
+        pub fn fund_contract(balance: Balance) {
+            test::set_account_balance::<DefaultEnvironment>(contract_account(), balance);
+        }
+
+        /// Extra balance the deploy helpers give the contract on top of its existing one,
+        /// which the off-chain environment puts exactly at the existential deposit.
+        pub const CONTRACT_FLOAT: Balance = 1_000_000_000;
+
+        /// Adds `CONTRACT_FLOAT` to the contract account, unless it has no balance at all.
+        pub fn float_contract() {
+            if let Ok(balance) = test::get_account_balance::<DefaultEnvironment>(contract_account())
+            {
+                fund_contract(balance + CONTRACT_FLOAT);
+            }
+        }
+
+        /// Asserts that `terminate` removes `contract`, paying `value` to `beneficiary`.
+        pub fn assert_terminates(
+            mut contract: LockUnlockSmartContract,
+            beneficiary: AccountId,
+            value: Balance,
+        ) {
+            test::assert_contract_termination::<DefaultEnvironment, _>(
+                move || {
+                    let _ = contract.terminate();
+                },
+                beneficiary,
+                value,
+            );
+        }
+
+        /// Sets the number of the current block.
+        pub fn set_block(number: BlockNumber) {
+            test::set_block_number::<DefaultEnvironment>(number);
+        }
+
+        /// Moves the block timestamp forward by `duration`.
+        pub fn advance_time(duration: Timestamp) {
+            set_time(now() + duration);
+        }
+
+        /// A contract under test plus the accounts used to drive it.
+        pub struct ContractHarness {
+            contract: LockUnlockSmartContract,
+            accounts: Accounts,
+            expiry: Option<Timestamp>,
+        }
+
+        /// Deploys a contract with the default test identity.
+        pub fn deploy() -> LockUnlockSmartContract {
+            float_contract();
+            LockUnlockSmartContract::new(
+                CHAIN_ID,
+                GENESIS_MARKER,
+                NATIVE_DECIMALS,
+                NATIVE_SYMBOL.to_vec(),
+            )
+        }
+
+        /// Deploys a canary contract with the default test identity.
+        pub fn deploy_canary(
+            canary_max_amount: Option<Balance>,
+            sunset_at: Option<Timestamp>,
+        ) -> LockUnlockSmartContract {
+            float_contract();
+            LockUnlockSmartContract::new_canary(
+                CHAIN_ID,
+                GENESIS_MARKER,
+                NATIVE_DECIMALS,
+                NATIVE_SYMBOL.to_vec(),
+                canary_max_amount,
+                sunset_at,
+            )
+        }
+
+        /// Deploys a contract whose commitments must be `min_commit_age_blocks` old to reveal.
+        pub fn deploy_with_commit_age(min_commit_age_blocks: u32) -> LockUnlockSmartContract {
+            float_contract();
+            LockUnlockSmartContract::new_with_commit_age(
+                CHAIN_ID,
+                GENESIS_MARKER,
+                NATIVE_DECIMALS,
+                NATIVE_SYMBOL.to_vec(),
+                min_commit_age_blocks,
+            )
+        }
+
+        /// Deploys a contract in which anyone may finalize a lock `finalize_grace` after its
+        /// expiry, earning `finalize_bounty`.
+        pub fn deploy_with_finalize_bounty(
+            finalize_grace: Timestamp,
+            finalize_bounty: FinalizeBounty,
+        ) -> LockUnlockSmartContract {
+            float_contract();
+            LockUnlockSmartContract::new_with_finalize_bounty(
+                CHAIN_ID,
+                GENESIS_MARKER,
+                NATIVE_DECIMALS,
+                NATIVE_SYMBOL.to_vec(),
+                finalize_grace,
+                finalize_bounty,
+            )
+        }
+
+        /// Deploys a contract handling redemptions that would reap it with `reap_policy`.
+        pub fn deploy_with_reap_policy(reap_policy: ReapPolicy) -> LockUnlockSmartContract {
+            float_contract();
+            LockUnlockSmartContract::new_with_reap_policy(
+                CHAIN_ID,
+                GENESIS_MARKER,
+                NATIVE_DECIMALS,
+                NATIVE_SYMBOL.to_vec(),
+                reap_policy,
+            )
+        }
+
+        /// Deploys a contract limiting native locks to `min_amount..=max_amount`.
+        pub fn deploy_with_limits(
+            min_amount: Balance,
+            max_amount: Option<Balance>,
+        ) -> LockUnlockSmartContract {
+            float_contract();
+            LockUnlockSmartContract::new_with_limits(
+                CHAIN_ID,
+                GENESIS_MARKER,
+                NATIVE_DECIMALS,
+                NATIVE_SYMBOL.to_vec(),
+                min_amount,
+                max_amount,
+            )
+        }
+
+        /// Starts a harness around a freshly deployed contract.
+        pub fn harness() -> ContractHarness {
+            ContractHarness {
+                contract: deploy(),
+                accounts: accounts(),
+                expiry: None,
+            }
+        }
+
+        impl ContractHarness {
+            /// Replaces the deployed contract, e.g. with one from another constructor.
+            pub fn with_contract(mut self, contract: LockUnlockSmartContract) -> Self {
+                self.contract = contract;
+                self
+            }
+
+            /// Sets the caller for subsequent actions.
+            pub fn with_caller(self, caller: AccountId) -> Self {
+                set_caller(caller);
+                self
+            }
+
+            /// Sets the transferred value for subsequent actions.
+            pub fn with_value(self, value: Balance) -> Self {
+                set_value(value);
+                self
+            }
+
+            /// Sets the block timestamp for subsequent actions.
+            pub fn at_time(self, timestamp: Timestamp) -> Self {
+                set_time(timestamp);
+                self
+            }
+
+            /// Gives locks created by `locked` an expiry `duration` from now.
+            pub fn with_expiry_in(mut self, duration: Timestamp) -> Self {
+                self.expiry = Some(now() + duration);
+                self
+            }
+
+            /// Locks the configured value as the configured caller behind `SECRET`.
+            ///
+            /// On a fresh harness the resulting lock has id `FIRST_LOCK_ID`.
+            pub fn locked(mut self) -> Self {
+                self.contract
+                    .lock(secret_hash(), None, self.expiry, None, None)
+                    .expect("lock failed");
+                self
+            }
+
+            /// Finishes the setup, handing back the contract and the accounts.
+            pub fn build(self) -> (LockUnlockSmartContract, Accounts) {
+                (self.contract, self.accounts)
+            }
+        }
+
+        /// Asset balances seen by `MockAssets`, shared with the test that registered it.
+        #[cfg(feature = "assets")]
+        #[derive(Default)]
+        pub struct AssetLedger {
+            /// Amount of the asset held by the contract.
+            pub held: Balance,
+            /// Transfers made by the contract, as `(to, amount)`.
+            pub paid: Vec<(AccountId, Balance)>,
+            /// Whether the next pull from a caller is rejected.
+            pub reject_pull: bool,
+            /// Account whose payouts are rejected, if any.
+            pub reject_payout_to: Option<AccountId>,
+        }
+
+        /// Off-chain stand-in for the assets chain extension of the runtime.
+        #[cfg(feature = "assets")]
+        pub struct MockAssets(pub std::rc::Rc<std::cell::RefCell<AssetLedger>>);
+
+        #[cfg(feature = "assets")]
+        impl test::ChainExtension for MockAssets {
+            fn ext_id(&self) -> u16 {
+                crate::assets::ASSETS_EXTENSION_ID
+            }
+
+            fn call(&mut self, func_id: u16, input: &[u8], output: &mut Vec<u8>) -> u32 {
+                use scale::{Decode, Encode};
+                let args = Vec::<u8>::decode(&mut &input[..]).expect("undecodable input");
+                let mut ledger = self.0.borrow_mut();
+                match func_id {
+                    1 => {
+                        let (_, to, amount) =
+                            <(u32, AccountId, Balance)>::decode(&mut &args[..]).unwrap();
+                        if ledger.reject_payout_to == Some(to) {
+                            return 1;
+                        }
+                        ledger.held -= amount;
+                        ledger.paid.push((to, amount));
+                    }
+                    2 => {
+                        if ledger.reject_pull {
+                            return 1;
+                        }
+                        let (_, _, _, amount) =
+                            <(u32, AccountId, AccountId, Balance)>::decode(&mut &args[..]).unwrap();
+                        ledger.held += amount;
+                    }
+                    _ => {
+                        ledger.held.encode_to(output);
+                        return 0;
+                    }
+                }
+                Ok::<(), crate::assets::AssetsError>(()).encode_to(output);
+                0
+            }
+        }
+
+        /// Registers a `MockAssets` extension and returns its ledger.
+        #[cfg(feature = "assets")]
+        pub fn mock_assets() -> std::rc::Rc<std::cell::RefCell<AssetLedger>> {
+            let ledger = std::rc::Rc::default();
+            test::register_chain_extension(MockAssets(std::rc::Rc::clone(&ledger)));
+            ledger
+        }
+
+        /// Decodes all recorded events of type `E`, in emission order.
+        pub fn events<E: Event + scale::Decode>() -> Vec<E> {
+            let signature = E::SIGNATURE_TOPIC.map(|topic| topic.to_vec());
+            test::recorded_events()
+                .filter(|event| event.topics.first() == signature.as_ref())
+                .map(|event| E::decode(&mut &event.data[..]).expect("undecodable event"))
+                .collect()
+        }
+
+        /// Asserts that some recorded event of type `E` satisfies `check`.
+        pub fn assert_event<E: Event + scale::Decode>(check: impl Fn(&E) -> bool) {
+            assert!(
+                events::<E>().iter().any(check),
+                "no matching {} event",
+                core::any::type_name::<E>()
+            );
+        }
+
+        /// Asserts that no event of type `E` was recorded.
+        pub fn assert_no_event<E: Event + scale::Decode>() {
+            assert!(
+                events::<E>().is_empty(),
+                "unexpected {} event",
+                core::any::type_name::<E>()
+            );
+        }
+    }
+
+    //----------------------------------
+    // Tests
+    //----------------------------------
+
+    #[cfg(test)]
+    mod tests {
+        use super::fixtures::*;
+        use super::*;
+
+        #[ink::test]
+        fn test_default_works() {
+            let contract = LockUnlockSmartContract::default();
+            assert_eq!(contract.locks.get(FIRST_LOCK_ID), None);
+            assert_eq!(contract.next_lock_id, FIRST_LOCK_ID);
+        }
+
+        #[ink::test]
+        fn test_new_works() {
+            let (contract, _) = harness().build();
+            assert_eq!(contract.locks.get(FIRST_LOCK_ID), None);
+            assert_eq!(contract.next_lock_id, FIRST_LOCK_ID);
+        }
+
+        #[ink::test]
+        fn test_lock_works() {
+            let (contract, accounts) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .locked()
+                .build();
+
+            let lock = contract.locks.get(FIRST_LOCK_ID).unwrap();
+            assert_eq!(lock.locker, accounts.alice);
+            assert_eq!(lock.amount, 100);
+            assert_eq!(contract.next_lock_id, FIRST_LOCK_ID + 1);
+            assert_event::<Locked>(|e| {
+                e.lock_id == FIRST_LOCK_ID && e.locker == accounts.alice && e.amount == Some(100)
+            });
+        }
+
+        #[ink::test]
+        fn test_second_lock_gets_new_id() {
+            let (mut contract, _) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .locked()
+                .build();
+
+            // Locking again creates an independent lock
+            assert_eq!(
+                contract
+                    .lock(secret_hash(), None, None, None, None)
+                    .map(|receipt| receipt.lock_id),
+                Ok(FIRST_LOCK_ID + 1)
+            );
+            assert!(contract.locks.get(FIRST_LOCK_ID).is_some());
+            assert!(contract.locks.get(FIRST_LOCK_ID + 1).is_some());
+        }
+
+        #[ink::test]
+        fn test_lock_fails_when_no_assets_sent() {
+            let (mut contract, _) = harness()
+                .with_caller(accounts().alice)
+                .with_value(0)
+                .build();
+
+            assert_eq!(
+                contract.lock(secret_hash(), None, None, None, None),
+                Err(Error::NoAssetsSent)
+            );
+            assert_eq!(contract.next_lock_id, FIRST_LOCK_ID);
+            assert_no_event::<Locked>();
+        }
+
+        #[ink::test]
+        fn test_redeem_works() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .locked()
+                .build();
+
+            assert!(contract
+                .redeem(FIRST_LOCK_ID, SECRET.to_vec(), None)
+                .is_ok());
+
+            assert_eq!(contract.locks.get(FIRST_LOCK_ID), None);
+            assert_event::<Redeemed>(|e| {
+                e.lock_id == FIRST_LOCK_ID && e.locker == accounts.alice && e.amount == 100
+            });
+        }
+
+        #[ink::test]
+        fn test_redeem_fails_when_not_locker() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .locked()
+                .build();
+
+            set_caller(accounts.bob);
+            let result = contract.redeem(FIRST_LOCK_ID, SECRET.to_vec(), None);
+            assert_eq!(result, Err(Error::NotLocker));
+        }
+
+        #[ink::test]
+        fn test_redeem_fails_with_wrong_preimage() {
+            let (mut contract, _) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .locked()
+                .build();
+
+            let result = contract.redeem(FIRST_LOCK_ID, b"wrong secret".to_vec(), None);
+            assert_eq!(result, Err(Error::InvalidPreimage));
+        }
+
+        #[ink::test]
+        fn test_redeem_fails_for_unknown_lock() {
+            let (mut contract, _) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .locked()
+                .build();
+
+            let result = contract.redeem(FIRST_LOCK_ID + 1, SECRET.to_vec(), None);
+            assert_eq!(result, Err(Error::LockNotFound));
+
+            // A redeemed lock cannot be redeemed again
+            assert!(contract
+                .redeem(FIRST_LOCK_ID, SECRET.to_vec(), None)
+                .is_ok());
+            let result = contract.redeem(FIRST_LOCK_ID, SECRET.to_vec(), None);
+            assert_eq!(result, Err(Error::LockNotFound));
+        }
+
+        #[ink::test]
+        fn test_independent_locks_redeem_in_any_order() {
+            let accounts = accounts();
+            let mut contract = deploy();
+
+            set_caller(accounts.alice);
+            set_value(100);
+            let alice_lock = contract
+                .lock(hash_of(b"alice secret"), None, None, None, None)
+                .unwrap()
+                .lock_id;
+
+            set_caller(accounts.bob);
+            set_value(200);
+            let bob_lock = contract
+                .lock(hash_of(b"bob secret"), None, None, None, None)
+                .unwrap()
+                .lock_id;
+            assert_ne!(alice_lock, bob_lock);
+
+            // Bob redeems first, Alice's lock is untouched
+            assert!(contract
+                .redeem(bob_lock, b"bob secret".to_vec(), None)
+                .is_ok());
+            assert_eq!(contract.locks.get(alice_lock).unwrap().amount, 100);
+
+            // Alice cannot redeem Bob's id, but her own works
+            set_caller(accounts.alice);
+            assert_eq!(
+                contract.redeem(bob_lock, b"bob secret".to_vec(), None),
+                Err(Error::LockNotFound)
+            );
+            assert!(contract
+                .redeem(alice_lock, b"alice secret".to_vec(), None)
+                .is_ok());
+
+            assert_event::<Redeemed>(|e| e.lock_id == bob_lock && e.amount == 200);
+            assert_event::<Redeemed>(|e| e.lock_id == alice_lock && e.amount == 100);
+        }
+
+        #[ink::test]
+        fn test_redeem_fails_when_reentered() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .locked()
+                .build();
+
+            // A call back into the contract while a settlement is in flight decodes its own
+            // copy of the storage root, and still sees the flag in its own cell
+            let mut nested = deploy();
+            let result =
+                contract.guarded(|_| Ok(nested.redeem(FIRST_LOCK_ID, SECRET.to_vec(), None)));
+            assert_eq!(result, Ok(Err(Error::ReentrantCall)));
+            let lock = contract.locks.get(FIRST_LOCK_ID).unwrap();
+            assert_eq!(lock.locker, accounts.alice);
+            assert_eq!(lock.amount, 100);
+        }
+
+        #[ink::test]
+        fn test_guard_released_after_redeem() {
+            let (mut contract, _) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .locked()
+                .build();
+
+            assert_eq!(
+                contract.redeem(FIRST_LOCK_ID, b"wrong secret".to_vec(), None),
+                Err(Error::InvalidPreimage)
+            );
+            assert_eq!(contract.entered.get(), Some(false));
+
+            assert!(contract
+                .redeem(FIRST_LOCK_ID, SECRET.to_vec(), None)
+                .is_ok());
+            assert_eq!(contract.entered.get(), Some(false));
+        }
+
+        #[ink::test]
+        fn test_canary_limit() {
+            let (mut contract, _) = harness()
+                .with_contract(deploy_canary(Some(100), None))
+                .with_caller(accounts().alice)
+                .with_value(101)
+                .build();
+            assert_eq!(
+                contract.lock(secret_hash(), None, None, None, None),
+                Err(Error::CanaryLimit)
+            );
+
+            // Exactly at the ceiling is accepted
+            set_value(100);
+            let lock_id = contract
+                .lock(secret_hash(), None, None, None, None)
+                .unwrap()
+                .lock_id;
+            assert_eq!(contract.locks.get(lock_id).unwrap().amount, 100);
+        }
+
+        #[ink::test]
+        fn test_canary_sunset() {
+            // Before the sunset new locks are accepted
+            let (mut contract, _) = harness()
+                .with_contract(deploy_canary(None, Some(days(1))))
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .at_time(days(1) - 1)
+                .locked()
+                .build();
+            assert!(!contract.contract_info().sunset);
+
+            // After the sunset the existing lock can still be redeemed
+            set_time(days(1));
+            assert!(contract.contract_info().sunset);
+            assert!(contract
+                .redeem(FIRST_LOCK_ID, SECRET.to_vec(), None)
+                .is_ok());
+
+            // But no new lock is accepted
+            assert_eq!(
+                contract.lock(secret_hash(), None, None, None, None),
+                Err(Error::Sunset)
+            );
+        }
+
+        #[ink::test]
+        fn test_contract_info() {
+            let (contract, _) = harness().build();
+            let info = contract.contract_info();
+            assert!(!info.canary);
+            assert_eq!(info.canary_max_amount, None);
+            assert_eq!(info.sunset_at, None);
+            assert!(!info.sunset);
+
+            let contract = deploy_canary(Some(50), Some(10));
+            let info = contract.contract_info();
+            assert!(info.canary);
+            assert_eq!(info.canary_max_amount, Some(50));
+            assert_eq!(info.sunset_at, Some(10));
+        }
+
+        #[ink::test]
+        fn test_chain_identity() {
+            let (contract, _) = harness().build();
+            assert_eq!(contract.genesis_marker(), GENESIS_MARKER);
+            assert_eq!(contract.contract_info().chain_id, CHAIN_ID);
+        }
+
+        #[ink::test]
+        fn test_events_carry_chain_id() {
+            let (mut contract, _) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .locked()
+                .build();
+            assert!(contract
+                .redeem(FIRST_LOCK_ID, SECRET.to_vec(), None)
+                .is_ok());
+
+            assert_eq!(events::<Locked>().len(), 1);
+            assert_eq!(events::<Redeemed>().len(), 1);
+            assert_event::<Locked>(|e| e.chain_id == CHAIN_ID);
+            assert_event::<Redeemed>(|e| e.chain_id == CHAIN_ID);
+        }
+
+        #[ink::test]
+        fn test_admin_events_carry_chain_id() {
+            let (mut contract, accounts) = harness().build();
+            contract.pause().unwrap();
+            contract.unpause().unwrap();
+            contract.add_to_allowlist(accounts.bob).unwrap();
+            contract.storage_version = STORAGE_VERSION - 1;
+            contract.migrate().unwrap();
+            contract.transfer_ownership(accounts.bob).unwrap();
+
+            assert_event::<Paused>(|e| e.chain_id == CHAIN_ID);
+            assert_event::<Unpaused>(|e| e.chain_id == CHAIN_ID);
+            assert_event::<AllowlistUpdated>(|e| e.chain_id == CHAIN_ID);
+            assert_event::<Migrated>(|e| e.chain_id == CHAIN_ID);
+            assert_event::<OwnershipTransferred>(|e| e.chain_id == CHAIN_ID);
+        }
+
+        #[ink::test]
+        fn test_token_metadata() {
+            // Checked first, as off-chain instances share storage cells
+            let contract = LockUnlockSmartContract::default();
+            assert_eq!(contract.token_metadata().decimals, 0);
+            assert!(contract.token_metadata().symbol.is_empty());
+
+            let (contract, _) = harness().build();
+            let metadata = contract.token_metadata();
+            assert_eq!(metadata.decimals, NATIVE_DECIMALS);
+            assert_eq!(metadata.symbol, NATIVE_SYMBOL.to_vec());
+        }
+
+        #[ink::test]
+        fn test_lock_shielded_hides_amount() {
+            let salt = [9; 32];
+            let (mut contract, _) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .build();
+            let lock_id = contract
+                .lock_shielded(secret_hash(), None, None, None, salt)
+                .unwrap();
+
+            // The contract still accounts for the real amount, but queries do not show it
+            assert_eq!(contract.locks.get(lock_id).unwrap().amount, 100);
+            let info = contract.get_lock_info(lock_id).unwrap();
+            assert_eq!(info.amount, 0);
+            assert_eq!(info.shield_salt, Some([0; 32]));
+            assert_eq!(info.locker, accounts().alice);
+            assert_eq!(contract.get_locked_amount(lock_id), 0);
+
+            let commitment = contract.amount_commitment(100, salt);
+            assert_event::<Locked>(|e| {
+                e.amount.is_none() && e.amount_commitment == Some(commitment)
+            });
+        }
+
+        #[ink::test]
+        fn test_shielded_amount_revealed_on_redeem() {
+            let salt = [9; 32];
+            let (mut contract, _) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .build();
+            let lock_id = contract
+                .lock_shielded(secret_hash(), None, None, None, salt)
+                .unwrap();
+            assert!(contract.redeem(lock_id, SECRET.to_vec(), None).is_ok());
+
+            // The revealed amount and salt open the published commitment
+            let locked = events::<Locked>().pop().unwrap();
+            let redeemed = events::<Redeemed>().pop().unwrap();
+            assert_eq!(redeemed.amount, 100);
+            assert_eq!(redeemed.salt, Some(salt));
+            assert_eq!(
+                locked.amount_commitment,
+                Some(contract.amount_commitment(redeemed.amount, salt))
+            );
+            assert_ne!(
+                locked.amount_commitment,
+                Some(contract.amount_commitment(99, salt))
+            );
+        }
+
+        #[ink::test]
+        fn test_unshielded_events() {
+            let (mut contract, _) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .locked()
+                .build();
+            assert!(contract
+                .redeem(FIRST_LOCK_ID, SECRET.to_vec(), None)
+                .is_ok());
+
+            assert_event::<Locked>(|e| e.amount == Some(100) && e.amount_commitment.is_none());
+            assert_event::<Redeemed>(|e| e.amount == 100 && e.salt.is_none());
+        }
+
+        #[ink::test]
+        fn test_redeem_fails_with_empty_preimage() {
+            let (mut contract, _) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .locked()
+                .build();
+
+            assert_eq!(
+                contract.redeem(FIRST_LOCK_ID, Vec::new(), None),
+                Err(Error::InvalidPreimage)
+            );
+            assert!(contract.locks.get(FIRST_LOCK_ID).is_some());
+        }
+
+        #[ink::test]
+        fn test_redeem_rejects_former_hard_coded_message() {
+            let (mut contract, _) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .locked()
+                .build();
+
+            // The message the contract once compiled in unlocks nothing
+            assert_eq!(
+                contract.redeem(FIRST_LOCK_ID, b"Hello, World!".to_vec(), None),
+                Err(Error::InvalidPreimage)
+            );
+            assert!(contract.locks.get(FIRST_LOCK_ID).is_some());
+        }
+
+        #[ink::test]
+        fn test_lock_stores_secret_hash() {
+            let (contract, _) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .locked()
+                .build();
+
+            let lock = contract.locks.get(FIRST_LOCK_ID).unwrap();
+            assert_eq!(lock.secret_hash, secret_hash());
+            assert_event::<Locked>(|e| e.hash == secret_hash());
+        }
+
+        #[ink::test]
+        fn test_each_lock_has_its_own_secret() {
+            let (mut contract, _) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .locked()
+                .build();
+            let other = contract
+                .lock(hash_of(b"another secret"), None, None, None, None)
+                .unwrap()
+                .lock_id;
+
+            // Each lock only opens with its own secret
+            assert_eq!(
+                contract.redeem(other, SECRET.to_vec(), None),
+                Err(Error::InvalidPreimage)
+            );
+            assert!(contract
+                .redeem(other, b"another secret".to_vec(), None)
+                .is_ok());
+            assert!(contract
+                .redeem(FIRST_LOCK_ID, SECRET.to_vec(), None)
+                .is_ok());
+        }
+
+        #[ink::test]
+        fn test_beneficiary_redeems() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .build();
+            let lock_id = contract
+                .lock(secret_hash(), Some(accounts.bob), None, None, None)
+                .unwrap()
+                .lock_id;
+            assert_event::<Locked>(|e| e.beneficiary == Some(accounts.bob));
+
+            set_caller(accounts.bob);
+            assert!(contract.redeem(lock_id, SECRET.to_vec(), None).is_ok());
+            assert_eq!(contract.locks.get(lock_id), None);
+            assert_event::<Redeemed>(|e| {
+                e.locker == accounts.alice && e.beneficiary == Some(accounts.bob) && e.amount == 100
+            });
+        }
+
+        #[ink::test]
+        fn test_locker_cannot_redeem_for_beneficiary() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .build();
+            let lock_id = contract
+                .lock(secret_hash(), Some(accounts.bob), None, None, None)
+                .unwrap()
+                .lock_id;
+
+            // The locker is rejected even with the right secret
+            assert_eq!(
+                contract.redeem(lock_id, SECRET.to_vec(), None),
+                Err(Error::NotBeneficiary)
+            );
+
+            // And so is any third party
+            set_caller(accounts.charlie);
+            assert_eq!(
+                contract.redeem(lock_id, SECRET.to_vec(), None),
+                Err(Error::NotBeneficiary)
+            );
+            assert!(contract.locks.get(lock_id).is_some());
+        }
+
+        #[ink::test]
+        fn test_refund_after_expiry() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .with_expiry_in(days(3))
+                .locked()
+                .build();
+            assert_event::<Locked>(|e| e.expiry == Some(now() + days(3)));
+
+            advance_time(days(3));
+            assert!(contract.refund(FIRST_LOCK_ID).is_ok());
+            assert_eq!(contract.locks.get(FIRST_LOCK_ID), None);
+            assert_event::<Refunded>(|e| {
+                e.lock_id == FIRST_LOCK_ID && e.locker == accounts.alice && e.amount == 100
+            });
+        }
+
+        #[ink::test]
+        fn test_refund_fails_before_expiry() {
+            let (mut contract, _) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .with_expiry_in(days(3))
+                .locked()
+                .build();
+
+            advance_time(days(3) - 1);
+            assert_eq!(contract.refund(FIRST_LOCK_ID), Err(Error::NotExpired));
+            assert!(contract.locks.get(FIRST_LOCK_ID).is_some());
+            assert_no_event::<Refunded>();
+        }
+
+        #[ink::test]
+        fn test_refund_fails_without_expiry() {
+            let (mut contract, _) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .locked()
+                .build();
+
+            advance_time(days(365));
+            assert_eq!(contract.refund(FIRST_LOCK_ID), Err(Error::NotExpired));
+        }
+
+        #[ink::test]
+        fn test_refund_fails_when_not_locker() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .with_expiry_in(days(3))
+                .locked()
+                .build();
+
+            advance_time(days(3));
+            set_caller(accounts.bob);
+            assert_eq!(contract.refund(FIRST_LOCK_ID), Err(Error::NotLocker));
+            assert_eq!(contract.refund(FIRST_LOCK_ID + 1), Err(Error::LockNotFound));
+        }
+
+        #[ink::test]
+        fn test_redeem_fails_after_expiry() {
+            let (mut contract, _) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .with_expiry_in(days(3))
+                .locked()
+                .build();
+
+            advance_time(days(3));
+            assert_eq!(
+                contract.redeem(FIRST_LOCK_ID, SECRET.to_vec(), None),
+                Err(Error::Expired)
+            );
+            assert!(contract.refund(FIRST_LOCK_ID).is_ok());
+        }
+
+        #[ink::test]
+        fn test_redeem_works_before_expiry() {
+            let (mut contract, _) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .with_expiry_in(days(3))
+                .locked()
+                .build();
+
+            advance_time(days(3) - 1);
+            assert!(contract
+                .redeem(FIRST_LOCK_ID, SECRET.to_vec(), None)
+                .is_ok());
+            assert_eq!(contract.refund(FIRST_LOCK_ID), Err(Error::LockNotFound));
+        }
+
+        #[ink::test]
+        fn test_getters_before_lock() {
+            let (contract, _) = harness().build();
+            assert_eq!(contract.get_locker(FIRST_LOCK_ID), None);
+            assert_eq!(contract.get_locked_amount(FIRST_LOCK_ID), 0);
+            assert_eq!(contract.get_lock_info(FIRST_LOCK_ID), None);
+        }
+
+        #[ink::test]
+        fn test_getters_after_lock() {
+            set_time(days(2));
+            let (contract, accounts) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .locked()
+                .build();
+
+            // Readable by anyone
+            set_caller(accounts.charlie);
+            assert_eq!(contract.get_locker(FIRST_LOCK_ID), Some(accounts.alice));
+            assert_eq!(contract.get_locked_amount(FIRST_LOCK_ID), 100);
+            assert_eq!(
+                contract.get_lock_info(FIRST_LOCK_ID),
+                Some(LockInfo {
+                    locker: accounts.alice,
+                    amount: 100,
+                    secret_hash: secret_hash(),
+                    beneficiary: None,
+                    shield_salt: None,
+                    expiry: None,
+                    locked_at: days(2),
+                    cancel_grace: None,
+                    token: None,
+                    token_metadata: None,
+                    asset_id: None,
+                    redeemer_pubkey: None,
+                    nonce: 0,
+                    vesting: None,
+                    escrow: None,
+                    arbitration: None,
+                    extension: None,
+                    approvals: None,
+                    redeemers: Vec::new(),
+                    memo: None,
+                    split: Vec::new(),
+                    recovery: None,
+                })
+            );
+        }
+
+        #[ink::test]
+        fn test_getters_after_redeem() {
+            let (mut contract, _) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .locked()
+                .build();
+
+            contract
+                .redeem(FIRST_LOCK_ID, SECRET.to_vec(), None)
+                .unwrap();
+            assert_eq!(contract.get_locker(FIRST_LOCK_ID), None);
+            assert_eq!(contract.get_locked_amount(FIRST_LOCK_ID), 0);
+            assert_eq!(contract.get_lock_info(FIRST_LOCK_ID), None);
+        }
+
+        #[ink::test]
+        fn test_cancel_works() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .locked()
+                .build();
+
+            assert!(contract.cancel(FIRST_LOCK_ID).is_ok());
+            assert_eq!(contract.get_lock_info(FIRST_LOCK_ID), None);
+            assert_event::<Cancelled>(|e| {
+                e.lock_id == FIRST_LOCK_ID && e.locker == accounts.alice && e.amount == 100
+            });
+        }
+
+        #[ink::test]
+        fn test_cancel_fails_when_not_locker() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .locked()
+                .build();
+
+            set_caller(accounts.bob);
+            assert_eq!(contract.cancel(FIRST_LOCK_ID), Err(Error::NotLocker));
+            assert!(contract.get_lock_info(FIRST_LOCK_ID).is_some());
+            assert_no_event::<Cancelled>();
+        }
+
+        #[ink::test]
+        fn test_cancel_fails_after_redeem() {
+            let (mut contract, _) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .locked()
+                .build();
+
+            contract
+                .redeem(FIRST_LOCK_ID, SECRET.to_vec(), None)
+                .unwrap();
+            assert_eq!(contract.cancel(FIRST_LOCK_ID), Err(Error::LockNotFound));
+        }
+
+        #[ink::test]
+        fn test_cancel_with_beneficiary_within_grace() {
+            let (mut contract, accounts) = harness().with_caller(accounts().alice).build();
+            set_value(100);
+            let lock_id = contract
+                .lock(secret_hash(), Some(accounts.bob), None, Some(days(1)), None)
+                .unwrap()
+                .lock_id;
+
+            advance_time(days(1) - 1);
+            assert!(contract.cancel(lock_id).is_ok());
+        }
+
+        #[ink::test]
+        fn test_cancel_with_beneficiary_fails_after_grace() {
+            let (mut contract, accounts) = harness().with_caller(accounts().alice).build();
+            set_value(100);
+            let lock_id = contract
+                .lock(secret_hash(), Some(accounts.bob), None, Some(days(1)), None)
+                .unwrap()
+                .lock_id;
+            let no_grace = contract
+                .lock(secret_hash(), Some(accounts.bob), None, None, None)
+                .unwrap()
+                .lock_id;
+
+            assert_eq!(contract.cancel(no_grace), Err(Error::CancelWindowClosed));
+            advance_time(days(1));
+            assert_eq!(contract.cancel(lock_id), Err(Error::CancelWindowClosed));
+
+            // The beneficiary can still redeem
+            set_caller(accounts.bob);
+            assert!(contract.redeem(lock_id, SECRET.to_vec(), None).is_ok());
+        }
+
+        #[ink::test]
+        fn test_top_up_works() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .locked()
+                .build();
+
+            set_value(50);
+            assert!(contract.top_up(FIRST_LOCK_ID).is_ok());
+            assert_eq!(contract.get_locked_amount(FIRST_LOCK_ID), 150);
+            assert_event::<ToppedUp>(|e| {
+                e.lock_id == FIRST_LOCK_ID
+                    && e.locker == accounts.alice
+                    && e.added == 50
+                    && e.new_total == 150
+            });
+
+            // The combined total is redeemed
+            set_value(0);
+            assert!(contract
+                .redeem(FIRST_LOCK_ID, SECRET.to_vec(), None)
+                .is_ok());
+            assert_event::<Redeemed>(|e| e.amount == 150);
+        }
+
+        #[ink::test]
+        fn test_top_up_fails_without_assets() {
+            let (mut contract, _) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .locked()
+                .build();
+
+            set_value(0);
+            assert_eq!(contract.top_up(FIRST_LOCK_ID), Err(Error::NoAssetsSent));
+        }
+
+        #[ink::test]
+        fn test_top_up_fails_when_not_locker() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .locked()
+                .build();
+
+            set_caller(accounts.bob);
+            assert_eq!(contract.top_up(FIRST_LOCK_ID), Err(Error::NotLocker));
+            assert_eq!(contract.get_locked_amount(FIRST_LOCK_ID), 100);
+            assert_no_event::<ToppedUp>();
+        }
+
+        #[ink::test]
+        fn test_top_up_fails_on_overflow() {
+            let (mut contract, _) = harness()
+                .with_caller(accounts().alice)
+                .with_value(Balance::MAX)
+                .locked()
+                .build();
+
+            set_value(1);
+            assert_eq!(contract.top_up(FIRST_LOCK_ID), Err(Error::Overflow));
+            assert_eq!(contract.get_locked_amount(FIRST_LOCK_ID), Balance::MAX);
+        }
+
+        #[ink::test]
+        fn test_top_up_fails_on_shielded_lock() {
+            let (mut contract, _) = harness().with_caller(accounts().alice).build();
+            set_value(100);
+            let lock_id = contract
+                .lock_shielded(secret_hash(), None, None, None, [1; 32])
+                .unwrap();
+
+            assert_eq!(contract.top_up(lock_id), Err(Error::Shielded));
+        }
+
+        #[ink::test]
+        fn test_partial_redemptions_drain_the_lock() {
+            let (mut contract, _) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .locked()
+                .build();
+
+            assert!(contract
+                .redeem(FIRST_LOCK_ID, SECRET.to_vec(), Some(30))
+                .is_ok());
+            assert_event::<Redeemed>(|e| e.amount == 30 && e.remaining == 70);
+            assert_eq!(contract.get_locked_amount(FIRST_LOCK_ID), 70);
+
+            assert!(contract
+                .redeem(FIRST_LOCK_ID, SECRET.to_vec(), Some(70))
+                .is_ok());
+            assert_event::<Redeemed>(|e| e.amount == 70 && e.remaining == 0);
+            assert_eq!(contract.get_lock_info(FIRST_LOCK_ID), None);
+
+            assert_eq!(
+                contract.redeem(FIRST_LOCK_ID, SECRET.to_vec(), Some(1)),
+                Err(Error::LockNotFound)
+            );
+        }
+
+        #[ink::test]
+        fn test_partial_redeem_rejects_invalid_amounts() {
+            let (mut contract, _) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .locked()
+                .build();
+
+            assert_eq!(
+                contract.redeem(FIRST_LOCK_ID, SECRET.to_vec(), Some(101)),
+                Err(Error::InsufficientLockedBalance)
+            );
+            assert_eq!(
+                contract.redeem(FIRST_LOCK_ID, SECRET.to_vec(), Some(0)),
+                Err(Error::ZeroAmount)
+            );
+            assert_eq!(contract.get_locked_amount(FIRST_LOCK_ID), 100);
+            assert_no_event::<Redeemed>();
+        }
+
+        #[ink::test]
+        fn test_lock_token_rejected_before_token_call() {
+            let (mut contract, accounts) = harness()
+                .with_contract(deploy_canary(None, Some(days(1))))
+                .with_caller(accounts().alice)
+                .build();
+
+            // Neither case reaches the token contract
+            assert_eq!(
+                contract.lock_token(accounts.django, 0, secret_hash(), None, None, None),
+                Err(Error::ZeroAmount)
+            );
+            set_time(days(1));
+            assert_eq!(
+                contract.lock_token(accounts.django, 100, secret_hash(), None, None, None),
+                Err(Error::Sunset)
+            );
+            assert_no_event::<Locked>();
+        }
+
+        #[ink::test]
+        fn test_top_up_fails_on_token_lock() {
+            let (mut contract, accounts) = harness().with_caller(accounts().alice).build();
+            contract.locks.insert(
+                FIRST_LOCK_ID,
+                &LockInfo {
+                    locker: accounts.alice,
+                    amount: 100,
+                    secret_hash: secret_hash(),
+                    beneficiary: None,
+                    shield_salt: None,
+                    expiry: None,
+                    locked_at: 0,
+                    cancel_grace: None,
+                    token: Some(accounts.django),
+                    token_metadata: None,
+                    asset_id: None,
+                    redeemer_pubkey: None,
+                    nonce: 0,
+                    vesting: None,
+                    escrow: None,
+                    arbitration: None,
+                    extension: None,
+                    approvals: None,
+                    redeemers: Vec::new(),
+                    memo: None,
+                    split: Vec::new(),
+                    recovery: None,
+                },
+            );
+
+            set_value(50);
+            assert_eq!(contract.top_up(FIRST_LOCK_ID), Err(Error::WrongAsset));
+            assert_eq!(contract.get_locked_amount(FIRST_LOCK_ID), 100);
+        }
+
+        #[ink::test]
+        fn test_lock_or_refund_works() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .build();
+
+            assert_eq!(
+                contract.lock_or_refund(secret_hash(), None, None, None, None),
+                Ok(LockOutcome::Locked {
+                    lock_id: FIRST_LOCK_ID,
+                    amount: 100
+                })
+            );
+            assert_eq!(contract.get_locker(FIRST_LOCK_ID), Some(accounts.alice));
+        }
+
+        #[ink::test]
+        fn test_lock_or_refund_nothing_sent() {
+            let (mut contract, _) = harness().with_caller(accounts().alice).build();
+
+            assert_eq!(
+                contract.lock_or_refund(secret_hash(), None, None, None, None),
+                Ok(LockOutcome::NothingSent)
+            );
+            assert_eq!(contract.next_lock_id, FIRST_LOCK_ID);
+            assert_no_event::<LockRejected>();
+        }
+
+        #[ink::test]
+        fn test_lock_or_refund_rejection_refunds_caller() {
+            let (mut contract, accounts) = harness()
+                .with_contract(deploy_canary(Some(100), None))
+                .with_caller(accounts().bob)
+                .with_value(101)
+                .build();
+            let bob_before = balance_of(accounts.bob);
+            let contract_before = balance_of(contract_account());
+
+            assert_eq!(
+                contract.lock_or_refund(secret_hash(), None, None, None, None),
+                Ok(LockOutcome::Rejected {
+                    reason: Error::CanaryLimit,
+                    refunded: 101
+                })
+            );
+            assert_eq!(balance_of(accounts.bob), bob_before + 101);
+            assert_eq!(balance_of(contract_account()), contract_before - 101);
+            assert_event::<LockRejected>(|e| {
+                e.caller == accounts.bob && e.reason == Error::CanaryLimit && e.refunded == 101
+            });
+
+            // State is untouched
+            assert_eq!(contract.next_lock_id, FIRST_LOCK_ID);
+            assert_eq!(contract.get_lock_info(FIRST_LOCK_ID), None);
+            assert_no_event::<Locked>();
+        }
+
+        #[ink::test]
+        fn test_lock_or_refund_fails_when_refund_fails() {
+            use_unfunded_contract_account();
+            let (mut contract, _) = harness()
+                .with_contract(deploy_canary(Some(100), None))
+                .with_caller(accounts().bob)
+                .with_value(101)
+                .build();
+
+            assert_eq!(
+                contract.lock_or_refund(secret_hash(), None, None, None, None),
+                Err(Error::TransferFailed)
+            );
+            assert_eq!(contract.next_lock_id, FIRST_LOCK_ID);
+            assert_no_event::<LockRejected>();
+        }
+
+        #[ink::test]
+        fn test_redeem_with_signature_works() {
+            let (mut contract, accounts) = harness().with_caller(accounts().alice).build();
+            set_value(100);
+            let lock_id = contract
+                .lock(secret_hash(), None, None, None, Some(redeemer_pubkey(1)))
+                .unwrap()
+                .lock_id;
+
+            let challenge = contract.signature_challenge(lock_id).unwrap();
+            assert!(contract
+                .redeem_with_signature(lock_id, sign(1, challenge), None)
+                .is_ok());
+            assert_eq!(contract.get_lock_info(lock_id), None);
+            assert_event::<Redeemed>(|e| e.lock_id == lock_id && e.locker == accounts.alice);
+        }
+
+        #[ink::test]
+        fn test_redeem_with_signature_fails_with_wrong_key() {
+            let (mut contract, _) = harness().with_caller(accounts().alice).build();
+            set_value(100);
+            let lock_id = contract
+                .lock(secret_hash(), None, None, None, Some(redeemer_pubkey(1)))
+                .unwrap()
+                .lock_id;
+
+            let challenge = contract.signature_challenge(lock_id).unwrap();
+            assert_eq!(
+                contract.redeem_with_signature(lock_id, sign(2, challenge), None),
+                Err(Error::InvalidSignature)
+            );
+            assert_eq!(
+                contract.redeem_with_signature(lock_id, sign(1, [0; 32]), None),
+                Err(Error::InvalidSignature)
+            );
+            assert_eq!(contract.get_locked_amount(lock_id), 100);
+        }
+
+        #[ink::test]
+        fn test_redeem_with_signature_cannot_be_replayed() {
+            let (mut contract, _) = harness().with_caller(accounts().alice).build();
+            set_value(100);
+            let first = contract
+                .lock(secret_hash(), None, None, None, Some(redeemer_pubkey(1)))
+                .unwrap()
+                .lock_id;
+            let second = contract
+                .lock(secret_hash(), None, None, None, Some(redeemer_pubkey(1)))
+                .unwrap()
+                .lock_id;
+
+            let signature = sign(1, contract.signature_challenge(first).unwrap());
+            assert!(contract
+                .redeem_with_signature(first, signature, Some(40))
+                .is_ok());
+
+            // Neither the remainder nor another lock accepts the same signature
+            assert_eq!(
+                contract.redeem_with_signature(first, signature, None),
+                Err(Error::InvalidSignature)
+            );
+            assert_eq!(
+                contract.redeem_with_signature(second, signature, None),
+                Err(Error::InvalidSignature)
+            );
+
+            // The remainder needs a signature over the advanced nonce
+            let signature = sign(1, contract.signature_challenge(first).unwrap());
+            assert!(contract
+                .redeem_with_signature(first, signature, None)
+                .is_ok());
+        }
+
+        #[ink::test]
+        fn test_redeem_with_signature_requires_redeemer_key() {
+            let (mut contract, _) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .locked()
+                .build();
+
+            let challenge = contract.signature_challenge(FIRST_LOCK_ID).unwrap();
+            assert_eq!(
+                contract.redeem_with_signature(FIRST_LOCK_ID, sign(1, challenge), None),
+                Err(Error::NoRedeemerKey)
+            );
+        }
+
+        #[ink::test]
+        fn test_deployer_is_owner() {
+            set_caller(accounts().bob);
+            let contract = deploy();
+            assert_eq!(contract.owner(), Some(accounts().bob));
+        }
+
+        #[ink::test]
+        fn test_transfer_ownership_works() {
+            let (mut contract, accounts) = harness().build();
+
+            assert!(contract.transfer_ownership(accounts.bob).is_ok());
+            assert_eq!(contract.owner(), Some(accounts.bob));
+            assert_event::<OwnershipTransferred>(|e| {
+                e.previous == Some(accounts.alice) && e.new == Some(accounts.bob)
+            });
+
+            // The previous owner lost the role
+            assert_eq!(
+                contract.transfer_ownership(accounts.alice),
+                Err(Error::NotOwner)
+            );
+        }
+
+        #[ink::test]
+        fn test_transfer_ownership_fails_when_not_owner() {
+            let (mut contract, accounts) = harness().with_caller(accounts().charlie).build();
+
+            assert_eq!(
+                contract.transfer_ownership(accounts.charlie),
+                Err(Error::NotOwner)
+            );
+            assert_eq!(contract.renounce_ownership(), Err(Error::NotOwner));
+            assert_eq!(contract.owner(), Some(accounts.alice));
+            assert_no_event::<OwnershipTransferred>();
+        }
+
+        #[ink::test]
+        fn test_renounce_ownership_is_permanent() {
+            let (mut contract, accounts) = harness().build();
+
+            assert!(contract.renounce_ownership().is_ok());
+            assert_eq!(contract.owner(), None);
+            assert_event::<OwnershipTransferred>(|e| {
+                e.previous == Some(accounts.alice) && e.new.is_none()
+            });
+
+            assert_eq!(
+                contract.transfer_ownership(accounts.alice),
+                Err(Error::NotOwner)
+            );
+            assert_eq!(contract.renounce_ownership(), Err(Error::NotOwner));
+        }
+
+        #[ink::test]
+        fn test_pause_blocks_locks_but_not_redeem() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .locked()
+                .build();
+
+            assert!(contract.pause().is_ok());
+            assert!(contract.paused());
+            assert_event::<Paused>(|e| e.by == accounts.alice);
+
+            assert_eq!(
+                contract.lock(secret_hash(), None, None, None, None),
+                Err(Error::ContractPaused)
+            );
+            assert_eq!(contract.top_up(FIRST_LOCK_ID), Err(Error::ContractPaused));
+            assert_eq!(
+                contract.lock_token(accounts.django, 100, secret_hash(), None, None, None),
+                Err(Error::ContractPaused)
+            );
+
+            // Users can still exit
+            assert!(contract
+                .redeem(FIRST_LOCK_ID, SECRET.to_vec(), None)
+                .is_ok());
+        }
+
+        #[ink::test]
+        fn test_unpause_resumes_locks() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .build();
+
+            contract.pause().unwrap();
+            assert!(contract.unpause().is_ok());
+            assert!(!contract.paused());
+            assert_event::<Unpaused>(|e| e.by == accounts.alice);
+            assert_eq!(
+                contract
+                    .lock(secret_hash(), None, None, None, None)
+                    .map(|receipt| receipt.lock_id),
+                Ok(FIRST_LOCK_ID)
+            );
+        }
+
+        #[ink::test]
+        fn test_pause_fails_when_not_owner() {
+            let (mut contract, _) = harness().with_caller(accounts().charlie).build();
+
+            assert_eq!(contract.pause(), Err(Error::NotOwner));
+            assert_eq!(contract.unpause(), Err(Error::NotOwner));
+            assert!(!contract.paused());
+            assert_no_event::<Paused>();
+        }
+
+        #[ink::test]
+        fn test_set_fee_works() {
+            let (mut contract, accounts) = harness().build();
+            assert_eq!(contract.fee(), (0, accounts.alice));
+
+            assert!(contract.set_fee(MAX_FEE_BPS).is_ok());
+            assert!(contract.set_fee_collector(accounts.django).is_ok());
+            assert_eq!(contract.fee(), (MAX_FEE_BPS, accounts.django));
+            assert_eq!(contract.set_fee(MAX_FEE_BPS + 1), Err(Error::FeeTooHigh));
+        }
+
+        #[ink::test]
+        fn test_set_fee_fails_when_not_owner() {
+            let (mut contract, accounts) = harness().with_caller(accounts().charlie).build();
+
+            assert_eq!(contract.set_fee(1), Err(Error::NotOwner));
+            assert_eq!(
+                contract.set_fee_collector(accounts.charlie),
+                Err(Error::NotOwner)
+            );
+            assert_eq!(contract.fee(), (0, accounts.alice));
+        }
+
+        #[ink::test]
+        fn test_fee_taken_on_redeem() {
+            let (mut contract, accounts) = harness().build();
+            contract.set_fee(MAX_FEE_BPS).unwrap();
+            contract.set_fee_collector(accounts.django).unwrap();
+            let collector_before = balance_of(accounts.django);
+
+            let (mut contract, _) = harness()
+                .with_contract(contract)
+                .with_caller(accounts.bob)
+                .with_value(9_999)
+                .locked()
+                .build();
+            assert!(contract
+                .redeem(FIRST_LOCK_ID, SECRET.to_vec(), None)
+                .is_ok());
+
+            // 10% of 9 999 is 999.9, rounded down
+            assert_event::<Redeemed>(|e| e.amount == 9_999 && e.fee == 999);
+            assert_eq!(balance_of(accounts.django), collector_before + 999);
+        }
+
+        #[ink::test]
+        fn test_fee_rounds_down_to_zero_on_one_unit() {
+            let (mut contract, accounts) = harness().build();
+            contract.set_fee(MAX_FEE_BPS).unwrap();
+            contract.set_fee_collector(accounts.django).unwrap();
+            let collector_before = balance_of(accounts.django);
+
+            let (mut contract, _) = harness()
+                .with_contract(contract)
+                .with_caller(accounts.bob)
+                .with_value(1)
+                .locked()
+                .build();
+            assert!(contract
+                .redeem(FIRST_LOCK_ID, SECRET.to_vec(), None)
+                .is_ok());
+
+            assert_event::<Redeemed>(|e| e.amount == 1 && e.fee == 0);
+            assert_eq!(balance_of(accounts.django), collector_before);
+        }
+
+        #[ink::test]
+        fn test_fee_math_does_not_overflow() {
+            let (mut contract, _) = harness().build();
+            contract.set_fee(MAX_FEE_BPS).unwrap();
+
+            assert_eq!(contract.fee_on(Balance::MAX), Balance::MAX / 10);
+            assert_eq!(contract.fee_on(10_000), 1_000);
+        }
+
+        #[ink::test]
+        fn test_redeem_that_would_reap_contract_keeps_lock() {
+            let (mut contract, _) = harness()
+                .with_caller(accounts().bob)
+                .with_value(100)
+                .locked()
+                .build();
+            let stored = contract.get_lock_info(FIRST_LOCK_ID);
+
+            // Exactly at the existential deposit, nothing can be paid out
+            fund_contract(existential_deposit());
+            assert_eq!(
+                contract.redeem(FIRST_LOCK_ID, SECRET.to_vec(), None),
+                Err(Error::WouldReapContract)
+            );
+            assert_eq!(contract.get_lock_info(FIRST_LOCK_ID), stored);
+            assert_no_event::<Redeemed>();
+
+            // Just above it, only what the contract can spare
+            fund_contract(existential_deposit() + 40);
+            assert_eq!(
+                contract.redeem(FIRST_LOCK_ID, SECRET.to_vec(), Some(41)),
+                Err(Error::WouldReapContract)
+            );
+            assert!(contract
+                .redeem(FIRST_LOCK_ID, SECRET.to_vec(), Some(40))
+                .is_ok());
+            assert_eq!(balance_of(contract_account()), existential_deposit());
+
+            // Well above it, the rest
+            fund_contract(existential_deposit() + 1_000_000);
+            assert!(contract
+                .redeem(FIRST_LOCK_ID, SECRET.to_vec(), None)
+                .is_ok());
+            assert_eq!(contract.get_lock_info(FIRST_LOCK_ID), None);
+            assert_event::<Redeemed>(|e| e.amount == 60 && e.shortfall == 0);
+        }
+
+        #[ink::test]
+        fn test_failed_cancel_and_refund_transfer_keeps_lock() {
+            use_unfunded_contract_account();
+            let (mut contract, _) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .with_expiry_in(days(1))
+                .locked()
+                .build();
+            let stored = contract.get_lock_info(FIRST_LOCK_ID);
+
+            assert_eq!(contract.cancel(FIRST_LOCK_ID), Err(Error::TransferFailed));
+            assert_eq!(contract.get_lock_info(FIRST_LOCK_ID), stored);
+
+            advance_time(days(1));
+            assert_eq!(contract.refund(FIRST_LOCK_ID), Err(Error::TransferFailed));
+            assert_eq!(contract.get_lock_info(FIRST_LOCK_ID), stored);
+            assert_no_event::<Cancelled>();
+            assert_no_event::<Refunded>();
+        }
+
+        #[ink::test]
+        fn test_history_records_settlements() {
+            set_time(days(1));
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .with_expiry_in(days(1))
+                .locked()
+                .locked()
+                .locked()
+                .build();
+            assert_eq!(contract.history_len(), 0);
+
+            set_time(days(2) - 1);
+            contract
+                .redeem(FIRST_LOCK_ID, SECRET.to_vec(), Some(40))
+                .unwrap();
+            contract.cancel(FIRST_LOCK_ID + 1).unwrap();
+            set_time(days(2));
+            contract.refund(FIRST_LOCK_ID + 2).unwrap();
+
+            assert_eq!(contract.history_len(), 3);
+            assert_eq!(
+                contract.get_record(0),
+                Some(LockRecord {
+                    lock_id: FIRST_LOCK_ID,
+                    locker: accounts.alice,
+                    amount: 40,
+                    locked_at: days(1),
+                    completed_at: days(2) - 1,
+                    outcome: Settlement::Redeemed,
+                })
+            );
+            let outcomes: Vec<_> = contract
+                .get_records(0, 10)
+                .iter()
+                .map(|record| (record.lock_id, record.outcome))
+                .collect();
+            assert_eq!(
+                outcomes,
+                [
+                    (FIRST_LOCK_ID, Settlement::Redeemed),
+                    (FIRST_LOCK_ID + 1, Settlement::Cancelled),
+                    (FIRST_LOCK_ID + 2, Settlement::Refunded),
+                ]
+            );
+            assert_eq!(contract.get_record(3), None);
+        }
+
+        #[ink::test]
+        fn test_get_records_limit_is_capped() {
+            let (mut contract, _) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .build();
+            for _ in 0..MAX_RECORDS_PER_QUERY + 1 {
+                let lock_id = contract
+                    .lock(secret_hash(), None, None, None, None)
+                    .unwrap()
+                    .lock_id;
+                contract.cancel(lock_id).unwrap();
+            }
+
+            assert_eq!(
+                contract.get_records(0, u32::MAX).len(),
+                MAX_RECORDS_PER_QUERY as usize
+            );
+            assert_eq!(
+                contract.get_records(MAX_RECORDS_PER_QUERY as u64, 10).len(),
+                1
+            );
+        }
+
+        #[ink::test]
+        fn test_prune_history() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .build();
+            for _ in 0..3 {
+                let lock_id = contract
+                    .lock(secret_hash(), None, None, None, None)
+                    .unwrap()
+                    .lock_id;
+                contract.cancel(lock_id).unwrap();
+            }
+
+            set_caller(accounts.bob);
+            assert_eq!(contract.prune_history(2), Err(Error::NotOwner));
+
+            set_caller(accounts.alice);
+            assert!(contract.prune_history(2).is_ok());
+            assert_eq!(contract.history_len(), 3);
+            assert_eq!(contract.get_record(1), None);
+            assert_eq!(contract.get_records(0, 10).len(), 1);
+            assert!(contract.get_record(2).is_some());
+        }
+
+        #[ink::test]
+        fn test_lock_limits_at_boundaries() {
+            let (mut contract, _) = harness()
+                .with_contract(deploy_with_limits(10, Some(100)))
+                .with_caller(accounts().alice)
+                .build();
+            assert_eq!(contract.limits(), (10, Some(100)));
+
+            set_value(9);
+            assert_eq!(
+                contract.lock(secret_hash(), None, None, None, None),
+                Err(Error::AmountBelowMinimum)
+            );
+            set_value(101);
+            assert_eq!(
+                contract.lock(secret_hash(), None, None, None, None),
+                Err(Error::AmountAboveMaximum)
+            );
+
+            set_value(10);
+            let lock_id = contract
+                .lock(secret_hash(), None, None, None, None)
+                .unwrap()
+                .lock_id;
+            set_value(100);
+            assert!(contract.lock(secret_hash(), None, None, None, None).is_ok());
+
+            // Top-ups respect the maximum too
+            set_value(91);
+            assert_eq!(contract.top_up(lock_id), Err(Error::AmountAboveMaximum));
+            set_value(90);
+            assert!(contract.top_up(lock_id).is_ok());
+        }
+
+        #[ink::test]
+        fn test_new_has_no_limits() {
+            let (contract, _) = harness().build();
+            assert_eq!(contract.limits(), (0, None));
+        }
+
+        #[ink::test]
+        fn test_set_limits_refused_while_locked() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .locked()
+                .build();
+
+            assert_eq!(
+                contract.set_limits(10, Some(50)),
+                Err(Error::AssetsAlreadyLocked)
+            );
+
+            contract
+                .redeem(FIRST_LOCK_ID, SECRET.to_vec(), Some(60))
+                .unwrap();
+            assert_eq!(
+                contract.set_limits(10, Some(50)),
+                Err(Error::AssetsAlreadyLocked)
+            );
+
+            contract
+                .redeem(FIRST_LOCK_ID, SECRET.to_vec(), None)
+                .unwrap();
+            set_caller(accounts.bob);
+            assert_eq!(contract.set_limits(10, Some(50)), Err(Error::NotOwner));
+            set_caller(accounts.alice);
+            assert!(contract.set_limits(10, Some(50)).is_ok());
+            assert_eq!(contract.limits(), (10, Some(50)));
+        }
+
+        #[ink::test]
+        fn test_vesting_claims_at_0_50_100_percent() {
+            set_time(days(10));
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().alice)
+                .with_value(1_000)
+                .build();
+            let lock_id = contract.lock_with_vesting(None, days(4)).unwrap();
+            assert_event::<VestingStarted>(|e| {
+                e.lock_id == lock_id && e.amount == 1_000 && e.start == days(10)
+            });
+
+            // 0%
+            assert_eq!(contract.claimable(lock_id), 0);
+            assert_eq!(contract.claim(lock_id), Err(Error::NothingToClaim));
+
+            // 50%
+            set_time(days(12));
+            assert_eq!(contract.claimable(lock_id), 500);
+            assert!(contract.claim(lock_id).is_ok());
+            assert_event::<Claimed>(|e| {
+                e.recipient == accounts.alice && e.amount == 500 && e.remaining == 500
+            });
+            assert_eq!(contract.claimable(lock_id), 0);
+
+            // 100%
+            set_time(days(14));
+            assert_eq!(contract.claimable(lock_id), 500);
+            assert!(contract.claim(lock_id).is_ok());
+            assert_event::<Claimed>(|e| e.amount == 500 && e.remaining == 0);
+            assert_eq!(contract.get_lock_info(lock_id), None);
+        }
+
+        #[ink::test]
+        fn test_vesting_final_claim_sweeps_dust() {
+            let (mut contract, _) = harness()
+                .with_caller(accounts().alice)
+                .with_value(10)
+                .build();
+            let lock_id = contract.lock_with_vesting(None, 3).unwrap();
+
+            let mut claimed = 0;
+            for time in 1..=2 {
+                set_time(time);
+                let amount = contract.claimable(lock_id);
+                contract.claim(lock_id).unwrap();
+                claimed += amount;
+            }
+            // floor(10 * 2 / 3)
+            assert_eq!(claimed, 6);
+
+            set_time(3);
+            assert_eq!(contract.claimable(lock_id), 4);
+            contract.claim(lock_id).unwrap();
+            assert_eq!(contract.get_lock_info(lock_id), None);
+        }
+
+        #[ink::test]
+        fn test_vesting_lock_rules() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .locked()
+                .build();
+            let lock_id = contract
+                .lock_with_vesting(Some(accounts.bob), days(1))
+                .unwrap();
+            set_time(days(1));
+
+            // Only the beneficiary claims, and only vesting locks can be claimed
+            assert_eq!(contract.claim(lock_id), Err(Error::NotBeneficiary));
+            assert_eq!(contract.claim(FIRST_LOCK_ID), Err(Error::NotVesting));
+
+            // Vesting locks are not redeemed or topped up
+            assert_eq!(contract.top_up(lock_id), Err(Error::VestingLock));
+            set_caller(accounts.bob);
+            assert_eq!(
+                contract.redeem(lock_id, Vec::new(), None),
+                Err(Error::VestingLock)
+            );
+            assert!(contract.claim(lock_id).is_ok());
+        }
+
+        #[ink::test]
+        fn test_escrow_state_machine_rejects_illegal_transitions() {
+            set_time(days(1));
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().bob)
+                .with_value(100)
+                .locked()
+                .build();
+            let lock_id = contract
+                .lock_escrow(accounts.charlie, EscrowDirection::ToLocker, days(2))
+                .unwrap();
+            assert_event::<EscrowOpened>(|e| {
+                e.lock_id == lock_id && e.counterparty == accounts.charlie && e.deadline == days(2)
+            });
+
+            // Escrow actions only apply to escrow locks
+            assert_eq!(contract.accept(FIRST_LOCK_ID), Err(Error::NotEscrow));
+            assert_eq!(contract.release(FIRST_LOCK_ID), Err(Error::NotEscrow));
+            assert_eq!(contract.withdraw(FIRST_LOCK_ID), Err(Error::NotEscrow));
+            assert_eq!(contract.accept(99), Err(Error::LockNotFound));
+
+            // Open: no release, no early withdrawal, and only the counterparty accepts
+            assert_eq!(contract.release(lock_id), Err(Error::NotAccepted));
+            assert_eq!(contract.withdraw(lock_id), Err(Error::NotExpired));
+            assert_eq!(contract.accept(lock_id), Err(Error::NotCounterparty));
+            assert_eq!(contract.top_up(lock_id), Err(Error::EscrowLock));
+            assert_eq!(contract.cancel(lock_id), Err(Error::EscrowLock));
+            assert_eq!(
+                contract.redeem(lock_id, Vec::new(), None),
+                Err(Error::EscrowLock)
+            );
+            set_caller(accounts.charlie);
+            assert_eq!(contract.withdraw(lock_id), Err(Error::NotLocker));
+
+            // Accepted: once only, no withdrawal, and only the counterparty releases
+            assert!(contract.accept(lock_id).is_ok());
+            assert_event::<EscrowAccepted>(|e| e.counterparty == accounts.charlie);
+            assert_eq!(contract.accept(lock_id), Err(Error::AlreadyAccepted));
+            set_caller(accounts.bob);
+            assert_eq!(contract.release(lock_id), Err(Error::NotCounterparty));
+            set_time(days(3));
+            assert_eq!(contract.withdraw(lock_id), Err(Error::AlreadyAccepted));
+
+            // Released: the lock is gone
+            set_caller(accounts.charlie);
+            assert!(contract.release(lock_id).is_ok());
+            assert_eq!(contract.release(lock_id), Err(Error::LockNotFound));
+            assert_eq!(contract.get_lock_info(lock_id), None);
+        }
+
+        #[ink::test]
+        fn test_escrow_release_to_counterparty() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().bob)
+                .with_value(100)
+                .build();
+            let lock_id = contract
+                .lock_escrow(accounts.charlie, EscrowDirection::ToCounterparty, days(1))
+                .unwrap();
+            set_caller(accounts.charlie);
+            contract.accept(lock_id).unwrap();
+            assert_eq!(contract.release(lock_id), Err(Error::NotLocker));
+
+            let charlie_before = balance_of(accounts.charlie);
+            set_caller(accounts.bob);
+            assert!(contract.release(lock_id).is_ok());
+            assert_eq!(balance_of(accounts.charlie), charlie_before + 100);
+            assert_event::<EscrowReleased>(|e| {
+                e.recipient == accounts.charlie && e.amount == 100 && e.fee == 0
+            });
+            assert_eq!(
+                contract.get_record(0).map(|r| r.outcome),
+                Some(Settlement::Released)
+            );
+        }
+
+        #[ink::test]
+        fn test_escrow_withdraw_after_missed_deadline() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().bob)
+                .with_value(100)
+                .build();
+            let lock_id = contract
+                .lock_escrow(accounts.charlie, EscrowDirection::ToLocker, days(1))
+                .unwrap();
+            set_time(days(1));
+            set_caller(accounts.charlie);
+            assert_eq!(contract.accept(lock_id), Err(Error::DeadlinePassed));
+
+            let bob_before = balance_of(accounts.bob);
+            set_caller(accounts.bob);
+            assert!(contract.withdraw(lock_id).is_ok());
+            assert_eq!(balance_of(accounts.bob), bob_before + 100);
+            assert_event::<EscrowWithdrawn>(|e| e.locker == accounts.bob && e.amount == 100);
+            assert_eq!(
+                contract.get_record(0).map(|r| r.outcome),
+                Some(Settlement::Withdrawn)
+            );
+        }
+
+        #[ink::test]
+        fn test_arbiter_resolves_both_ways() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().bob)
+                .with_value(100)
+                .build();
+            for (to_beneficiary, winner) in [(true, accounts.charlie), (false, accounts.bob)] {
+                set_caller(accounts.bob);
+                set_value(100);
+                let lock_id = contract
+                    .lock_with_arbiter(secret_hash(), accounts.charlie, accounts.django, None)
+                    .unwrap();
+                set_value(0);
+                contract.raise_dispute(lock_id).unwrap();
+                assert_event::<DisputeRaised>(|e| e.lock_id == lock_id && e.by == accounts.bob);
+
+                let winner_before = balance_of(winner);
+                set_caller(accounts.django);
+                assert!(contract.resolve(lock_id, to_beneficiary).is_ok());
+                assert_eq!(balance_of(winner), winner_before + 100);
+                assert_event::<Resolved>(|e| {
+                    e.lock_id == lock_id && e.winner == winner && e.amount == 100
+                });
+                assert_eq!(contract.get_lock_info(lock_id), None);
+            }
+        }
+
+        #[ink::test]
+        fn test_resolve_requires_arbiter_and_dispute() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().bob)
+                .with_value(100)
+                .build();
+            let lock_id = contract
+                .lock_with_arbiter(secret_hash(), accounts.charlie, accounts.django, None)
+                .unwrap();
+
+            // No dispute raised yet
+            set_caller(accounts.django);
+            assert_eq!(contract.resolve(lock_id, true), Err(Error::NotDisputed));
+            assert_eq!(contract.raise_dispute(lock_id), Err(Error::NotParty));
+
+            // Only the arbiter resolves, and only arbitrated locks can be disputed
+            set_caller(accounts.charlie);
+            set_value(0);
+            contract.raise_dispute(lock_id).unwrap();
+            assert_eq!(contract.raise_dispute(lock_id), Err(Error::Disputed));
+            assert_eq!(contract.resolve(lock_id, true), Err(Error::NotArbiter));
+            set_caller(accounts.bob);
+            assert_eq!(contract.resolve(lock_id, false), Err(Error::NotArbiter));
+            set_value(100);
+            let plain_id = contract
+                .lock(secret_hash(), None, None, None, None)
+                .unwrap()
+                .lock_id;
+            assert_eq!(contract.raise_dispute(plain_id), Err(Error::NotArbitrated));
+            assert_eq!(contract.resolve(plain_id, false), Err(Error::NotArbitrated));
+        }
+
+        #[ink::test]
+        fn test_dispute_freezes_normal_paths() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().bob)
+                .with_value(100)
+                .build();
+            let lock_id = contract
+                .lock_with_arbiter(
+                    secret_hash(),
+                    accounts.charlie,
+                    accounts.django,
+                    Some(days(1)),
+                )
+                .unwrap();
+            set_value(0);
+            contract.raise_dispute(lock_id).unwrap();
+            set_value(100);
+
+            assert_eq!(contract.cancel(lock_id), Err(Error::Disputed));
+            assert_eq!(contract.refund(lock_id), Err(Error::Disputed));
+            assert_eq!(contract.top_up(lock_id), Err(Error::Disputed));
+            set_caller(accounts.charlie);
+            assert_eq!(
+                contract.redeem(lock_id, SECRET.to_vec(), None),
+                Err(Error::Disputed)
+            );
+        }
+
+        #[ink::test]
+        fn test_arbitrated_lock_redeems_without_dispute() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().bob)
+                .with_value(100)
+                .build();
+            let lock_id = contract
+                .lock_with_arbiter(secret_hash(), accounts.charlie, accounts.django, None)
+                .unwrap();
+
+            set_caller(accounts.charlie);
+            assert!(contract.redeem(lock_id, SECRET.to_vec(), None).is_ok());
+            assert_event::<Redeemed>(|e| {
+                e.beneficiary == Some(accounts.charlie) && e.amount == 100
+            });
+        }
+
+        #[ink::test]
+        fn test_lock_batch_splits_transferred_value() {
+            let (mut contract, _) = harness()
+                .with_caller(accounts().alice)
+                .with_value(60)
+                .build();
+            let ids = contract
+                .lock_batch(vec![spec(10), spec(20), spec(30)])
+                .unwrap();
+
+            assert_eq!(ids, vec![1, 2, 3]);
+            assert_eq!(contract.get_locked_amount(2), 20);
+            assert_eq!(events::<Locked>().len(), 3);
+        }
+
+        #[ink::test]
+        fn test_lock_batch_fails_atomically() {
+            let (mut contract, _) = harness()
+                .with_contract(deploy_with_limits(5, None))
+                .with_caller(accounts().alice)
+                .with_value(60)
+                .build();
+
+            assert_eq!(
+                contract.lock_batch(vec![spec(10), spec(20)]),
+                Err(Error::BatchAmountMismatch)
+            );
+            assert_eq!(
+                contract.lock_batch(vec![spec(58), spec(2)]),
+                Err(Error::AmountBelowMinimum)
+            );
+            assert_eq!(
+                contract.lock_batch(vec![spec(60), spec(0)]),
+                Err(Error::ZeroAmount)
+            );
+            let specs = vec![spec(1); MAX_BATCH_LOCKS as usize + 1];
+            assert_eq!(
+                contract.lock_batch(specs),
+                Err(Error::BatchTooLarge {
+                    max: MAX_BATCH_LOCKS,
+                    got: MAX_BATCH_LOCKS + 1
+                })
+            );
+            assert_eq!(contract.active_locks, 0);
+            assert_eq!(events::<Locked>().len(), 0);
+        }
+
+        #[ink::test]
+        fn test_redeem_batch_reports_each_outcome() {
+            let (mut contract, _) = harness()
+                .with_caller(accounts().alice)
+                .with_value(60)
+                .build();
+            contract
+                .lock_batch(vec![spec(10), spec(20), spec(30)])
+                .unwrap();
+
+            let results = contract
+                .redeem_batch(
+                    vec![1, 99, 3],
+                    vec![SECRET.to_vec(), SECRET.to_vec(), SECRET.to_vec()],
+                )
+                .unwrap();
+            assert_eq!(results, vec![Ok(()), Err(Error::LockNotFound), Ok(())]);
+            assert_eq!(contract.get_locked_amount(1), 0);
+            assert_eq!(contract.get_locked_amount(2), 20);
+            assert_eq!(contract.get_locked_amount(3), 0);
+
+            assert_eq!(
+                contract.redeem_batch(vec![2], Vec::new()),
+                Err(Error::BatchLengthMismatch)
+            );
+            let ids = vec![2; MAX_BATCH_REDEEMS as usize + 1];
+            let preimages = vec![SECRET.to_vec(); ids.len()];
+            assert_eq!(
+                contract.redeem_batch(ids, preimages),
+                Err(Error::BatchTooLarge {
+                    max: MAX_BATCH_REDEEMS,
+                    got: MAX_BATCH_REDEEMS + 1
+                })
+            );
+        }
+
+        #[ink::test]
+        fn test_terminate_guard_conditions() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().bob)
+                .with_value(100)
+                .locked()
+                .build();
+
+            // Only the owner terminates or changes the grace period
+            assert_eq!(contract.terminate(), Err(Error::NotOwner));
+            assert_eq!(contract.set_termination_grace(0), Err(Error::NotOwner));
+
+            // A lock without expiry is never abandoned
+            set_caller(accounts.alice);
+            assert_eq!(contract.terminate(), Err(Error::ActiveLockExists));
+
+            // An expired lock blocks termination until the grace period has passed
+            set_caller(accounts.bob);
+            contract.cancel(FIRST_LOCK_ID).unwrap();
+            set_value(100);
+            contract
+                .lock(secret_hash(), None, Some(days(1)), None, None)
+                .unwrap();
+            set_caller(accounts.alice);
+            assert_eq!(contract.termination_grace(), DEFAULT_TERMINATION_GRACE);
+            set_time(days(1) + DEFAULT_TERMINATION_GRACE);
+            assert_eq!(contract.terminate(), Err(Error::ActiveLockExists));
+            assert_eq!(contract.active_locks, 1);
+        }
+
+        #[ink::test]
+        fn test_terminate_without_locks_pays_owner() {
+            let (mut contract, accounts) = harness().build();
+            contract.transfer_ownership(accounts.django).unwrap();
+            set_caller(accounts.django);
+            let balance = balance_of(contract_account());
+
+            assert_terminates(contract, accounts.django, balance);
+        }
+
+        #[ink::test]
+        fn test_terminate_refunds_abandoned_locks() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().bob)
+                .with_value(100)
+                .build();
+            contract
+                .lock(secret_hash(), None, Some(days(1)), None, None)
+                .unwrap();
+            set_caller(accounts.alice);
+            contract.set_termination_grace(days(30)).unwrap();
+            contract.transfer_ownership(accounts.django).unwrap();
+            set_caller(accounts.django);
+            set_time(days(31) + 1);
+            let bob_before = balance_of(accounts.bob);
+            let balance = balance_of(contract_account());
+
+            assert_terminates(contract, accounts.django, balance - 100);
+            assert_eq!(balance_of(accounts.bob), bob_before + 100);
+            assert_event::<Refunded>(|e| e.locker == accounts.bob && e.amount == 100);
+        }
+
+        #[ink::test]
+        fn test_stats_follow_settlements() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .locked()
+                .build();
+            contract
+                .redeem(FIRST_LOCK_ID, SECRET.to_vec(), None)
+                .unwrap();
+
+            // Failed attempts leave the counters alone
+            assert_eq!(
+                contract.redeem(FIRST_LOCK_ID, SECRET.to_vec(), None),
+                Err(Error::LockNotFound)
+            );
+            set_value(0);
+            assert_eq!(
+                contract.lock(secret_hash(), None, None, None, None),
+                Err(Error::NoAssetsSent)
+            );
+
+            set_value(50);
+            let lock_id = contract
+                .lock(secret_hash(), None, None, None, None)
+                .unwrap()
+                .lock_id;
+            set_value(25);
+            contract.top_up(lock_id).unwrap();
+            contract.cancel(lock_id).unwrap();
+            set_caller(accounts.bob);
+            assert_eq!(contract.cancel(lock_id), Err(Error::LockNotFound));
+
+            assert_eq!(
+                contract.stats(),
+                ContractStats {
+                    total_locks: 2,
+                    total_redeemed: 1,
+                    total_refunded: 0,
+                    total_cancelled: 1,
+                    cumulative_volume: 175,
+                }
+            );
+        }
+
+        #[ink::test]
+        fn test_stats_count_refunds() {
+            let (mut contract, _) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .with_expiry_in(days(1))
+                .locked()
+                .build();
+            assert_eq!(contract.refund(FIRST_LOCK_ID), Err(Error::NotExpired));
+            advance_time(days(1));
+            contract.refund(FIRST_LOCK_ID).unwrap();
+
+            assert_eq!(contract.stats().total_refunded, 1);
+            assert_eq!(contract.stats().total_redeemed, 0);
+        }
+
+        #[ink::test]
+        fn test_lock_for_gives_rights_to_named_locker() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().bob)
+                .with_value(100)
+                .build();
+            let lock_id = contract
+                .lock_for(accounts.charlie, secret_hash(), None, None, None, None)
+                .unwrap()
+                .lock_id;
+            assert_eq!(contract.get_locker(lock_id), Some(accounts.charlie));
+            assert_event::<Locked>(|e| e.locker == accounts.charlie && e.funded_by == accounts.bob);
+
+            // The funder has no rights over the lock
+            assert_eq!(
+                contract.redeem(lock_id, SECRET.to_vec(), None),
+                Err(Error::NotLocker)
+            );
+            assert_eq!(contract.cancel(lock_id), Err(Error::NotLocker));
+
+            set_caller(accounts.charlie);
+            assert!(contract.redeem(lock_id, SECRET.to_vec(), None).is_ok());
+        }
+
+        #[ink::test]
+        fn test_lock_for_self_matches_lock() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().bob)
+                .with_value(100)
+                .build();
+            let for_id = contract
+                .lock_for(accounts.bob, secret_hash(), None, None, None, None)
+                .unwrap()
+                .lock_id;
+            let lock_id = contract
+                .lock(secret_hash(), None, None, None, None)
+                .unwrap()
+                .lock_id;
+
+            assert_eq!(
+                contract.get_lock_info(for_id),
+                contract.get_lock_info(lock_id)
+            );
+            assert_event::<Locked>(|e| e.lock_id == for_id && e.funded_by == e.locker);
+        }
+
+        #[ink::test]
+        fn test_lock_for_rejects_invalid_locker() {
+            let (mut contract, _) = harness()
+                .with_caller(accounts().bob)
+                .with_value(100)
+                .build();
+
+            for locker in [AccountId::from([0; 32]), contract_account()] {
+                assert_eq!(
+                    contract.lock_for(locker, secret_hash(), None, None, None, None),
+                    Err(Error::InvalidLocker)
+                );
+            }
+            assert_eq!(contract.active_locks, 0);
+        }
+
+        #[ink::test]
+        fn test_allowlist_toggles_mid_lifecycle() {
+            let (mut contract, accounts) = harness().build();
+            contract.add_to_allowlist(accounts.bob).unwrap();
+            assert_event::<AllowlistUpdated>(|e| e.account == accounts.bob && e.allowed);
+            assert!(contract.is_allowed(accounts.bob));
+            assert!(!contract.is_allowed(accounts.charlie));
+
+            // Disabled: anyone locks
+            set_caller(accounts.charlie);
+            set_value(100);
+            let lock_id = contract
+                .lock(secret_hash(), None, None, None, None)
+                .unwrap()
+                .lock_id;
+
+            // Enabled: only listed accounts lock, existing locks still settle
+            set_caller(accounts.alice);
+            contract.set_allowlist_enabled(true).unwrap();
+            assert!(contract.allowlist_enabled());
+            set_caller(accounts.charlie);
+            assert_eq!(
+                contract.lock(secret_hash(), None, None, None, None),
+                Err(Error::NotAllowed)
+            );
+            assert_eq!(contract.lock_batch(vec![spec(100)]), Err(Error::NotAllowed));
+            assert!(contract.redeem(lock_id, SECRET.to_vec(), None).is_ok());
+            set_caller(accounts.bob);
+            assert!(contract.lock(secret_hash(), None, None, None, None).is_ok());
+
+            // Removed from the list
+            set_caller(accounts.alice);
+            contract.remove_from_allowlist(accounts.bob).unwrap();
+            assert_event::<AllowlistUpdated>(|e| e.account == accounts.bob && !e.allowed);
+            set_caller(accounts.bob);
+            assert_eq!(
+                contract.lock(secret_hash(), None, None, None, None),
+                Err(Error::NotAllowed)
+            );
+
+            // Disabled again: back to unchanged behavior
+            set_caller(accounts.alice);
+            contract.set_allowlist_enabled(false).unwrap();
+            set_caller(accounts.charlie);
+            assert!(contract.lock(secret_hash(), None, None, None, None).is_ok());
+        }
+
+        #[ink::test]
+        fn test_allowlist_is_owner_only() {
+            let (mut contract, accounts) = harness().with_caller(accounts().bob).build();
+
+            assert_eq!(
+                contract.add_to_allowlist(accounts.bob),
+                Err(Error::NotOwner)
+            );
+            assert_eq!(
+                contract.remove_from_allowlist(accounts.bob),
+                Err(Error::NotOwner)
+            );
+            assert_eq!(contract.set_allowlist_enabled(true), Err(Error::NotOwner));
+            assert!(!contract.allowlist_enabled());
+        }
+
+        #[ink::test]
+        fn test_lock_returns_receipt_and_events_carry_block() {
+            set_time(days(2));
+            set_block(7);
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .build();
+
+            let receipt = contract
+                .lock(secret_hash(), None, None, None, None)
+                .unwrap();
+            assert_eq!(
+                receipt,
+                LockReceipt {
+                    lock_id: FIRST_LOCK_ID,
+                    amount: 100,
+                    timestamp: days(2),
+                    block_number: 7,
+                }
+            );
+            assert_event::<Locked>(|e| e.timestamp == days(2) && e.block_number == 7);
+            let receipt = contract
+                .lock_for(accounts.bob, secret_hash(), None, None, None, None)
+                .unwrap();
+            assert_eq!(receipt.lock_id, FIRST_LOCK_ID + 1);
+
+            contract
+                .redeem(FIRST_LOCK_ID, SECRET.to_vec(), None)
+                .unwrap();
+            assert_event::<Redeemed>(|e| e.timestamp == days(2) && e.block_number == 7);
+            assert_eq!(contract.version(), CONTRACT_VERSION);
+        }
+
+        #[ink::test]
+        fn test_redeem_pays_exact_accrued_reward() {
+            let (mut contract, accounts) = harness().with_value(1_000).build();
+            contract.fund_rewards().unwrap();
+            contract.set_reward_rate(10).unwrap();
+            set_caller(accounts.bob);
+            set_value(10_000);
+            let lock_id = contract
+                .lock(secret_hash(), None, None, None, None)
+                .unwrap()
+                .lock_id;
+
+            // Only full days count: 3 days and a bit earn 10_000 * 10 * 3 / 10_000
+            advance_time(days(3) + days(1) - 1);
+            let bob_before = balance_of(accounts.bob);
+            contract.redeem(lock_id, SECRET.to_vec(), None).unwrap();
+            assert_eq!(balance_of(accounts.bob), bob_before + 10_030);
+            assert_event::<Redeemed>(|e| e.amount == 10_000 && e.reward == 30);
+            assert_eq!(contract.rewards(), (970, 10));
+        }
+
+        #[ink::test]
+        fn test_reward_is_zero_for_zero_duration_and_capped_by_pool() {
+            let (mut contract, accounts) = harness().with_value(50).build();
+            contract.fund_rewards().unwrap();
+            contract.set_reward_rate(100).unwrap();
+            set_caller(accounts.bob);
+            set_value(10_000);
+            let first = contract
+                .lock(secret_hash(), None, None, None, None)
+                .unwrap()
+                .lock_id;
+            let second = contract
+                .lock(secret_hash(), None, None, None, None)
+                .unwrap()
+                .lock_id;
+
+            // Redeemed right away: nothing accrued
+            contract.redeem(first, SECRET.to_vec(), None).unwrap();
+            assert_event::<Redeemed>(|e| e.lock_id == first && e.reward == 0);
+
+            // 100 accrued, but the pool only holds 50
+            advance_time(days(1));
+            contract.redeem(second, SECRET.to_vec(), None).unwrap();
+            assert_event::<Redeemed>(|e| e.lock_id == second && e.reward == 50);
+            assert_eq!(contract.rewards().0, 0);
+        }
+
+        #[ink::test]
+        fn test_withdraw_rewards_keeps_accrued_obligations() {
+            let (mut contract, accounts) = harness().with_value(1_000).build();
+            contract.fund_rewards().unwrap();
+            contract.set_reward_rate(10).unwrap();
+            set_caller(accounts.bob);
+            set_value(100_000);
+            contract
+                .lock(secret_hash(), None, None, None, None)
+                .unwrap();
+            assert_eq!(contract.fund_rewards(), Err(Error::NotOwner));
+            assert_eq!(contract.withdraw_rewards(1), Err(Error::NotOwner));
+            assert_eq!(contract.set_reward_rate(1), Err(Error::NotOwner));
+
+            // After 2 days, 200 of the pool is owed to the lock
+            advance_time(days(2));
+            set_caller(accounts.alice);
+            assert_eq!(contract.withdraw_rewards(801), Err(Error::RewardsCommitted));
+            assert!(contract.withdraw_rewards(800).is_ok());
+            assert_eq!(contract.rewards().0, 200);
+        }
+
+        #[ink::test]
+        fn test_upgrade_is_owner_only() {
+            let (mut contract, _) = harness().with_caller(accounts().bob).build();
+
+            assert_eq!(contract.upgrade(Hash::from([7; 32])), Err(Error::NotOwner));
+            assert_no_event::<Upgraded>();
+        }
+
+        #[ink::test]
+        fn test_migrate_runs_once_per_version_bump() {
+            let (mut contract, accounts) = harness().build();
+            assert_eq!(contract.storage_version(), STORAGE_VERSION);
+            assert_eq!(contract.migrate(), Err(Error::AlreadyMigrated));
+
+            // Storage left behind by older code
+            contract.storage_version = STORAGE_VERSION - 1;
+            set_caller(accounts.bob);
+            assert_eq!(contract.migrate(), Err(Error::NotOwner));
+            set_caller(accounts.alice);
+            assert!(contract.migrate().is_ok());
+            assert_event::<Migrated>(|e| e.from == STORAGE_VERSION - 1 && e.to == STORAGE_VERSION);
+            assert_eq!(contract.storage_version(), STORAGE_VERSION);
+            assert_eq!(contract.migrate(), Err(Error::AlreadyMigrated));
+        }
+
+        #[cfg(feature = "assets")]
+        #[ink::test]
+        fn test_lock_asset_pulls_and_redeem_pays_out_asset() {
+            let ledger = mock_assets();
+            let (mut contract, accounts) = harness().with_caller(accounts().bob).build();
+
+            let lock_id = contract
+                .lock_asset(7, 500, secret_hash(), None, None, None)
+                .unwrap();
+            assert_eq!(ledger.borrow().held, 500);
+            assert_event::<Locked>(|e| e.asset_id == Some(7) && e.amount == Some(500));
+
+            // Native assets cannot be mixed into an asset lock
+            set_value(100);
+            assert_eq!(contract.top_up(lock_id), Err(Error::AssetMismatch));
+
+            assert!(contract.redeem(lock_id, SECRET.to_vec(), None).is_ok());
+            assert_eq!(ledger.borrow().held, 0);
+            assert_eq!(ledger.borrow().paid, vec![(accounts.bob, 500)]);
+        }
+
+        #[cfg(feature = "assets")]
+        #[ink::test]
+        fn test_lock_asset_fails_when_pull_is_rejected() {
+            let ledger = mock_assets();
+            ledger.borrow_mut().reject_pull = true;
+            let (mut contract, _) = harness().with_caller(accounts().bob).build();
+
+            assert_eq!(
+                contract.lock_asset(7, 500, secret_hash(), None, None, None),
+                Err(Error::TokenTransferFailed)
+            );
+            assert_eq!(
+                contract.lock_asset(7, 0, secret_hash(), None, None, None),
+                Err(Error::ZeroAmount)
+            );
+            assert_eq!(contract.active_locks, 0);
+        }
+
+        #[cfg(feature = "assets")]
+        #[ink::test]
+        fn test_redeem_batch_fails_whole_when_a_payout_fails() {
+            let ledger = mock_assets();
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().bob)
+                .with_value(100)
+                .locked()
+                .build();
+            let lock_id = contract
+                .lock_asset(7, 500, secret_hash(), None, None, None)
+                .unwrap();
+            set_caller(accounts.alice);
+            contract.set_fee(100).unwrap();
+            contract.set_fee_collector(accounts.charlie).unwrap();
+
+            // The redeemer is paid, then the fee transfer fails: the batch must fail as a
+            // whole so that the call reverts, rather than collect the error and keep the
+            // redeemer's payout
+            ledger.borrow_mut().reject_payout_to = Some(accounts.charlie);
+            set_caller(accounts.bob);
+            let preimages = vec![SECRET.to_vec(), SECRET.to_vec()];
+            assert_eq!(
+                contract.redeem_batch(vec![FIRST_LOCK_ID, lock_id], preimages.clone()),
+                Err(Error::TokenTransferFailed)
+            );
+            assert!(contract.get_lock_info(lock_id).is_some());
+
+            // Once payouts go through the lock is redeemed exactly once. The off-chain
+            // engine does not revert the failed call, so undo its payout by hand
+            ledger.borrow_mut().reject_payout_to = None;
+            ledger.borrow_mut().held = 500;
+            assert_eq!(
+                contract.redeem_batch(vec![lock_id, lock_id], preimages),
+                Ok(vec![Ok(()), Err(Error::LockNotFound)])
+            );
+            assert_eq!(contract.get_lock_info(lock_id), None);
+        }
+
+        #[ink::test]
+        fn test_reveal_redeems_matured_commitment() {
+            let (mut contract, accounts) = harness().with_value(100).locked().build();
+            let commitment = contract.redeem_commitment(accounts.alice, SECRET.to_vec());
+            set_block(10);
+            contract.commit(FIRST_LOCK_ID, commitment);
+
+            assert_eq!(
+                contract.reveal(FIRST_LOCK_ID, SECRET.to_vec(), None),
+                Err(Error::CommitmentTooFresh)
+            );
+            set_block(11);
+            assert!(contract
+                .reveal(FIRST_LOCK_ID, SECRET.to_vec(), None)
+                .is_ok());
+            assert_eq!(contract.locks.get(FIRST_LOCK_ID), None);
+            assert_eq!(
+                contract.commitments.get((accounts.alice, FIRST_LOCK_ID)),
+                None
+            );
+            assert_event::<Redeemed>(|e| e.lock_id == FIRST_LOCK_ID && e.amount == 100);
+        }
+
+        #[ink::test]
+        fn test_reveal_requires_own_matching_commitment() {
+            let (mut contract, accounts) = harness()
+                .with_contract(deploy_with_commit_age(5))
+                .with_value(100)
+                .locked()
+                .build();
+            assert_eq!(
+                contract.reveal(FIRST_LOCK_ID, SECRET.to_vec(), None),
+                Err(Error::NoCommitment)
+            );
+
+            // A commitment binds the committer, so bob's cannot be revealed by alice
+            let commitment = contract.redeem_commitment(accounts.bob, SECRET.to_vec());
+            contract.commit(FIRST_LOCK_ID, commitment);
+            set_block(5);
+            assert_eq!(
+                contract.reveal(FIRST_LOCK_ID, SECRET.to_vec(), None),
+                Err(Error::CommitmentMismatch)
+            );
+
+            // Re-committing replaces the old commitment and restarts its age
+            let commitment = contract.redeem_commitment(accounts.alice, SECRET.to_vec());
+            contract.commit(FIRST_LOCK_ID, commitment);
+            set_block(9);
+            assert_eq!(
+                contract.reveal(FIRST_LOCK_ID, SECRET.to_vec(), None),
+                Err(Error::CommitmentTooFresh)
+            );
+            set_block(10);
+            assert!(contract
+                .reveal(FIRST_LOCK_ID, SECRET.to_vec(), None)
+                .is_ok());
+        }
+
+        #[ink::test]
+        fn test_failed_reveal_keeps_commitment() {
+            let (mut contract, accounts) = harness().with_value(100).locked().build();
+            let commitment = contract.redeem_commitment(accounts.alice, b"wrong".to_vec());
+            contract.commit(FIRST_LOCK_ID, commitment);
+            set_block(1);
+
+            assert_eq!(
+                contract.reveal(FIRST_LOCK_ID, b"wrong".to_vec(), None),
+                Err(Error::InvalidPreimage)
+            );
+            assert!(contract
+                .commitments
+                .get((accounts.alice, FIRST_LOCK_ID))
+                .is_some());
+            assert!(contract.locks.get(FIRST_LOCK_ID).is_some());
+        }
+
+        #[ink::test]
+        fn test_extension_needs_both_parties() {
+            let (mut contract, accounts) = harness().with_value(100).build();
+            let expiry = now() + days(1);
+            contract
+                .lock(secret_hash(), Some(accounts.bob), Some(expiry), None, None)
+                .unwrap();
+            let later = expiry + days(1);
+
+            // Neither a third party nor the proposer alone can move the expiry
+            set_caller(accounts.charlie);
+            assert_eq!(
+                contract.propose_extension(FIRST_LOCK_ID, later),
+                Err(Error::NotParty)
+            );
+            set_caller(accounts.alice);
+            contract.propose_extension(FIRST_LOCK_ID, later).unwrap();
+            assert_event::<ExtensionProposed>(|e| {
+                e.lock_id == FIRST_LOCK_ID && e.proposer == accounts.alice && e.new_expiry == later
+            });
+            assert_eq!(
+                contract.accept_extension(FIRST_LOCK_ID),
+                Err(Error::NoExtensionProposal)
+            );
+            set_caller(accounts.charlie);
+            assert_eq!(
+                contract.accept_extension(FIRST_LOCK_ID),
+                Err(Error::NotParty)
+            );
+            assert_eq!(
+                contract.locks.get(FIRST_LOCK_ID).unwrap().expiry,
+                Some(expiry)
+            );
+
+            set_caller(accounts.bob);
+            contract.accept_extension(FIRST_LOCK_ID).unwrap();
+            let lock = contract.locks.get(FIRST_LOCK_ID).unwrap();
+            assert_eq!(lock.expiry, Some(later));
+            assert_eq!(lock.extension, None);
+            assert_event::<ExtensionAccepted>(|e| {
+                e.lock_id == FIRST_LOCK_ID && e.accepted_by == accounts.bob && e.new_expiry == later
+            });
+        }
+
+        #[ink::test]
+        fn test_extension_proposal_is_superseded() {
+            let (mut contract, accounts) = harness().with_value(100).build();
+            let expiry = now() + days(1);
+            contract
+                .lock(secret_hash(), Some(accounts.bob), Some(expiry), None, None)
+                .unwrap();
+            contract
+                .propose_extension(FIRST_LOCK_ID, expiry + days(7))
+                .unwrap();
+
+            // The beneficiary counters with a shorter extension, which the locker accepts
+            set_caller(accounts.bob);
+            contract
+                .propose_extension(FIRST_LOCK_ID, expiry + days(2))
+                .unwrap();
+            assert_eq!(
+                contract.accept_extension(FIRST_LOCK_ID),
+                Err(Error::NoExtensionProposal)
+            );
+            set_caller(accounts.alice);
+            contract.accept_extension(FIRST_LOCK_ID).unwrap();
+            assert_eq!(
+                contract.locks.get(FIRST_LOCK_ID).unwrap().expiry,
+                Some(expiry + days(2))
+            );
+        }
+
+        #[ink::test]
+        fn test_extension_requires_later_unexpired_expiry() {
+            let (mut contract, accounts) = harness().with_value(100).build();
+            let expiry = now() + days(1);
+            contract
+                .lock(secret_hash(), Some(accounts.bob), Some(expiry), None, None)
+                .unwrap();
+            contract
+                .lock(secret_hash(), Some(accounts.bob), None, None, None)
+                .unwrap();
+            contract
+                .lock(secret_hash(), None, Some(expiry), None, None)
+                .unwrap();
+
+            assert_eq!(
+                contract.propose_extension(FIRST_LOCK_ID, expiry),
+                Err(Error::InvalidExpiry)
+            );
+            assert_eq!(
+                contract.propose_extension(FIRST_LOCK_ID + 1, expiry),
+                Err(Error::InvalidExpiry)
+            );
+            assert_eq!(
+                contract.propose_extension(FIRST_LOCK_ID + 2, expiry + 1),
+                Err(Error::NotParty)
+            );
+
+            // A proposal made in time cannot be accepted once the lock has expired
+            contract
+                .propose_extension(FIRST_LOCK_ID, expiry + days(1))
+                .unwrap();
+            set_time(expiry);
+            assert_eq!(
+                contract.propose_extension(FIRST_LOCK_ID, expiry + days(2)),
+                Err(Error::Expired)
+            );
+            set_caller(accounts.bob);
+            assert_eq!(
+                contract.accept_extension(FIRST_LOCK_ID),
+                Err(Error::Expired)
+            );
+            assert_eq!(
+                contract.locks.get(FIRST_LOCK_ID).unwrap().expiry,
+                Some(expiry)
+            );
+        }
+
+        #[ink::test]
+        fn test_redemption_discards_extension_proposal() {
+            let (mut contract, accounts) = harness().with_value(100).build();
+            let expiry = now() + days(1);
+            contract
+                .lock(secret_hash(), Some(accounts.bob), Some(expiry), None, None)
+                .unwrap();
+            contract
+                .propose_extension(FIRST_LOCK_ID, expiry + days(1))
+                .unwrap();
+
+            set_caller(accounts.bob);
+            contract
+                .redeem(FIRST_LOCK_ID, SECRET.to_vec(), Some(40))
+                .unwrap();
+            assert_eq!(contract.locks.get(FIRST_LOCK_ID).unwrap().extension, None);
+            assert_eq!(
+                contract.accept_extension(FIRST_LOCK_ID),
+                Err(Error::NoExtensionProposal)
+            );
+
+            contract
+                .redeem(FIRST_LOCK_ID, SECRET.to_vec(), None)
+                .unwrap();
+            assert_eq!(
+                contract.accept_extension(FIRST_LOCK_ID),
+                Err(Error::LockNotFound)
+            );
+        }
+
+        #[ink::test]
+        fn test_redeem_pays_available_balance_under_pay_available() {
+            let (mut contract, accounts) = harness()
+                .with_contract(deploy_with_reap_policy(ReapPolicy::PayAvailable))
+                .build();
+            contract.set_fee(MAX_FEE_BPS).unwrap();
+            contract.set_fee_collector(accounts.django).unwrap();
+            let (mut contract, _) = harness()
+                .with_contract(contract)
+                .with_caller(accounts.bob)
+                .with_value(100)
+                .locked()
+                .locked()
+                .locked()
+                .build();
+            let bob_before = balance_of(accounts.bob);
+            let collector_before = balance_of(accounts.django);
+
+            // Well above the deposit: 90 to bob, 10 to the collector
+            fund_contract(existential_deposit() + 1_000_000);
+            contract
+                .redeem(FIRST_LOCK_ID, SECRET.to_vec(), None)
+                .unwrap();
+            assert_event::<Redeemed>(|e| e.fee == 10 && e.shortfall == 0);
+            assert_eq!(balance_of(accounts.bob), bob_before + 90);
+
+            // Just above: the fee absorbs the shortfall first
+            fund_contract(existential_deposit() + 95);
+            contract
+                .redeem(FIRST_LOCK_ID + 1, SECRET.to_vec(), None)
+                .unwrap();
+            assert_event::<Redeemed>(|e| e.amount == 100 && e.fee == 5 && e.shortfall == 5);
+            assert_eq!(balance_of(accounts.bob), bob_before + 180);
+            assert_eq!(balance_of(accounts.django), collector_before + 15);
+
+            // Exactly at the deposit: the lock is settled without a payout
+            fund_contract(existential_deposit());
+            contract
+                .redeem(FIRST_LOCK_ID + 2, SECRET.to_vec(), None)
+                .unwrap();
+            assert_event::<Redeemed>(|e| e.fee == 0 && e.shortfall == 100);
+            assert_eq!(balance_of(accounts.bob), bob_before + 180);
+            assert_eq!(balance_of(contract_account()), existential_deposit());
+            assert_eq!(contract.active_locks, 0);
+        }
+
+        #[ink::test]
+        fn test_approved_lock_pays_locker_after_both_approvals() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().bob)
+                .with_value(100)
+                .build();
+            let lock_id = contract
+                .lock_with_approvers(accounts.charlie, accounts.django)
+                .unwrap();
+            let bob_before = balance_of(accounts.bob);
+            assert_eq!(
+                contract.redeem(lock_id, Vec::new(), None),
+                Err(Error::ApprovalLock)
+            );
+            assert_eq!(contract.approve_redeem(lock_id), Err(Error::NotApprover));
+
+            set_caller(accounts.charlie);
+            contract.approve_redeem(lock_id).unwrap();
+            assert_event::<ApprovalGiven>(|e| e.approver == accounts.charlie);
+            assert_eq!(
+                contract.approve_redeem(lock_id),
+                Err(Error::AlreadyApproved)
+            );
+            assert_eq!(
+                contract.execute_redeem(lock_id),
+                Err(Error::InsufficientApprovals)
+            );
+
+            set_caller(accounts.django);
+            contract.approve_redeem(lock_id).unwrap();
+            assert_event::<ApprovalGiven>(|e| e.approver == accounts.django);
+
+            // Anyone can execute, but the funds only go to the locker
+            set_caller(accounts.eve);
+            contract.execute_redeem(lock_id).unwrap();
+            assert_eq!(balance_of(accounts.bob), bob_before + 100);
+            assert_eq!(contract.get_lock_info(lock_id), None);
+            assert_event::<ApprovedRedeemed>(|e| {
+                e.lock_id == lock_id && e.locker == accounts.bob && e.amount == 100
+            });
+            assert_eq!(contract.stats().total_redeemed, 1);
+        }
+
+        #[ink::test]
+        fn test_revoked_approval_blocks_execution() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().bob)
+                .with_value(100)
+                .build();
+            assert_eq!(
+                contract.lock_with_approvers(accounts.charlie, accounts.charlie),
+                Err(Error::DuplicateApprover)
+            );
+            let lock_id = contract
+                .lock_with_approvers(accounts.charlie, accounts.django)
+                .unwrap();
+
+            set_caller(accounts.charlie);
+            assert_eq!(
+                contract.revoke_approval(lock_id),
+                Err(Error::InsufficientApprovals)
+            );
+            contract.approve_redeem(lock_id).unwrap();
+            set_caller(accounts.django);
+            contract.approve_redeem(lock_id).unwrap();
+            contract.revoke_approval(lock_id).unwrap();
+            assert_event::<ApprovalRevoked>(|e| {
+                e.lock_id == lock_id && e.approver == accounts.django
+            });
+            assert_eq!(
+                contract.execute_redeem(lock_id),
+                Err(Error::InsufficientApprovals)
+            );
+            let approvals = contract.get_lock_info(lock_id).unwrap().approvals.unwrap();
+            assert_eq!(approvals.granted, 0b01);
+
+            // Cancelling discards the lock along with the remaining approval
+            set_caller(accounts.bob);
+            contract.cancel(lock_id).unwrap();
+            set_caller(accounts.django);
+            assert_eq!(contract.approve_redeem(lock_id), Err(Error::LockNotFound));
+            assert_eq!(contract.execute_redeem(lock_id), Err(Error::LockNotFound));
+        }
+
+        #[ink::test]
+        fn test_stream_withdrawals_add_up_to_original() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().bob)
+                .with_value(1_000)
+                .build();
+            let lock_id = contract.open_stream(accounts.charlie, 7_000).unwrap();
+            assert_event::<StreamOpened>(|e| {
+                e.lock_id == lock_id && e.beneficiary == accounts.charlie && e.amount == 1_000
+            });
+            let charlie_before = balance_of(accounts.charlie);
+
+            set_caller(accounts.charlie);
+            let mut withdrawn = 0;
+            for step in [1_234, 0, 2_099, 3_666, 1] {
+                advance_time(step);
+                match contract.withdraw_stream(lock_id) {
+                    Ok(()) => {}
+                    Err(Error::NothingToClaim) => assert_eq!(step, 0),
+                    Err(error) => panic!("unexpected {error:?}"),
+                }
+                withdrawn = balance_of(accounts.charlie) - charlie_before;
+                let remaining = contract.get_locked_amount(lock_id);
+                assert_eq!(withdrawn + remaining, 1_000);
+            }
+
+            // The last step reaches the end exactly, with no rounding left behind
+            assert_eq!(withdrawn, 1_000);
+            assert_eq!(contract.get_lock_info(lock_id), None);
+            assert_event::<StreamWithdrawn>(|e| e.withdrawn == 1_000 && e.remaining == 0);
+            assert_no_event::<Claimed>();
+        }
+
+        #[ink::test]
+        fn test_cancel_stream_splits_pro_rata() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().bob)
+                .with_value(1_000)
+                .build();
+            let lock_id = contract.open_stream(accounts.charlie, 10_000).unwrap();
+            set_value(100);
+            let vesting_id = contract.lock_with_vesting(None, 10_000).unwrap();
+            let bob_before = balance_of(accounts.bob);
+            let charlie_before = balance_of(accounts.charlie);
+
+            advance_time(2_500);
+            set_caller(accounts.charlie);
+            contract.withdraw_stream(lock_id).unwrap();
+            assert_eq!(contract.cancel_stream(lock_id), Err(Error::NotLocker));
+            set_caller(accounts.bob);
+            assert_eq!(contract.withdraw_stream(vesting_id), Err(Error::NotStream));
+            assert_eq!(contract.cancel_stream(vesting_id), Err(Error::NotStream));
+            assert_eq!(
+                contract.withdraw_stream(lock_id),
+                Err(Error::NotBeneficiary)
+            );
+
+            // At 40%, 150 more is owed to charlie and 600 goes back to bob
+            advance_time(1_500);
+            contract.cancel_stream(lock_id).unwrap();
+            assert_eq!(balance_of(accounts.charlie), charlie_before + 400);
+            assert_eq!(balance_of(accounts.bob), bob_before + 600);
+            assert_eq!(contract.get_lock_info(lock_id), None);
+            assert_event::<StreamCancelled>(|e| {
+                e.lock_id == lock_id && e.streamed == 150 && e.returned == 600
+            });
+        }
+
+        #[ink::test]
+        fn test_redeem_fails_when_reward_overflows_payout() {
+            let (mut contract, _) = harness().with_value(1_000).build();
+            contract.fund_rewards().unwrap();
+            contract.set_reward_rate(1).unwrap();
+            set_value(Balance::MAX);
+            contract
+                .lock(secret_hash(), None, None, None, None)
+                .unwrap();
+
+            advance_time(days(1));
+            assert_eq!(
+                contract.redeem(FIRST_LOCK_ID, SECRET.to_vec(), None),
+                Err(Error::Overflow)
+            );
+            assert_eq!(contract.get_locked_amount(FIRST_LOCK_ID), Balance::MAX);
+            assert_eq!(contract.rewards().0, 1_000);
+            assert_no_event::<Redeemed>();
+        }
+
+        #[ink::test]
+        fn test_can_redeem_matches_redeem() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().bob)
+                .with_value(100)
+                .with_expiry_in(days(1))
+                .locked()
+                .build();
+            let shielded_id = contract
+                .lock_shielded(secret_hash(), None, None, None, [9; 32])
+                .unwrap();
+
+            // Asks `can_redeem`, then calls `redeem` and checks it did what was predicted. A
+            // shielded lock is reported as zero, so its payout is the whole stored amount.
+            let mut check = |caller: AccountId, lock_id: u64, preimage: &[u8], amount| {
+                let verdict = contract.can_redeem(caller, lock_id, preimage.to_vec(), amount);
+                let shielded = contract
+                    .locks
+                    .get(lock_id)
+                    .filter(|lock| lock.shield_salt.is_some())
+                    .map(|lock| lock.amount);
+                set_caller(caller);
+                let before = balance_of(caller);
+                let result = contract.redeem(lock_id, preimage.to_vec(), amount);
+                match verdict {
+                    RedeemCheck::Ok { payout, .. } => {
+                        assert_eq!(result, Ok(()));
+                        assert_eq!(balance_of(caller), before + shielded.unwrap_or(payout));
+                    }
+                    RedeemCheck::Rejected { reason } => assert_eq!(result, Err(reason)),
+                }
+                verdict
+            };
+            let rejected = |reason| RedeemCheck::Rejected { reason };
+
+            assert_eq!(
+                check(accounts.charlie, FIRST_LOCK_ID, SECRET, None),
+                rejected(Error::NotLocker)
+            );
+            assert_eq!(
+                check(accounts.bob, FIRST_LOCK_ID, b"wrong", None),
+                rejected(Error::InvalidPreimage)
+            );
+            assert_eq!(
+                check(accounts.bob, 99, SECRET, None),
+                rejected(Error::LockNotFound)
+            );
+            assert_eq!(
+                check(accounts.bob, FIRST_LOCK_ID, SECRET, Some(0)),
+                rejected(Error::ZeroAmount)
+            );
+            assert_eq!(
+                check(accounts.bob, FIRST_LOCK_ID, SECRET, Some(101)),
+                rejected(Error::InsufficientLockedBalance)
+            );
+            assert_eq!(
+                check(accounts.bob, FIRST_LOCK_ID, SECRET, Some(40)),
+                RedeemCheck::Ok {
+                    amount: 40,
+                    payout: 40
+                }
+            );
+            assert_eq!(
+                check(accounts.bob, FIRST_LOCK_ID, SECRET, None),
+                RedeemCheck::Ok {
+                    amount: 60,
+                    payout: 60
+                }
+            );
+            assert_eq!(
+                check(accounts.bob, FIRST_LOCK_ID, SECRET, None),
+                rejected(Error::LockNotFound)
+            );
+
+            // A shielded lock is only redeemed whole
+            assert_eq!(
+                check(accounts.charlie, shielded_id, SECRET, Some(40)),
+                rejected(Error::NotLocker)
+            );
+            assert_eq!(
+                check(accounts.bob, shielded_id, SECRET, Some(40)),
+                rejected(Error::Shielded)
+            );
+            assert_eq!(
+                check(accounts.bob, shielded_id, SECRET, Some(100)),
+                RedeemCheck::Ok {
+                    amount: 0,
+                    payout: 0
+                }
+            );
+            assert_eq!(
+                check(accounts.bob, shielded_id, SECRET, None),
+                rejected(Error::LockNotFound)
+            );
+        }
+
+        #[ink::test]
+        fn test_can_redeem_reports_amounts_and_expiry() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().bob)
+                .with_value(100)
+                .with_expiry_in(days(1))
+                .locked()
+                .build();
+
+            // The check is made for the given caller, not whoever queries
+            set_caller(accounts.charlie);
+            assert_eq!(
+                contract.can_redeem(accounts.bob, FIRST_LOCK_ID, SECRET.to_vec(), Some(40)),
+                RedeemCheck::Ok {
+                    amount: 40,
+                    payout: 40
+                }
+            );
+
+            advance_time(days(1) + 1);
+            assert_eq!(
+                contract.can_redeem(accounts.bob, FIRST_LOCK_ID, SECRET.to_vec(), None),
+                RedeemCheck::Rejected {
+                    reason: Error::Expired
+                }
+            );
+            set_caller(accounts.bob);
+            assert_eq!(
+                contract.redeem(FIRST_LOCK_ID, SECRET.to_vec(), None),
+                Err(Error::Expired)
+            );
+        }
+
+        #[ink::test]
+        fn test_finalize_expired_pays_bounty() {
+            let (mut contract, accounts) = harness()
+                .with_contract(deploy_with_finalize_bounty(
+                    days(7),
+                    FinalizeBounty::Fixed(10),
+                ))
+                .with_caller(accounts().bob)
+                .with_value(100)
+                .with_expiry_in(days(1))
+                .locked()
+                .build();
+            let locker_before = balance_of(accounts.bob);
+            let finalizer_before = balance_of(accounts.charlie);
+
+            // Not before the grace period after expiry has passed
+            set_caller(accounts.charlie);
+            advance_time(days(8) - 1);
+            assert_eq!(
+                contract.finalize_expired(FIRST_LOCK_ID),
+                Err(Error::NotExpired)
+            );
+
+            advance_time(1);
+            assert_eq!(contract.finalize_expired(FIRST_LOCK_ID), Ok(()));
+            assert_eq!(contract.get_lock_info(FIRST_LOCK_ID), None);
+            assert_eq!(balance_of(accounts.bob), locker_before + 90);
+            assert_eq!(balance_of(accounts.charlie), finalizer_before + 10);
+            assert_event::<ExpiredFinalized>(|e| {
+                e.locker == accounts.bob
+                    && e.amount == 90
+                    && e.finalizer == accounts.charlie
+                    && e.bounty == 10
+            });
+            assert_eq!(
+                contract.finalize_expired(FIRST_LOCK_ID),
+                Err(Error::LockNotFound)
+            );
+        }
+
+        #[ink::test]
+        fn test_finalize_expired_by_locker_pays_no_bounty() {
+            let (mut contract, accounts) = harness()
+                .with_contract(deploy_with_finalize_bounty(
+                    days(7),
+                    FinalizeBounty::Bps(500),
+                ))
+                .with_caller(accounts().bob)
+                .with_value(100)
+                .with_expiry_in(days(1))
+                .locked()
+                .locked()
+                .build();
+            let locker_before = balance_of(accounts.bob);
+            advance_time(days(8));
+
+            // A bounty in basis points is a share of the lock
+            set_caller(accounts.charlie);
+            contract.finalize_expired(FIRST_LOCK_ID).unwrap();
+            assert_event::<ExpiredFinalized>(|e| e.amount == 95 && e.bounty == 5);
+
+            set_caller(accounts.bob);
+            contract.finalize_expired(FIRST_LOCK_ID + 1).unwrap();
+            assert_event::<ExpiredFinalized>(|e| e.amount == 100 && e.bounty == 0);
+            assert_eq!(balance_of(accounts.bob), locker_before + 195);
+        }
+
+        #[ink::test]
+        fn test_finalize_expired_rejects_unfinalizable_locks() {
+            let (mut contract, accounts) = harness()
+                .with_contract(deploy_with_finalize_bounty(0, FinalizeBounty::Fixed(101)))
+                .with_caller(accounts().bob)
+                .with_value(100)
+                .with_expiry_in(days(1))
+                .locked()
+                .build();
+            contract
+                .lock(secret_hash(), None, None, None, None)
+                .unwrap();
+            advance_time(days(1));
+            set_caller(accounts.charlie);
+
+            // A lock without an expiry never expires
+            assert_eq!(
+                contract.finalize_expired(FIRST_LOCK_ID + 1),
+                Err(Error::NotExpired)
+            );
+            assert_eq!(contract.finalize_expired(99), Err(Error::LockNotFound));
+
+            // The bounty cannot eat into more than the lock holds
+            assert_eq!(
+                contract.finalize_expired(FIRST_LOCK_ID),
+                Err(Error::BountyExceedsLock)
+            );
+            assert!(contract.get_lock_info(FIRST_LOCK_ID).is_some());
+
+            // The locker pays no bounty, so can still finalize it
+            set_caller(accounts.bob);
+            assert_eq!(contract.finalize_expired(FIRST_LOCK_ID), Ok(()));
+        }
+
+        #[ink::test]
+        fn test_condition_and_proof_encoding() {
+            use scale::{Decode, Encode};
+
+            // Variant indices are part of the ABI, so pin them along with the layout
+            let account = AccountId::from([9; 32]);
+            let cases = [
+                (
+                    Condition::MessageHash([1; 32]),
+                    [&[0][..], &[1; 32]].concat(),
+                ),
+                (
+                    Condition::Beneficiary(account),
+                    [&[1][..], &[9; 32]].concat(),
+                ),
+                (
+                    Condition::Timelock(5),
+                    [&[2][..], &5u64.to_le_bytes()].concat(),
+                ),
+                (
+                    Condition::And(vec![Condition::Timelock(5)]),
+                    [&[3, 4, 2][..], &5u64.to_le_bytes()].concat(),
+                ),
+            ];
+            for (condition, bytes) in cases {
+                assert_eq!(condition.encode(), bytes);
+                assert_eq!(Condition::decode(&mut &bytes[..]), Ok(condition));
+            }
+
+            let proofs = [
+                (Proof::Preimage(b"ab".to_vec()), vec![0, 8, b'a', b'b']),
+                (Proof::Signature([3; 65]), [&[1][..], &[3; 65]].concat()),
+            ];
+            for (proof, bytes) in proofs {
+                assert_eq!(proof.encode(), bytes);
+                assert_eq!(Proof::decode(&mut &bytes[..]), Ok(proof));
+            }
+        }
+
+        #[ink::test]
+        fn test_lock_with_condition_matrix() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().bob)
+                .with_value(100)
+                .build();
+            let expiry = now() + days(1);
+
+            // Every combination of the three kinds of condition, flat and grouped
+            for mask in 0..8u8 {
+                for grouped in [false, true] {
+                    let mut conditions = Vec::new();
+                    if mask & 1 != 0 {
+                        conditions.push(Condition::MessageHash(secret_hash()));
+                    }
+                    if mask & 2 != 0 {
+                        conditions.push(Condition::Beneficiary(accounts.charlie));
+                    }
+                    if mask & 4 != 0 {
+                        conditions.push(Condition::Timelock(expiry));
+                    }
+                    let flat = conditions.clone();
+                    if grouped {
+                        conditions = vec![Condition::And(conditions)];
+                    }
+
+                    set_caller(accounts.bob);
+                    let receipt = contract.lock_with(conditions);
+                    if mask & 1 == 0 {
+                        assert_eq!(receipt, Err(Error::InvalidConditions));
+                        continue;
+                    }
+                    let lock_id = receipt.unwrap().lock_id;
+                    assert_eq!(contract.lock_conditions(lock_id), Some(flat));
+
+                    let (redeemer, outsider, refused) = if mask & 2 != 0 {
+                        (accounts.charlie, accounts.bob, Error::NotBeneficiary)
+                    } else {
+                        (accounts.bob, accounts.charlie, Error::NotLocker)
+                    };
+                    let proofs = vec![
+                        Proof::Preimage(b"wrong".to_vec()),
+                        Proof::Preimage(SECRET.to_vec()),
+                    ];
+                    set_caller(outsider);
+                    assert_eq!(
+                        contract.redeem_with(lock_id, proofs.clone(), None),
+                        Err(refused)
+                    );
+                    set_caller(redeemer);
+                    assert_eq!(
+                        contract.redeem_with(lock_id, proofs[..1].to_vec(), None),
+                        Err(Error::InvalidPreimage)
+                    );
+                    assert_eq!(contract.redeem_with(lock_id, proofs, None), Ok(()));
+                    assert_eq!(contract.get_lock_info(lock_id), None);
+                }
+            }
+        }
+
+        #[ink::test]
+        fn test_lock_with_bounds_conditions_and_proofs() {
+            let (mut contract, _) = harness()
+                .with_caller(accounts().bob)
+                .with_value(100)
+                .build();
+            let hash = Condition::MessageHash(secret_hash());
+
+            // Nine conditions, counting the nested ones, are too many, as is a third level
+            let mut conditions = vec![hash.clone()];
+            conditions.extend((0..7).map(|_| Condition::And(Vec::new())));
+            assert!(contract.lock_with(conditions.clone()).is_ok());
+            conditions.push(Condition::And(Vec::new()));
+            assert_eq!(
+                contract.lock_with(conditions),
+                Err(Error::TooManyConditions)
+            );
+            assert_eq!(
+                contract.lock_with(vec![
+                    hash.clone(),
+                    Condition::And(vec![Condition::And(vec![Condition::Timelock(now())])])
+                ]),
+                Err(Error::TooManyConditions)
+            );
+
+            // A lock holds one hash and at most one of each other condition
+            assert_eq!(
+                contract.lock_with(vec![hash.clone(), Condition::And(vec![hash.clone()])]),
+                Err(Error::InvalidConditions)
+            );
+            assert_eq!(
+                contract.lock_with(vec![
+                    hash.clone(),
+                    Condition::Timelock(now()),
+                    Condition::Timelock(now())
+                ]),
+                Err(Error::InvalidConditions)
+            );
+
+            // A timelock is the lock's expiry
+            let lock_id = contract
+                .lock_with(vec![Condition::And(vec![
+                    hash,
+                    Condition::Timelock(now() + days(1)),
+                ])])
+                .unwrap()
+                .lock_id;
+            let proofs = vec![Proof::Preimage(SECRET.to_vec()); 9];
+            assert_eq!(
+                contract.redeem_with(lock_id, proofs, None),
+                Err(Error::TooManyConditions)
+            );
+            assert_eq!(
+                contract.redeem_with(lock_id, Vec::new(), None),
+                Err(Error::InvalidPreimage)
+            );
+            advance_time(days(1));
+            assert_eq!(
+                contract.redeem_with(lock_id, vec![Proof::Preimage(SECRET.to_vec())], None),
+                Err(Error::Expired)
+            );
+            assert_eq!(contract.refund(lock_id), Ok(()));
+        }
+
+        #[ink::test]
+        fn test_sweep_excess_leaves_locked_principal() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().bob)
+                .with_value(100)
+                .locked()
+                .build();
+            set_caller(accounts.alice);
+            set_value(50);
+            contract.fund_rewards().unwrap();
+
+            // Value sent to the contract account outside of `lock` is excess
+            fund_contract(existential_deposit() + 150 + 30);
+            assert_eq!(contract.get_excess_balance(), 30);
+
+            set_caller(accounts.bob);
+            assert_eq!(contract.sweep_excess(accounts.django), Err(Error::NotOwner));
+
+            set_caller(accounts.alice);
+            let before = balance_of(accounts.django);
+            assert_eq!(contract.sweep_excess(accounts.django), Ok(30));
+            assert_eq!(balance_of(accounts.django), before + 30);
+            assert_event::<ExcessSwept>(|e| e.recovery == accounts.django && e.amount == 30);
+            assert_eq!(contract.get_excess_balance(), 0);
+            assert_eq!(contract.sweep_excess(accounts.django), Ok(0));
+            assert_eq!(balance_of(contract_account()), existential_deposit() + 150);
+
+            // The principal and the reward pool are still there
+            set_caller(accounts.bob);
+            assert!(contract
+                .redeem(FIRST_LOCK_ID, SECRET.to_vec(), None)
+                .is_ok());
+            assert_eq!(contract.rewards().0, 50);
+        }
+
+        #[ink::test]
+        fn test_back_to_back_identical_locks_have_distinct_ids_in_events() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().bob)
+                .with_value(100)
+                .build();
+
+            // The same account locking the same amount twice, each lock redeemed in turn
+            for _ in 0..2 {
+                let receipt = contract
+                    .lock(secret_hash(), None, None, None, None)
+                    .unwrap();
+                assert_eq!(
+                    contract.redeem(receipt.lock_id, SECRET.to_vec(), None),
+                    Ok(())
+                );
+            }
+
+            let locked: Vec<u64> = events::<Locked>().iter().map(|e| e.lock_id).collect();
+            let redeemed: Vec<u64> = events::<Redeemed>().iter().map(|e| e.lock_id).collect();
+            assert_eq!(locked, [FIRST_LOCK_ID, FIRST_LOCK_ID + 1]);
+            assert_eq!(redeemed, locked);
+            assert!(events::<Locked>()
+                .iter()
+                .all(|e| e.locker == accounts.bob && e.amount == Some(100)));
+        }
 
-    //----------------------------------
-    // Default Implementation
-    //----------------------------------
-    /// Provides default initialization values for the contract.
-    ///
-    /// When the contract is first deployed, it starts with no assets locked and no locker.
-    impl Default for LockUnlockSmartContract {
-        fn default() -> Self {
-            Self {
-                locker: None,
-                locked_amount: 0,
+        #[ink::test]
+        fn test_lock_with_redeemers() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().bob)
+                .with_value(100)
+                .build();
+            let members = vec![accounts.charlie, accounts.django, accounts.eve];
+            let lock_id = contract
+                .lock_with_redeemers(secret_hash(), members, None)
+                .unwrap();
+
+            // Outsiders are refused
+            set_caller(accounts.frank);
+            assert_eq!(
+                contract.redeem(lock_id, SECRET.to_vec(), Some(10)),
+                Err(Error::NotLocker)
+            );
+
+            // Each member, and the locker, can redeem and is paid
+            for redeemer in [
+                accounts.charlie,
+                accounts.django,
+                accounts.eve,
+                accounts.bob,
+            ] {
+                set_caller(redeemer);
+                let before = balance_of(redeemer);
+                assert_eq!(contract.redeem(lock_id, SECRET.to_vec(), Some(25)), Ok(()));
+                assert_eq!(balance_of(redeemer), before + 25);
+                assert_event::<Redeemed>(|e| {
+                    e.locker == accounts.bob && e.redeemer == redeemer && e.amount == 25
+                });
             }
+            assert_eq!(contract.get_lock_info(lock_id), None);
         }
-    }
 
-    impl LockUnlockSmartContract {
-        /// Constructor that initializes the contract with default values.
-        ///
-        /// This function is called when the contract is deployed.
-        #[ink(constructor)]
-        pub fn new() -> Self {
-            Self::default()
+        #[ink::test]
+        fn test_lock_with_no_redeemers_is_a_plain_lock() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().bob)
+                .with_value(100)
+                .build();
+            let expiry = Some(now() + days(1));
+            let lock_id = contract
+                .lock_with_redeemers(secret_hash(), Vec::new(), expiry)
+                .unwrap();
+            let plain = contract
+                .lock(secret_hash(), None, expiry, None, None)
+                .unwrap()
+                .lock_id;
+            assert_eq!(
+                contract.get_lock_info(lock_id),
+                contract.get_lock_info(plain)
+            );
+
+            set_caller(accounts.charlie);
+            assert_eq!(
+                contract.redeem(lock_id, SECRET.to_vec(), None),
+                Err(Error::NotLocker)
+            );
+            set_caller(accounts.bob);
+            assert_eq!(contract.redeem(lock_id, SECRET.to_vec(), None), Ok(()));
+
+            // The set is bounded
+            let crowd = vec![accounts.charlie; MAX_REDEEMERS as usize + 1];
+            assert_eq!(
+                contract.lock_with_redeemers(secret_hash(), crowd, None),
+                Err(Error::TooManyRedeemers)
+            );
         }
 
-        /// Locks the assets sent by the caller.
-        ///
-        /// **Requirements:**
-        /// - No assets must be currently locked.
-        /// - The caller must send a non-zero amount of assets.
-        ///
-        /// **Effects:**
-        /// - Sets the `locker` to the caller's account ID.
-        /// - Sets the `locked_amount` to the amount of assets sent.
-        /// - Emits a `Locked` event.
-        #[ink(message, payable)]
-        pub fn lock(&mut self) -> Result<(), Error> {
-            let caller = self.env().caller();
-            let transferred = self.env().transferred_value();
+        #[ink::test]
+        fn test_lock_with_memo() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().bob)
+                .with_value(100)
+                .build();
+            let memo = String::from("order #4711");
+            let lock_id = contract
+                .lock_with_memo(secret_hash(), None, None, memo.clone())
+                .unwrap();
+            assert_eq!(
+                contract.get_lock_info(lock_id).and_then(|lock| lock.memo),
+                Some(memo.clone())
+            );
+            assert_event::<Locked>(|e| e.memo_hash == Some(hash_of(memo.as_bytes())));
 
-            // Ensure no assets are already locked.
-            if self.locker.is_some() {
-                return Err(Error::AssetsAlreadyLocked);
+            assert_eq!(contract.redeem(lock_id, SECRET.to_vec(), None), Ok(()));
+            assert_event::<Redeemed>(|e| {
+                e.redeemer == accounts.bob && e.memo_hash == Some(hash_of(memo.as_bytes()))
+            });
+
+            // Locks without a memo carry none in their events
+            contract
+                .lock(secret_hash(), None, None, None, None)
+                .unwrap();
+            assert!(events::<Locked>().last().unwrap().memo_hash.is_none());
+        }
+
+        #[ink::test]
+        fn test_memo_length_is_counted_in_bytes() {
+            let (mut contract, _) = harness()
+                .with_caller(accounts().bob)
+                .with_value(100)
+                .build();
+            let lock = |contract: &mut LockUnlockSmartContract, memo: String| {
+                contract.lock_with_memo(secret_hash(), None, None, memo)
+            };
+
+            assert!(lock(&mut contract, "a".repeat(MAX_MEMO_LEN)).is_ok());
+            assert_eq!(
+                lock(&mut contract, "a".repeat(MAX_MEMO_LEN + 1)),
+                Err(Error::MemoTooLong)
+            );
+
+            // Two bytes per character: 64 fit, 65 are 130 bytes
+            assert!(lock(&mut contract, "é".repeat(64)).is_ok());
+            assert_eq!(lock(&mut contract, "é".repeat(65)), Err(Error::MemoTooLong));
+        }
+
+        #[ink::test]
+        fn test_lock_with_split_divides_payout() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().bob)
+                .with_value(1001)
+                .build();
+            let recipients = [accounts.charlie, accounts.django, accounts.eve];
+            let lock_id = contract
+                .lock_with_split(
+                    secret_hash(),
+                    vec![
+                        (recipients[0], 3333),
+                        (recipients[1], 3333),
+                        (recipients[2], 3334),
+                    ],
+                )
+                .unwrap();
+            let before: Vec<Balance> = recipients.iter().map(|&to| balance_of(to)).collect();
+
+            // 1001 does not divide evenly: each share rounds down to 333 and the first
+            // recipient gets the remaining 2
+            assert_eq!(contract.redeem(lock_id, SECRET.to_vec(), None), Ok(()));
+            let received: Vec<Balance> = recipients
+                .iter()
+                .zip(before)
+                .map(|(&to, before)| balance_of(to) - before)
+                .collect();
+            assert_eq!(received, [335, 333, 333]);
+            assert_eq!(events::<Redeemed>().len(), 1);
+            let sent: Vec<(AccountId, Balance)> = events::<PayoutSent>()
+                .iter()
+                .map(|e| (e.to, e.amount))
+                .collect();
+            assert_eq!(
+                sent,
+                [
+                    (recipients[0], 335),
+                    (recipients[1], 333),
+                    (recipients[2], 333)
+                ]
+            );
+        }
+
+        #[ink::test]
+        fn test_lock_with_split_rejects_invalid_shares() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().bob)
+                .with_value(100)
+                .build();
+            let invalid = [
+                Vec::new(),
+                vec![(accounts.charlie, 5000), (accounts.django, 4999)],
+                vec![(accounts.charlie, 5000), (accounts.django, 5001)],
+                (0..9)
+                    .map(|i| (accounts.charlie, if i == 0 { 10_000 } else { 0 }))
+                    .collect(),
+            ];
+            for recipients in invalid {
+                assert_eq!(
+                    contract.lock_with_split(secret_hash(), recipients),
+                    Err(Error::InvalidSplit)
+                );
             }
 
-            // Ensure some assets are being sent to lock.
-            if transferred == 0 {
-                return Err(Error::NoAssetsSent);
+            // Only the locker redeems, paying the recipients rather than themselves
+            let lock_id = contract
+                .lock_with_split(secret_hash(), vec![(accounts.charlie, 10_000)])
+                .unwrap();
+            set_caller(accounts.charlie);
+            assert_eq!(
+                contract.redeem(lock_id, SECRET.to_vec(), None),
+                Err(Error::NotLocker)
+            );
+            set_caller(accounts.bob);
+            let before = balance_of(accounts.bob);
+            assert_eq!(contract.redeem(lock_id, SECRET.to_vec(), None), Ok(()));
+            assert_eq!(balance_of(accounts.bob), before);
+            assert_event::<PayoutSent>(|e| e.to == accounts.charlie && e.amount == 100);
+        }
+
+        #[ink::test]
+        fn test_beneficiary_rejects_lock() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().bob)
+                .with_value(100)
+                .build();
+            let lock_id = contract
+                .lock(
+                    hash_of(b"unknown to charlie"),
+                    Some(accounts.charlie),
+                    Some(now() + days(30)),
+                    None,
+                    None,
+                )
+                .unwrap()
+                .lock_id;
+
+            // Only the named beneficiary may reject, not even the locker
+            for outsider in [accounts.bob, accounts.django] {
+                set_caller(outsider);
+                assert_eq!(contract.reject(lock_id), Err(Error::NotBeneficiary));
             }
 
-            // Set the locker and locked amount.
-            self.locker = Some(caller);
-            self.locked_amount = transferred;
+            set_caller(accounts.charlie);
+            let before = balance_of(accounts.bob);
+            assert_eq!(contract.reject(lock_id), Ok(()));
+            assert_eq!(balance_of(accounts.bob), before + 100);
+            assert_eq!(contract.get_lock_info(lock_id), None);
+            assert_event::<Rejected>(|e| {
+                e.locker == accounts.bob && e.beneficiary == accounts.charlie && e.amount == 100
+            });
+            assert_eq!(contract.stats().total_refunded, 1);
 
-            // Emit the Locked event.
-            self.env().emit_event(Locked {
-                locker: caller,
-                amount: transferred,
+            // Another user can lock straight away
+            set_caller(accounts.django);
+            assert!(contract
+                .lock(secret_hash(), Some(accounts.charlie), None, None, None)
+                .is_ok());
+            assert_eq!(contract.reject(lock_id), Err(Error::LockNotFound));
+        }
+
+        #[ink::test]
+        fn test_reject_needs_a_beneficiary_and_cannot_be_blocked() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().bob)
+                .with_value(100)
+                .locked()
+                .build();
+            assert_eq!(contract.reject(FIRST_LOCK_ID), Err(Error::NoBeneficiary));
+
+            // A dispute raised by the locker does not hold it up
+            let lock_id = contract
+                .lock_with_arbiter(secret_hash(), accounts.charlie, accounts.django, None)
+                .unwrap();
+            set_value(0);
+            contract.raise_dispute(lock_id).unwrap();
+            set_caller(accounts.charlie);
+            assert_eq!(contract.reject(lock_id), Ok(()));
+        }
+
+        #[ink::test]
+        fn test_capabilities_follow_configuration() {
+            let (mut contract, _) = harness().build();
+            let compiled = if cfg!(feature = "assets") {
+                CAP_EXPIRY | CAP_PSP22 | CAP_ASSETS
+            } else {
+                CAP_EXPIRY | CAP_PSP22
+            };
+            assert_eq!(contract.capabilities(), compiled | CAP_LOCKING);
+
+            contract.set_fee(50).unwrap();
+            contract.set_reward_rate(10).unwrap();
+            contract.set_allowlist_enabled(true).unwrap();
+            contract.pause().unwrap();
+            assert_eq!(
+                contract.capabilities(),
+                compiled | CAP_FEES | CAP_REWARDS | CAP_ALLOWLIST
+            );
+
+            contract.set_fee(0).unwrap();
+            contract.unpause().unwrap();
+            assert_eq!(
+                contract.capabilities(),
+                compiled | CAP_REWARDS | CAP_ALLOWLIST | CAP_LOCKING
+            );
+        }
+
+        #[ink::test]
+        fn test_capabilities_of_canary_and_version() {
+            let contract = deploy_canary(None, Some(now() + days(1)));
+            assert_eq!(
+                contract.capabilities() & (CAP_CANARY | CAP_LOCKING),
+                CAP_CANARY | CAP_LOCKING
+            );
+            advance_time(days(1));
+            assert_eq!(contract.capabilities() & CAP_LOCKING, 0);
+
+            assert_eq!(contract.contract_version(), CONTRACT_SEMVER);
+            assert_eq!(u32::from(contract.contract_version().0), contract.version());
+        }
+
+        #[ink::test]
+        fn test_recover_after_inactivity() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().bob)
+                .with_value(100)
+                .build();
+            let lock_id = contract
+                .lock_with_recovery(secret_hash(), accounts.charlie, days(90))
+                .unwrap();
+
+            set_caller(accounts.django);
+            assert_eq!(contract.recover(lock_id), Err(Error::NotRecoveryAccount));
+            set_caller(accounts.charlie);
+            assert_eq!(
+                contract.recover(lock_id),
+                Err(Error::RecoveryDelayNotElapsed)
+            );
+
+            advance_time(days(90));
+            let before = balance_of(accounts.charlie);
+            assert_eq!(contract.recover(lock_id), Ok(()));
+            assert_eq!(balance_of(accounts.charlie), before + 100);
+            assert_eq!(contract.get_lock_info(lock_id), None);
+            assert_event::<Recovered>(|e| {
+                e.original_locker == accounts.bob
+                    && e.recovery_account == accounts.charlie
+                    && e.amount == 100
             });
 
-            Ok(())
+            // Plain locks have no recovery account
+            set_caller(accounts.bob);
+            contract
+                .lock(secret_hash(), None, None, None, None)
+                .unwrap();
+            assert_eq!(contract.recover(lock_id + 1), Err(Error::NoRecoveryAccount));
         }
 
-        /// Redeems the locked assets if the correct message is provided.
-        ///
-        /// **Requirements:**
-        /// - The caller must be the same account that locked the assets.
-        /// - The provided `message` must be "Hello, World!".
-        ///
-        /// **Effects:**
-        /// - Transfers the `locked_amount` back to the `locker`.
-        /// - Resets the `locker` to `None`.
-        /// - Resets the `locked_amount` to 0.
-        /// - Emits a `Redeemed` event.
-        #[ink(message)]
-        pub fn redeem(&mut self, message: String) -> Result<(), Error> {
-            let caller = self.env().caller();
+        #[ink::test]
+        fn test_redeem_just_before_recovery_window_restarts_it() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().bob)
+                .with_value(100)
+                .build();
+            let lock_id = contract
+                .lock_with_recovery(secret_hash(), accounts.charlie, days(90))
+                .unwrap();
 
-            // Ensure the caller is the locker.
-            if self.locker != Some(caller) {
-                return Err(Error::NotLocker);
+            // The locker redeems part of it one block before the window opens
+            advance_time(days(90) - 1);
+            assert_eq!(contract.redeem(lock_id, SECRET.to_vec(), Some(40)), Ok(()));
+            advance_time(1);
+            set_caller(accounts.charlie);
+            assert_eq!(
+                contract.recover(lock_id),
+                Err(Error::RecoveryDelayNotElapsed)
+            );
+
+            // Only a full delay after that redemption
+            advance_time(days(90) - 2);
+            assert_eq!(
+                contract.recover(lock_id),
+                Err(Error::RecoveryDelayNotElapsed)
+            );
+            advance_time(1);
+            assert_eq!(contract.recover(lock_id), Ok(()));
+            assert_event::<Recovered>(|e| e.amount == 60);
+
+            // A full redemption leaves nothing to recover
+            set_caller(accounts.bob);
+            let lock_id = contract
+                .lock_with_recovery(secret_hash(), accounts.charlie, days(90))
+                .unwrap();
+            advance_time(days(90) - 1);
+            assert_eq!(contract.redeem(lock_id, SECRET.to_vec(), None), Ok(()));
+            advance_time(1);
+            set_caller(accounts.charlie);
+            assert_eq!(contract.recover(lock_id), Err(Error::LockNotFound));
+        }
+
+        #[ink::test]
+        fn test_new_and_lock_locks_the_endowment() {
+            let accounts = accounts();
+            set_caller(accounts.bob);
+            set_value(existential_deposit() + 100);
+            float_contract();
+            let mut contract = LockUnlockSmartContract::new_and_lock(
+                CHAIN_ID,
+                GENESIS_MARKER,
+                NATIVE_DECIMALS,
+                NATIVE_SYMBOL.to_vec(),
+                secret_hash(),
+            )
+            .unwrap();
+
+            let lock = contract.get_lock_info(FIRST_LOCK_ID).unwrap();
+            assert_eq!(lock.locker, accounts.bob);
+            assert_eq!(lock.amount, 100);
+            assert_eq!(contract.next_lock_id, FIRST_LOCK_ID + 1);
+            assert_eq!(contract.owner(), Some(accounts.bob));
+            assert_event::<Locked>(|e| {
+                e.lock_id == FIRST_LOCK_ID && e.locker == accounts.bob && e.amount == Some(100)
+            });
+
+            // The first lock redeems like any other
+            set_value(0);
+            let before = balance_of(accounts.bob);
+            assert_eq!(
+                contract.redeem(FIRST_LOCK_ID, SECRET.to_vec(), None),
+                Ok(())
+            );
+            assert_eq!(balance_of(accounts.bob), before + 100);
+        }
+
+        #[ink::test]
+        fn test_new_and_lock_rejects_an_endowment_without_surplus() {
+            set_value(existential_deposit());
+            let result = LockUnlockSmartContract::new_and_lock(
+                CHAIN_ID,
+                GENESIS_MARKER,
+                NATIVE_DECIMALS,
+                NATIVE_SYMBOL.to_vec(),
+                secret_hash(),
+            );
+            assert_eq!(result.err(), Some(Error::NoAssetsSent));
+        }
+
+        #[ink::test]
+        fn test_reconcile_sends_surplus_to_the_configured_recipient() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().bob)
+                .with_value(100)
+                .locked()
+                .build();
+            assert_eq!(contract.surplus_recipient(), Some(accounts.alice));
+
+            // A plain transfer to the contract account leaves a surplus
+            fund_contract(existential_deposit() + 100 + 40);
+
+            set_caller(accounts.bob);
+            assert_eq!(
+                contract.set_surplus_recipient(accounts.bob),
+                Err(Error::NotOwner)
+            );
+            assert_eq!(contract.reconcile(), Err(Error::NotOwner));
+
+            set_caller(accounts.alice);
+            contract.set_surplus_recipient(accounts.django).unwrap();
+            assert_eq!(contract.surplus_recipient(), Some(accounts.django));
+            let before = balance_of(accounts.django);
+            assert_eq!(contract.reconcile(), Ok(40));
+            assert_eq!(balance_of(accounts.django), before + 40);
+            assert_event::<ExcessSwept>(|e| e.recovery == accounts.django && e.amount == 40);
+            assert_eq!(contract.reconcile(), Ok(0));
+
+            // The locked principal is untouched
+            assert_eq!(balance_of(contract_account()), existential_deposit() + 100);
+            set_caller(accounts.bob);
+            let before = balance_of(accounts.bob);
+            assert_eq!(
+                contract.redeem(FIRST_LOCK_ID, SECRET.to_vec(), None),
+                Ok(())
+            );
+            assert_eq!(balance_of(accounts.bob), before + 100);
+        }
+
+        #[ink::test]
+        fn test_open_lock_index_follows_settlements() {
+            let (mut contract, _) = harness().with_caller(accounts().bob).build();
+            for value in [100, 200, 300] {
+                set_value(value);
+                contract
+                    .lock(secret_hash(), None, None, None, None)
+                    .unwrap();
             }
+            set_value(0);
+            let excess = contract.get_excess_balance();
+
+            // Settling the first lock moves the last one into its place
+            contract
+                .redeem(FIRST_LOCK_ID, SECRET.to_vec(), None)
+                .unwrap();
+            let mut open: Vec<u64> = contract
+                .active_lock_list()
+                .into_iter()
+                .map(|(lock_id, _)| lock_id)
+                .collect();
+            open.sort_unstable();
+            assert_eq!(open, vec![FIRST_LOCK_ID + 1, FIRST_LOCK_ID + 2]);
+            assert_eq!(contract.native_locked, 500);
+            assert_eq!(contract.get_excess_balance(), excess);
+
+            // A partial redemption only lowers the total
+            contract
+                .redeem(FIRST_LOCK_ID + 2, SECRET.to_vec(), Some(120))
+                .unwrap();
+            assert_eq!(contract.native_locked, 380);
+            assert_eq!(contract.active_lock_list().len(), 2);
+            assert_eq!(contract.get_excess_balance(), excess);
+            contract
+                .redeem(FIRST_LOCK_ID + 1, SECRET.to_vec(), None)
+                .unwrap();
+            contract
+                .redeem(FIRST_LOCK_ID + 2, SECRET.to_vec(), None)
+                .unwrap();
+            assert!(contract.active_lock_list().is_empty());
+            assert_eq!(contract.native_locked, 0);
+        }
+
+        #[ink::test]
+        fn test_withdraw_rewards_counts_partial_days() {
+            let (mut contract, accounts) = harness().with_value(1_000).build();
+            contract.fund_rewards().unwrap();
+            contract.set_reward_rate(10).unwrap();
+            set_caller(accounts.bob);
+            set_value(100_000);
+            contract
+                .lock(secret_hash(), None, None, None, None)
+                .unwrap();
+
+            // A day and a half in, the bound keeps 150 even though 100 has accrued
+            advance_time(days(1) + days(1) / 2);
+            set_caller(accounts.alice);
+            assert_eq!(contract.withdraw_rewards(851), Err(Error::RewardsCommitted));
+            assert!(contract.withdraw_rewards(850).is_ok());
+            assert_eq!(contract.rewards().0, 150);
+        }
+
+        #[ink::test]
+        fn test_psp22_metadata_is_read_once() {
+            let (mut contract, accounts) = harness().build();
+            let metadata = TokenMetadata {
+                decimals: 6,
+                symbol: b"USDT".to_vec(),
+            };
+            assert_eq!(contract.token_metadata_of(accounts.django), None);
+
+            // A cached token is never called again; off-chain, a call would panic
+            contract
+                .token_metadata_cache
+                .insert(accounts.django, &metadata);
+            assert_eq!(contract.psp22_metadata(accounts.django), metadata);
+            assert_eq!(contract.token_metadata_of(accounts.django), Some(metadata));
+            assert_eq!(contract.token_metadata_of(accounts.eve), None);
+        }
+
+        #[ink::test]
+        fn test_lock_info_keeps_token_metadata() {
+            let (mut contract, accounts) = harness().build();
+            let metadata = TokenMetadata {
+                decimals: 6,
+                symbol: b"USDT".to_vec(),
+            };
+            contract.store_lock(
+                FIRST_LOCK_ID,
+                &LockInfo {
+                    token: Some(accounts.django),
+                    token_metadata: Some(metadata.clone()),
+                    ..contract.native_lock(100, secret_hash(), None)
+                },
+            );
+
+            let lock = contract.get_lock_info(FIRST_LOCK_ID).unwrap();
+            assert_eq!(lock.token_metadata, Some(metadata));
+        }
 
-            // Verify the message matches the expected value.
-            if message != "Hello, World!" {
-                return Err(Error::IncorrectMessage);
+        #[ink::test]
+        fn test_stats_hide_shielded_amount_until_settled() {
+            let (mut contract, _) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .build();
+            let lock_id = contract
+                .lock_shielded(secret_hash(), None, None, None, [9; 32])
+                .unwrap();
+
+            // The lock is counted, but its amount only once the redemption reveals it
+            assert_eq!(contract.stats().total_locks, 1);
+            assert_eq!(contract.stats().cumulative_volume, 0);
+            contract.redeem(lock_id, SECRET.to_vec(), None).unwrap();
+            assert_eq!(contract.stats().cumulative_volume, 100);
+        }
+
+        #[ink::test]
+        fn test_can_redeem_hides_shielded_amount() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .build();
+            let lock_id = contract
+                .lock_shielded(secret_hash(), None, None, None, [9; 32])
+                .unwrap();
+
+            assert_eq!(
+                contract.can_redeem(accounts.alice, lock_id, SECRET.to_vec(), None),
+                RedeemCheck::Ok {
+                    amount: 0,
+                    payout: 0
+                }
+            );
+            // Partial amounts are refused by both paths, since settling one would publish
+            // what is left
+            for amount in [1, 99, 101] {
+                assert_eq!(
+                    contract.can_redeem(accounts.alice, lock_id, SECRET.to_vec(), Some(amount)),
+                    RedeemCheck::Rejected {
+                        reason: Error::Shielded
+                    }
+                );
+                assert_eq!(
+                    contract.redeem(lock_id, SECRET.to_vec(), Some(amount)),
+                    Err(Error::Shielded)
+                );
             }
+            assert_eq!(contract.locks.get(lock_id).unwrap().amount, 100);
+            assert_no_event::<Redeemed>();
 
-            // Transfer the locked assets back to the caller.
-            let amount = self.locked_amount;
-            self.env()
-                .transfer(caller, amount)
-                .map_err(|_| Error::TransferFailed)?;
+            // Entitlement is checked first, as in `redeem`
+            assert_eq!(
+                contract.can_redeem(accounts.bob, lock_id, SECRET.to_vec(), Some(1)),
+                RedeemCheck::Rejected {
+                    reason: Error::NotLocker
+                }
+            );
+            assert_eq!(
+                contract.can_redeem(accounts.alice, lock_id, b"wrong".to_vec(), Some(1)),
+                RedeemCheck::Rejected {
+                    reason: Error::InvalidPreimage
+                }
+            );
+        }
 
-            // Reset the contract state.
-            self.locker = None;
-            self.locked_amount = 0;
+        #[ink::test]
+        fn test_scheduled_claim_pays_keeper_the_tip() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .build();
+            contract
+                .lock(
+                    secret_hash(),
+                    Some(accounts.bob),
+                    Some(now() + days(7)),
+                    None,
+                    None,
+                )
+                .unwrap();
 
-            // Emit the Redeemed event.
-            self.env().emit_event(Redeemed {
-                locker: caller,
-                amount,
+            // Only the beneficiary schedules, with a valid preimage and an affordable tip
+            assert_eq!(
+                contract.schedule_claim(FIRST_LOCK_ID, SECRET.to_vec(), now() + days(1), 5),
+                Err(Error::NotBeneficiary)
+            );
+            set_caller(accounts.bob);
+            assert_eq!(
+                contract.schedule_claim(FIRST_LOCK_ID, b"wrong".to_vec(), now() + days(1), 5),
+                Err(Error::InvalidPreimage)
+            );
+            assert_eq!(
+                contract.schedule_claim(FIRST_LOCK_ID, SECRET.to_vec(), now() + days(1), 101),
+                Err(Error::TipExceedsPayout)
+            );
+            assert_eq!(
+                contract.schedule_claim(FIRST_LOCK_ID, SECRET.to_vec(), now() + days(7), 5),
+                Err(Error::Expired)
+            );
+            let execute_after = now() + days(1);
+            assert_eq!(
+                contract.schedule_claim(FIRST_LOCK_ID, SECRET.to_vec(), execute_after, 5),
+                Ok(())
+            );
+            assert_eq!(
+                contract.scheduled_claim(FIRST_LOCK_ID),
+                Some((execute_after, 5))
+            );
+            assert_event::<ClaimScheduled>(|e| {
+                e.lock_id == FIRST_LOCK_ID
+                    && e.beneficiary == accounts.bob
+                    && e.execute_after == execute_after
+                    && e.tip == 5
             });
 
-            Ok(())
+            // Not executable before its time
+            set_caller(accounts.charlie);
+            advance_time(days(1) - 1);
+            assert_eq!(
+                contract.execute_scheduled_claim(FIRST_LOCK_ID),
+                Err(Error::ClaimNotDue)
+            );
+            assert!(contract.get_lock_info(FIRST_LOCK_ID).is_some());
+
+            advance_time(1);
+            let beneficiary_before = balance_of(accounts.bob);
+            let keeper_before = balance_of(accounts.charlie);
+            assert_eq!(contract.execute_scheduled_claim(FIRST_LOCK_ID), Ok(()));
+            assert_eq!(contract.get_lock_info(FIRST_LOCK_ID), None);
+            assert_eq!(balance_of(accounts.bob), beneficiary_before + 95);
+            assert_eq!(balance_of(accounts.charlie), keeper_before + 5);
+            assert_event::<Redeemed>(|e| e.lock_id == FIRST_LOCK_ID);
+            assert_event::<ScheduledClaimExecuted>(|e| {
+                e.lock_id == FIRST_LOCK_ID && e.keeper == accounts.charlie && e.tip == 5
+            });
+            assert_eq!(contract.scheduled_claim(FIRST_LOCK_ID), None);
+            assert_eq!(
+                contract.execute_scheduled_claim(FIRST_LOCK_ID),
+                Err(Error::NoScheduledClaim)
+            );
         }
-    }
 
-    //----------------------------------
-    // Tests
-    //----------------------------------
+        #[ink::test]
+        fn test_cancelled_claim_cannot_be_executed() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .build();
+            contract
+                .lock(secret_hash(), Some(accounts.bob), None, None, None)
+                .unwrap();
+            set_caller(accounts.bob);
+            contract
+                .schedule_claim(FIRST_LOCK_ID, SECRET.to_vec(), now(), 5)
+                .unwrap();
 
-    #[cfg(test)]
-    mod tests {
-        use super::*;
-        use ink::env::test;
+            set_caller(accounts.charlie);
+            assert_eq!(
+                contract.cancel_scheduled_claim(FIRST_LOCK_ID),
+                Err(Error::NotBeneficiary)
+            );
+            set_caller(accounts.bob);
+            assert_eq!(contract.cancel_scheduled_claim(FIRST_LOCK_ID), Ok(()));
+            assert_event::<ScheduledClaimCancelled>(|e| {
+                e.lock_id == FIRST_LOCK_ID && e.beneficiary == accounts.bob
+            });
+            assert_eq!(
+                contract.cancel_scheduled_claim(FIRST_LOCK_ID),
+                Err(Error::NoScheduledClaim)
+            );
+
+            set_caller(accounts.charlie);
+            assert_eq!(
+                contract.execute_scheduled_claim(FIRST_LOCK_ID),
+                Err(Error::NoScheduledClaim)
+            );
+            assert_eq!(contract.get_lock_info(FIRST_LOCK_ID).unwrap().amount, 100);
+        }
 
         #[ink::test]
-        fn test_default_works() {
-            let contract = LockUnlockSmartContract::default();
-            assert_eq!(contract.locker, None);
-            assert_eq!(contract.locked_amount, 0);
+        fn test_scheduled_claim_cleared_when_lock_is_redeemed() {
+            let (mut contract, accounts) = harness()
+                .with_caller(accounts().alice)
+                .with_value(100)
+                .build();
+            contract
+                .lock(secret_hash(), Some(accounts.bob), None, None, None)
+                .unwrap();
+            set_caller(accounts.bob);
+            contract
+                .schedule_claim(FIRST_LOCK_ID, SECRET.to_vec(), now(), 5)
+                .unwrap();
+            contract
+                .redeem(FIRST_LOCK_ID, SECRET.to_vec(), None)
+                .unwrap();
+
+            assert_eq!(contract.scheduled_claim(FIRST_LOCK_ID), None);
+            set_caller(accounts.charlie);
+            assert_eq!(
+                contract.execute_scheduled_claim(FIRST_LOCK_ID),
+                Err(Error::NoScheduledClaim)
+            );
         }
 
         #[ink::test]
-        fn test_new_works() {
-            let contract = LockUnlockSmartContract::new();
-            assert_eq!(contract.locker, None);
-            assert_eq!(contract.locked_amount, 0);
+        fn test_lock_commitment_matches_offchain_encoding() {
+            let (mut contract, accounts) = harness().with_value(100).build();
+            set_time(1_000);
+            contract
+                .lock(secret_hash(), Some(accounts.bob), Some(days(1)), None, None)
+                .unwrap();
+
+            let fields = (
+                accounts.alice,
+                Some(accounts.bob),
+                100 as Balance,
+                secret_hash(),
+                None::<[u8; 33]>,
+                None::<AccountId>,
+                None::<u32>,
+                None::<[u8; 32]>,
+                1_000 as Timestamp,
+                0u16,
+            );
+            let expected = hash_of(&scale::Encode::encode(&fields));
+            assert_eq!(contract.get_lock_commitment(FIRST_LOCK_ID), Some(expected));
+            assert_event::<Locked>(|e| e.lock_id == FIRST_LOCK_ID && e.commitment == expected);
+            assert_eq!(contract.get_lock_commitment(FIRST_LOCK_ID + 1), None);
         }
 
         #[ink::test]
-        fn test_lock_works() {
-            let mut contract = LockUnlockSmartContract::new();
-            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+        fn test_lock_commitment_ignores_later_changes() {
+            let (mut contract, accounts) = harness().with_value(100).build();
+            contract
+                .lock(
+                    secret_hash(),
+                    Some(accounts.bob),
+                    Some(now() + days(1)),
+                    None,
+                    None,
+                )
+                .unwrap();
+            let commitment = contract.get_lock_commitment(FIRST_LOCK_ID).unwrap();
 
-            // Set the caller to Alice
-            test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
-            // Set the transferred value to 100
-            test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            // An accepted extension
+            contract
+                .propose_extension(FIRST_LOCK_ID, now() + days(2))
+                .unwrap();
+            set_caller(accounts.bob);
+            contract.accept_extension(FIRST_LOCK_ID).unwrap();
+            assert_eq!(
+                contract.get_lock_commitment(FIRST_LOCK_ID),
+                Some(commitment)
+            );
 
-            assert!(contract.lock().is_ok());
+            // A fee change and a partial redemption
+            set_caller(accounts.alice);
+            contract.set_fee(100).unwrap();
+            set_caller(accounts.bob);
+            contract
+                .redeem(FIRST_LOCK_ID, SECRET.to_vec(), Some(40))
+                .unwrap();
+            assert_eq!(
+                contract.get_lock_commitment(FIRST_LOCK_ID),
+                Some(commitment)
+            );
 
-            assert_eq!(contract.locker, Some(accounts.alice));
-            assert_eq!(contract.locked_amount, 100);
+            // Cleared once the lock is settled
+            contract
+                .redeem(FIRST_LOCK_ID, SECRET.to_vec(), None)
+                .unwrap();
+            assert_eq!(contract.get_lock_commitment(FIRST_LOCK_ID), None);
         }
 
         #[ink::test]
-        fn test_lock_fails_when_already_locked() {
-            let mut contract = LockUnlockSmartContract::new();
-            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+        fn test_lock_commitment_covers_each_immutable_field() {
+            let (mut contract, accounts) = harness().with_value(100).build();
+            let commitment_of = |contract: &mut LockUnlockSmartContract,
+                                 hash: [u8; 32],
+                                 beneficiary: Option<AccountId>| {
+                let receipt = contract.lock(hash, beneficiary, None, None, None).unwrap();
+                contract.get_lock_commitment(receipt.lock_id).unwrap()
+            };
+            let base = commitment_of(&mut contract, secret_hash(), Some(accounts.bob));
+            let mut seen = vec![base];
+            let mut assert_new = |commitment: [u8; 32]| {
+                assert!(!seen.contains(&commitment));
+                seen.push(commitment);
+            };
+
+            // Locker
+            set_caller(accounts.charlie);
+            assert_new(commitment_of(
+                &mut contract,
+                secret_hash(),
+                Some(accounts.bob),
+            ));
+            set_caller(accounts.alice);
+            // Beneficiary
+            assert_new(commitment_of(
+                &mut contract,
+                secret_hash(),
+                Some(accounts.django),
+            ));
+            // Condition
+            assert_new(commitment_of(
+                &mut contract,
+                hash_of(b"other"),
+                Some(accounts.bob),
+            ));
+            // Amount
+            set_value(99);
+            assert_new(commitment_of(
+                &mut contract,
+                secret_hash(),
+                Some(accounts.bob),
+            ));
+            set_value(100);
+            // Creation time
+            advance_time(1);
+            assert_new(commitment_of(
+                &mut contract,
+                secret_hash(),
+                Some(accounts.bob),
+            ));
+            // Fee snapshot
+            contract.set_fee(100).unwrap();
+            assert_new(commitment_of(
+                &mut contract,
+                secret_hash(),
+                Some(accounts.bob),
+            ));
+
+            // The same fields again give the same commitment
+            assert_eq!(
+                commitment_of(&mut contract, secret_hash(), Some(accounts.bob)),
+                *seen.last().unwrap()
+            );
+        }
 
-            // Set the caller to Alice
-            test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
-            // Set the transferred value to 100
-            test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+        #[ink::test]
+        fn test_dispute_bond_returned_to_winning_disputer() {
+            let (mut contract, accounts) = harness().build();
+            contract.set_dispute_bond(500, 10).unwrap();
+            set_caller(accounts.bob);
+            set_value(1_000);
+            let lock_id = contract
+                .lock_with_arbiter(secret_hash(), accounts.charlie, accounts.django, None)
+                .unwrap();
 
-            assert!(contract.lock().is_ok());
+            // The bond must be exactly 5% of the amount
+            set_caller(accounts.charlie);
+            for wrong in [0, 49, 51] {
+                set_value(wrong);
+                assert_eq!(contract.raise_dispute(lock_id), Err(Error::InvalidBond));
+            }
+            set_value(50);
+            assert_eq!(contract.raise_dispute(lock_id), Ok(()));
+            let arbitration = contract
+                .get_lock_info(lock_id)
+                .unwrap()
+                .arbitration
+                .unwrap();
+            assert_eq!(arbitration.disputed_by, Some(accounts.charlie));
+            assert_eq!(arbitration.bond, 50);
+            assert_eq!(contract.native_locked, 1_050);
 
-            // Try to lock again
-            let result = contract.lock();
-            assert_eq!(result, Err(Error::AssetsAlreadyLocked));
+            let beneficiary_before = balance_of(accounts.charlie);
+            set_caller(accounts.django);
+            assert_eq!(contract.resolve(lock_id, true), Ok(()));
+            assert_eq!(balance_of(accounts.charlie), beneficiary_before + 1_050);
+            assert_eq!(contract.native_locked, 0);
+            assert_event::<Resolved>(|e| {
+                e.lock_id == lock_id
+                    && e.winner == accounts.charlie
+                    && e.amount == 1_000
+                    && e.bond == 50
+                    && e.bond_returned
+            });
         }
 
         #[ink::test]
-        fn test_lock_fails_when_no_assets_sent() {
-            let mut contract = LockUnlockSmartContract::new();
-            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+        fn test_dispute_bond_forfeited_to_counterparty() {
+            let (mut contract, accounts) = harness().build();
+            contract.set_dispute_bond(100, 30).unwrap();
+            set_caller(accounts.bob);
+            set_value(1_000);
+            let lock_id = contract
+                .lock_with_arbiter(secret_hash(), accounts.charlie, accounts.django, None)
+                .unwrap();
 
-            // Set the caller to Alice
-            test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
-            // Set the transferred value to 0
-            test::set_value_transferred::<ink::env::DefaultEnvironment>(0);
+            // 1% of the amount is below the floor
+            set_caller(accounts.charlie);
+            set_value(10);
+            assert_eq!(contract.raise_dispute(lock_id), Err(Error::InvalidBond));
+            set_value(30);
+            contract.raise_dispute(lock_id).unwrap();
 
-            let result = contract.lock();
-            assert_eq!(result, Err(Error::NoAssetsSent));
+            let beneficiary_before = balance_of(accounts.charlie);
+            let locker_before = balance_of(accounts.bob);
+            set_caller(accounts.django);
+            assert_eq!(contract.resolve(lock_id, false), Ok(()));
+            assert_eq!(balance_of(accounts.bob), locker_before + 1_030);
+            assert_eq!(balance_of(accounts.charlie), beneficiary_before);
+            assert_event::<Resolved>(|e| {
+                e.lock_id == lock_id && e.winner == accounts.bob && e.bond == 30 && !e.bond_returned
+            });
+
+            // A beneficiary rejecting a lock the locker disputed hands the bond back too
+            set_caller(accounts.bob);
+            set_value(1_000);
+            let lock_id = contract
+                .lock_with_arbiter(secret_hash(), accounts.charlie, accounts.django, None)
+                .unwrap();
+            set_value(30);
+            contract.raise_dispute(lock_id).unwrap();
+            let locker_before = balance_of(accounts.bob);
+            set_caller(accounts.charlie);
+            assert_eq!(contract.reject(lock_id), Ok(()));
+            assert_eq!(balance_of(accounts.bob), locker_before + 1_030);
+            assert_eq!(contract.native_locked, 0);
         }
 
         #[ink::test]
-        fn test_redeem_works() {
-            let mut contract = LockUnlockSmartContract::new();
-            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+        fn test_set_dispute_bond_is_owner_only_and_bounded() {
+            let (mut contract, accounts) = harness().build();
+            assert_eq!(contract.dispute_bond(), (0, 0));
+            assert_eq!(
+                contract.set_dispute_bond(10_001, 0),
+                Err(Error::InvalidBond)
+            );
+            assert_eq!(contract.set_dispute_bond(10_000, 5), Ok(()));
+            assert_eq!(contract.dispute_bond(), (10_000, 5));
+            set_caller(accounts.bob);
+            assert_eq!(contract.set_dispute_bond(0, 0), Err(Error::NotOwner));
+        }
+
+        #[ink::test]
+        fn test_preview_settlement_matches_redeem() {
+            for (fee_bps, rate, wait) in
+                [(0, 0, 0), (250, 0, 0), (0, 10, days(3)), (250, 10, days(3))]
+            {
+                let (mut contract, accounts) = harness().with_value(1_000).build();
+                contract.fund_rewards().unwrap();
+                contract.set_fee(fee_bps).unwrap();
+                contract.set_reward_rate(rate).unwrap();
+                contract.set_fee_collector(accounts.eve).unwrap();
+                set_caller(accounts.bob);
+                set_value(10_000);
+                let lock_id = contract
+                    .lock(secret_hash(), None, Some(now() + days(7)), None, None)
+                    .unwrap()
+                    .lock_id;
+
+                // A preview for later agrees with one taken then
+                let later = now() + wait;
+                let preview = contract
+                    .preview_settlement(lock_id, accounts.bob, Some(later))
+                    .unwrap();
+                advance_time(wait);
+                assert_eq!(
+                    contract
+                        .preview_settlement(lock_id, accounts.bob, None)
+                        .as_ref(),
+                    Ok(&preview)
+                );
+
+                let redeemer_before = balance_of(accounts.bob);
+                let collector_before = balance_of(accounts.eve);
+                contract.redeem(lock_id, SECRET.to_vec(), None).unwrap();
+                assert_eq!(preview.amount, 10_000);
+                assert_eq!(preview.recipients, vec![(accounts.bob, preview.payout)]);
+                assert_eq!(balance_of(accounts.bob), redeemer_before + preview.payout);
+                assert_eq!(balance_of(accounts.eve), collector_before + preview.fee);
+                assert_event::<Redeemed>(|e| {
+                    e.lock_id == lock_id && e.reward == preview.reward && e.fee == preview.fee
+                });
+                assert_eq!(preview.fee, 10_000 * Balance::from(fee_bps) / 10_000);
+                assert_eq!(preview.reward, if rate > 0 { 30 } else { 0 });
+            }
+        }
+
+        #[ink::test]
+        fn test_preview_settlement_reports_routing_and_failures() {
+            let (mut contract, accounts) = harness().with_value(1_000).build();
+            let split_id = contract
+                .lock_with_split(
+                    secret_hash(),
+                    vec![(accounts.bob, 7_000), (accounts.charlie, 3_000)],
+                )
+                .unwrap();
+            let preview = contract
+                .preview_settlement(split_id, accounts.alice, None)
+                .unwrap();
+            assert_eq!(
+                preview.recipients,
+                vec![(accounts.bob, 700), (accounts.charlie, 300)]
+            );
 
-            // Set the caller to Alice
-            test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
-            // Set the transferred value to 100
-            test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            // The same failures as redeem, judged at the given time
+            set_value(100);
+            let lock_id = contract
+                .lock(
+                    secret_hash(),
+                    Some(accounts.bob),
+                    Some(now() + days(1)),
+                    None,
+                    None,
+                )
+                .unwrap()
+                .lock_id;
+            assert_eq!(
+                contract.preview_settlement(lock_id, accounts.charlie, None),
+                Err(Error::NotBeneficiary)
+            );
+            assert!(contract
+                .preview_settlement(lock_id, accounts.bob, None)
+                .is_ok());
+            assert_eq!(
+                contract.preview_settlement(lock_id, accounts.bob, Some(now() + days(1))),
+                Err(Error::Expired)
+            );
+            assert_eq!(
+                contract.preview_settlement(lock_id + 1, accounts.bob, None),
+                Err(Error::LockNotFound)
+            );
+            let shielded_id = contract
+                .lock_shielded(secret_hash(), None, None, None, [9; 32])
+                .unwrap();
+            assert_eq!(
+                contract.preview_settlement(shielded_id, accounts.alice, None),
+                Err(Error::Shielded)
+            );
+        }
 
-            assert!(contract.lock().is_ok());
+        #[ink::test]
+        fn test_redeem_percent_rounds_down_then_sweeps() {
+            let (mut contract, accounts) = harness().with_value(1_001).locked().build();
 
-            // Redeem the locked assets
-            assert!(contract.redeem(String::from("Hello, World!")).is_ok());
+            // 25% of 1001 is 250.25, rounded down
+            let before = balance_of(accounts.alice);
+            assert_eq!(
+                contract.redeem_percent(FIRST_LOCK_ID, SECRET.to_vec(), 2_500),
+                Ok(())
+            );
+            assert_eq!(balance_of(accounts.alice), before + 250);
+            assert_eq!(contract.get_locked_amount(FIRST_LOCK_ID), 751);
+            assert_event::<RedeemedPercent>(|e| {
+                e.lock_id == FIRST_LOCK_ID && e.bps == 2_500 && e.amount == 250 && !e.swept
+            });
 
-            assert_eq!(contract.locker, None);
-            assert_eq!(contract.locked_amount, 0);
+            // 100% takes whatever is left
+            assert_eq!(
+                contract.redeem_percent(FIRST_LOCK_ID, SECRET.to_vec(), 10_000),
+                Ok(())
+            );
+            assert_eq!(balance_of(accounts.alice), before + 1_001);
+            assert_eq!(contract.get_lock_info(FIRST_LOCK_ID), None);
+            assert_event::<RedeemedPercent>(|e| e.bps == 10_000 && e.amount == 751);
         }
 
         #[ink::test]
-        fn test_redeem_fails_when_not_locker() {
-            let mut contract = LockUnlockSmartContract::new();
-            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+        fn test_redeem_percent_closes_lock_left_with_dust() {
+            let (mut contract, accounts) = harness().build();
+            contract.set_limits(10, None).unwrap();
+            set_value(100);
+            contract
+                .lock(secret_hash(), None, None, None, None)
+                .unwrap();
 
-            // Set the caller to Alice
-            test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
-            // Set the transferred value to 100
-            test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            for bps in [0, 10_001] {
+                assert_eq!(
+                    contract.redeem_percent(FIRST_LOCK_ID, SECRET.to_vec(), bps),
+                    Err(Error::InvalidPercent)
+                );
+            }
 
-            assert!(contract.lock().is_ok());
+            // 95% would leave 5, below the minimum lock of 10
+            let before = balance_of(accounts.alice);
+            assert_eq!(
+                contract.redeem_percent(FIRST_LOCK_ID, SECRET.to_vec(), 9_500),
+                Ok(())
+            );
+            assert_eq!(balance_of(accounts.alice), before + 100);
+            assert_eq!(contract.get_lock_info(FIRST_LOCK_ID), None);
+            assert_event::<RedeemedPercent>(|e| e.bps == 9_500 && e.amount == 100 && e.swept);
 
-            // Set the caller to Bob
-            test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            // A shielded lock is still only redeemed whole
+            let shielded_id = contract
+                .lock_shielded(secret_hash(), None, None, None, [9; 32])
+                .unwrap();
+            assert_eq!(
+                contract.redeem_percent(shielded_id, SECRET.to_vec(), 5_000),
+                Err(Error::Shielded)
+            );
+            assert_eq!(
+                contract.redeem_percent(shielded_id, SECRET.to_vec(), 10_000),
+                Ok(())
+            );
+        }
 
-            // Try to redeem the locked assets
-            let result = contract.redeem(String::from("Hello, World!"));
-            assert_eq!(result, Err(Error::NotLocker));
+        #[ink::test]
+        fn test_batch_limits_are_enforced_at_their_bounds() {
+            let (mut contract, accounts) = harness().build();
+            let limits = contract.get_batch_limits();
+            assert_eq!(
+                limits,
+                BatchLimits {
+                    batch_locks: MAX_BATCH_LOCKS,
+                    batch_redeems: MAX_BATCH_REDEEMS,
+                    split_recipients: MAX_SPLIT_RECIPIENTS as u32,
+                    redeemers: MAX_REDEEMERS,
+                    conditions: MAX_CONDITIONS,
+                    records_per_query: MAX_RECORDS_PER_QUERY,
+                }
+            );
+
+            // lock_batch and redeem_batch take exactly their limit
+            let count = limits.batch_locks as usize;
+            set_value(count as Balance);
+            let ids = contract.lock_batch(vec![spec(1); count]).unwrap();
+            assert_eq!(ids.len(), count);
+            let ids: Vec<u64> = ids
+                .into_iter()
+                .take(limits.batch_redeems as usize)
+                .collect();
+            let preimages = vec![SECRET.to_vec(); ids.len()];
+            let results = contract.redeem_batch(ids, preimages).unwrap();
+            assert!(results.iter().all(Result::is_ok));
+
+            // lock_with_split and lock_with_redeemers take exactly theirs
+            set_value(100);
+            let members = |n: u32| (0..n).map(|i| AccountId::from([i as u8 + 1; 32]));
+            let even = 10_000 / limits.split_recipients as u16;
+            let mut recipients: Vec<_> = members(limits.split_recipients)
+                .map(|to| (to, even))
+                .collect();
+            recipients[0].1 += 10_000 - even * limits.split_recipients as u16;
+            assert!(contract
+                .lock_with_split(secret_hash(), recipients.clone())
+                .is_ok());
+            recipients.push((accounts.django, 0));
+            assert_eq!(
+                contract.lock_with_split(secret_hash(), recipients),
+                Err(Error::InvalidSplit)
+            );
+            let redeemers: Vec<_> = members(limits.redeemers).collect();
+            assert!(contract
+                .lock_with_redeemers(secret_hash(), redeemers, None)
+                .is_ok());
+
+            // redeem_with takes as many proofs as there are conditions
+            let lock_id = contract
+                .lock(secret_hash(), None, None, None, None)
+                .unwrap()
+                .lock_id;
+            let mut proofs = vec![Proof::Preimage(SECRET.to_vec()); limits.conditions as usize];
+            proofs.push(Proof::Preimage(SECRET.to_vec()));
+            assert_eq!(
+                contract.redeem_with(lock_id, proofs.clone(), None),
+                Err(Error::TooManyConditions)
+            );
+            proofs.pop();
+            assert_eq!(contract.redeem_with(lock_id, proofs, None), Ok(()));
         }
 
         #[ink::test]
-        fn test_redeem_fails_with_incorrect_message() {
-            let mut contract = LockUnlockSmartContract::new();
-            let accounts = test::default_accounts::<ink::env::DefaultEnvironment>();
+        fn test_error_encoding_keeps_discriminants() {
+            use scale::Encode;
+            assert_eq!(Error::TokenTransferFailed.encode(), vec![17]);
+            assert_eq!(
+                Error::BatchTooLarge { max: 50, got: 51 }.encode(),
+                (41u8, 50u32, 51u32).encode()
+            );
+        }
+    }
+
+    //----------------------------------
+    // End-to-End Tests
+    //----------------------------------
+
+    /// Runs against a `substrate-contracts-node` (set `CONTRACTS_NODE` to its path) with
+    /// `cargo test --features e2e-tests`, so real balances move.
+    #[cfg(all(test, feature = "e2e-tests"))]
+    mod e2e_tests {
+        use super::fixtures::{hash_of, secret_hash, CHAIN_ID, GENESIS_MARKER, SECRET};
+        use super::*;
+        use ink_e2e::{ChainBackend, ContractsBackend};
+        use psp22_mock::{Psp22Mock, Psp22MockRef, Reentry, PSP22 as _};
+
+        type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+        const LOCKED: Balance = 1_000_000_000;
+
+        #[ink_e2e::test]
+        async fn e2e_lock_and_redeem_moves_balances<Client: E2EBackend>(
+            mut client: Client,
+        ) -> E2EResult<()> {
+            let mut constructor =
+                LockUnlockSmartContractRef::new(CHAIN_ID, GENESIS_MARKER, 12, b"UNIT".to_vec());
+            let contract = client
+                .instantiate(
+                    "lock_unlock_smart_contract",
+                    &ink_e2e::alice(),
+                    &mut constructor,
+                )
+                .submit()
+                .await
+                .expect("instantiate failed");
+            let mut call_builder = contract.call_builder::<LockUnlockSmartContract>();
+            let contract = contract.account_id;
+            let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+            let contract_before = client.free_balance(contract).await?;
+            let alice_before = client.free_balance(alice).await?;
+
+            // Lock from Alice: the contract holds the value
+            let lock = call_builder.lock(secret_hash(), None, None, None, None);
+            let lock_id = client
+                .call(&ink_e2e::alice(), &lock)
+                .value(LOCKED)
+                .submit()
+                .await
+                .expect("lock failed")
+                .return_value()
+                .expect("lock rejected")
+                .lock_id;
+            assert_eq!(
+                client.free_balance(contract).await?,
+                contract_before + LOCKED
+            );
+            let alice_locked = client.free_balance(alice).await?;
+            assert!(alice_locked < alice_before - LOCKED);
+
+            // Redeem: Alice gets the value back, the contract is back to its deposit
+            let redeem = call_builder.redeem(lock_id, SECRET.to_vec(), None);
+            client
+                .call(&ink_e2e::alice(), &redeem)
+                .submit()
+                .await
+                .expect("redeem failed")
+                .return_value()
+                .expect("redeem rejected");
+            assert_eq!(client.free_balance(contract).await?, contract_before);
+            let alice_after = client.free_balance(alice).await?;
+            assert!(alice_after > alice_locked);
+            // Only gas was spent
+            assert!(alice_before - alice_after < LOCKED);
+
+            Ok(())
+        }
+
+        #[ink_e2e::test]
+        async fn e2e_redeem_by_other_account_fails<Client: E2EBackend>(
+            mut client: Client,
+        ) -> E2EResult<()> {
+            let mut constructor =
+                LockUnlockSmartContractRef::new(CHAIN_ID, GENESIS_MARKER, 12, b"UNIT".to_vec());
+            let contract = client
+                .instantiate(
+                    "lock_unlock_smart_contract",
+                    &ink_e2e::alice(),
+                    &mut constructor,
+                )
+                .submit()
+                .await
+                .expect("instantiate failed");
+            let mut call_builder = contract.call_builder::<LockUnlockSmartContract>();
+
+            let lock = call_builder.lock(hash_of(SECRET), None, None, None, None);
+            let lock_id = client
+                .call(&ink_e2e::alice(), &lock)
+                .value(LOCKED)
+                .submit()
+                .await
+                .expect("lock failed")
+                .return_value()
+                .expect("lock rejected")
+                .lock_id;
+
+            let redeem = call_builder.redeem(lock_id, SECRET.to_vec(), None);
+            let result = client.call(&ink_e2e::bob(), &redeem).dry_run().await?;
+            assert_eq!(result.return_value(), Err(Error::NotLocker));
+
+            // The lock is untouched
+            let amount = call_builder.get_locked_amount(lock_id);
+            let result = client.call(&ink_e2e::bob(), &amount).dry_run().await?;
+            assert_eq!(result.return_value(), LOCKED);
+
+            Ok(())
+        }
+
+        #[ink_e2e::test]
+        async fn e2e_upgrade_keeps_storage<Client: E2EBackend>(
+            mut client: Client,
+        ) -> E2EResult<()> {
+            let mut constructor =
+                LockUnlockSmartContractRef::new(CHAIN_ID, GENESIS_MARKER, 12, b"UNIT".to_vec());
+            let contract = client
+                .instantiate(
+                    "lock_unlock_smart_contract",
+                    &ink_e2e::alice(),
+                    &mut constructor,
+                )
+                .submit()
+                .await
+                .expect("instantiate failed");
+            let mut call_builder = contract.call_builder::<LockUnlockSmartContract>();
+
+            let lock = call_builder.lock(hash_of(SECRET), None, None, None, None);
+            let lock_id = client
+                .call(&ink_e2e::alice(), &lock)
+                .value(LOCKED)
+                .submit()
+                .await
+                .expect("lock failed")
+                .return_value()
+                .expect("lock rejected")
+                .lock_id;
+
+            // Code of the next release, which reports a newer version
+            let code_hash = client
+                .upload("lock_unlock_next", &ink_e2e::alice())
+                .submit()
+                .await
+                .expect("upload failed")
+                .code_hash;
+
+            // Only the owner upgrades
+            let upgrade = call_builder.upgrade(code_hash);
+            let result = client.call(&ink_e2e::bob(), &upgrade).dry_run().await?;
+            assert_eq!(result.return_value(), Err(Error::NotOwner));
+            client
+                .call(&ink_e2e::alice(), &upgrade)
+                .submit()
+                .await
+                .expect("upgrade failed")
+                .return_value()
+                .expect("upgrade rejected");
+
+            // Calls are dispatched to the new code and the lock survived
+            let version = call_builder.contract_version();
+            let result = client.call(&ink_e2e::alice(), &version).dry_run().await?;
+            assert_eq!(result.return_value(), (3, 0, 0));
+            let amount = call_builder.get_locked_amount(lock_id);
+            let result = client.call(&ink_e2e::alice(), &amount).dry_run().await?;
+            assert_eq!(result.return_value(), LOCKED);
+            let version = call_builder.storage_version();
+            let result = client.call(&ink_e2e::alice(), &version).dry_run().await?;
+            assert_eq!(result.return_value(), STORAGE_VERSION);
+
+            Ok(())
+        }
+
+        #[ink_e2e::test]
+        async fn e2e_failed_split_share_reverts_batch<Client: E2EBackend>(
+            mut client: Client,
+        ) -> E2EResult<()> {
+            let mut constructor =
+                LockUnlockSmartContractRef::new(CHAIN_ID, GENESIS_MARKER, 12, b"UNIT".to_vec());
+            let contract = client
+                .instantiate(
+                    "lock_unlock_smart_contract",
+                    &ink_e2e::alice(),
+                    &mut constructor,
+                )
+                .submit()
+                .await
+                .expect("instantiate failed");
+            let mut call_builder = contract.call_builder::<LockUnlockSmartContract>();
+            let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+
+            // The second share is a single unit for an account that does not exist yet,
+            // which is below the existential deposit, so its transfer fails after Bob's
+            // share has been sent
+            let fresh = AccountId::from([0x77; 32]);
+            let lock =
+                call_builder.lock_with_split(secret_hash(), vec![(bob, 5_000), (fresh, 5_000)]);
+            let lock_id = client
+                .call(&ink_e2e::alice(), &lock)
+                .value(2)
+                .submit()
+                .await
+                .expect("lock failed")
+                .return_value()
+                .expect("lock rejected");
+
+            let bob_before = client.free_balance(bob).await?;
+            let batch = call_builder.redeem_batch(vec![lock_id], vec![SECRET.to_vec()]);
+            let result = client.call(&ink_e2e::alice(), &batch).dry_run().await?;
+            assert_eq!(result.return_value(), Err(Error::TransferFailed));
+            let _ = client.call(&ink_e2e::alice(), &batch).submit().await;
 
-            // Set the caller to Alice
-            test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
-            // Set the transferred value to 100
-            test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            // Bob's share was reverted with the batch, and the lock is still whole
+            assert_eq!(client.free_balance(bob).await?, bob_before);
+            let amount = call_builder.get_locked_amount(lock_id);
+            let result = client.call(&ink_e2e::alice(), &amount).dry_run().await?;
+            assert_eq!(result.return_value(), 2);
 
-            assert!(contract.lock().is_ok());
+            Ok(())
+        }
+
+        #[ink_e2e::test]
+        async fn e2e_token_calling_back_cannot_redeem_twice<Client: E2EBackend>(
+            mut client: Client,
+        ) -> E2EResult<()> {
+            let mut constructor =
+                LockUnlockSmartContractRef::new(CHAIN_ID, GENESIS_MARKER, 12, b"UNIT".to_vec());
+            let contract = client
+                .instantiate(
+                    "lock_unlock_smart_contract",
+                    &ink_e2e::alice(),
+                    &mut constructor,
+                )
+                .submit()
+                .await
+                .expect("instantiate failed");
+            let mut call_builder = contract.call_builder::<LockUnlockSmartContract>();
+            let escrow = contract.account_id;
+
+            let mut constructor = Psp22MockRef::new(LOCKED);
+            let token = client
+                .instantiate("psp22_mock", &ink_e2e::alice(), &mut constructor)
+                .submit()
+                .await
+                .expect("token instantiate failed");
+            let mut token_calls = token.call_builder::<Psp22Mock>();
+            let token = token.account_id;
+
+            // Alice locks tokens for the token contract itself, which then redeems them
+            let approve = token_calls.approve(escrow, LOCKED);
+            client
+                .call(&ink_e2e::alice(), &approve)
+                .submit()
+                .await
+                .expect("approve failed");
+            let lock =
+                call_builder.lock_token(token, LOCKED, secret_hash(), Some(token), None, None);
+            let lock_id = client
+                .call(&ink_e2e::alice(), &lock)
+                .submit()
+                .await
+                .expect("lock failed")
+                .return_value()
+                .expect("lock rejected");
+
+            // Paying the token out calls its `transfer`, which tries to redeem the same lock
+            // again. The escrow made that call without allowing reentry, so the runtime
+            // refuses the nested redemption; the token traps and nothing is paid
+            let reentry = token_calls.set_reentry(Some(Reentry {
+                escrow,
+                lock_id,
+                preimage: SECRET.to_vec(),
+            }));
+            client
+                .call(&ink_e2e::alice(), &reentry)
+                .submit()
+                .await
+                .expect("set_reentry failed");
+            let redeem = token_calls.redeem_from(escrow, lock_id, SECRET.to_vec());
+            let result = client
+                .call(&ink_e2e::alice(), &redeem)
+                .submit()
+                .await
+                .expect("redeem failed")
+                .return_value();
+            assert_eq!(
+                result,
+                Err(scale::Encode::encode(&Error::TokenTransferFailed)[0])
+            );
+            let amount = call_builder.get_locked_amount(lock_id);
+            let result = client.call(&ink_e2e::alice(), &amount).dry_run().await?;
+            assert_eq!(result.return_value(), LOCKED);
+            let held = token_calls.balance_of(escrow);
+            let result = client.call(&ink_e2e::alice(), &held).dry_run().await?;
+            assert_eq!(result.return_value(), LOCKED);
+
+            // Without the callback the same redemption goes through, paying out once
+            let reentry = token_calls.set_reentry(None);
+            client
+                .call(&ink_e2e::alice(), &reentry)
+                .submit()
+                .await
+                .expect("set_reentry failed");
+            let result = client
+                .call(&ink_e2e::alice(), &redeem)
+                .submit()
+                .await
+                .expect("redeem failed")
+                .return_value();
+            assert_eq!(result, Ok(()));
+            let held = token_calls.balance_of(escrow);
+            let result = client.call(&ink_e2e::alice(), &held).dry_run().await?;
+            assert_eq!(result.return_value(), 0);
+            let paid = token_calls.balance_of(token);
+            let result = client.call(&ink_e2e::alice(), &paid).dry_run().await?;
+            assert_eq!(result.return_value(), LOCKED);
+
+            Ok(())
+        }
+
+        #[ink_e2e::test]
+        async fn e2e_lock_and_redeem_token_moves_balances<Client: E2EBackend>(
+            mut client: Client,
+        ) -> E2EResult<()> {
+            let mut constructor =
+                LockUnlockSmartContractRef::new(CHAIN_ID, GENESIS_MARKER, 12, b"UNIT".to_vec());
+            let contract = client
+                .instantiate(
+                    "lock_unlock_smart_contract",
+                    &ink_e2e::alice(),
+                    &mut constructor,
+                )
+                .submit()
+                .await
+                .expect("instantiate failed");
+            let mut call_builder = contract.call_builder::<LockUnlockSmartContract>();
+            let escrow = contract.account_id;
+
+            let mut constructor = Psp22MockRef::new(2 * LOCKED);
+            let token = client
+                .instantiate("psp22_mock", &ink_e2e::alice(), &mut constructor)
+                .submit()
+                .await
+                .expect("token instantiate failed");
+            let mut token_calls = token.call_builder::<Psp22Mock>();
+            let token = token.account_id;
+            let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+            let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+
+            // Without an allowance the token refuses `transfer_from` and nothing is locked
+            let lock = call_builder.lock_token(token, LOCKED, secret_hash(), Some(bob), None, None);
+            let result = client
+                .call(&ink_e2e::alice(), &lock)
+                .submit()
+                .await
+                .expect("lock failed")
+                .return_value();
+            assert_eq!(result, Err(Error::TokenTransferFailed));
+            let held = token_calls.balance_of(escrow);
+            let result = client.call(&ink_e2e::alice(), &held).dry_run().await?;
+            assert_eq!(result.return_value(), 0);
+            let owned = token_calls.balance_of(alice);
+            let result = client.call(&ink_e2e::alice(), &owned).dry_run().await?;
+            assert_eq!(result.return_value(), 2 * LOCKED);
+
+            // Once approved, the escrow pulls the tokens from Alice
+            let approve = token_calls.approve(escrow, LOCKED);
+            client
+                .call(&ink_e2e::alice(), &approve)
+                .submit()
+                .await
+                .expect("approve failed");
+            let lock_id = client
+                .call(&ink_e2e::alice(), &lock)
+                .submit()
+                .await
+                .expect("lock failed")
+                .return_value()
+                .expect("lock rejected");
+            let result = client.call(&ink_e2e::alice(), &held).dry_run().await?;
+            assert_eq!(result.return_value(), LOCKED);
+            let result = client.call(&ink_e2e::alice(), &owned).dry_run().await?;
+            assert_eq!(result.return_value(), LOCKED);
+            let allowance = token_calls.allowance(alice, escrow);
+            let result = client.call(&ink_e2e::alice(), &allowance).dry_run().await?;
+            assert_eq!(result.return_value(), 0);
+
+            // Bob redeems and receives the tokens, leaving the escrow empty
+            let redeem = call_builder.redeem(lock_id, SECRET.to_vec(), None);
+            client
+                .call(&ink_e2e::bob(), &redeem)
+                .submit()
+                .await
+                .expect("redeem failed")
+                .return_value()
+                .expect("redeem rejected");
+            let result = client.call(&ink_e2e::alice(), &held).dry_run().await?;
+            assert_eq!(result.return_value(), 0);
+            let received = token_calls.balance_of(bob);
+            let result = client.call(&ink_e2e::alice(), &received).dry_run().await?;
+            assert_eq!(result.return_value(), LOCKED);
+            let result = client.call(&ink_e2e::alice(), &owned).dry_run().await?;
+            assert_eq!(result.return_value(), LOCKED);
+            let amount = call_builder.get_locked_amount(lock_id);
+            let result = client.call(&ink_e2e::alice(), &amount).dry_run().await?;
+            assert_eq!(result.return_value(), 0);
+
+            Ok(())
+        }
+
+        #[ink_e2e::test]
+        async fn e2e_lock_weight_does_not_grow_with_storage<Client: E2EBackend>(
+            mut client: Client,
+        ) -> E2EResult<()> {
+            let mut constructor =
+                LockUnlockSmartContractRef::new(CHAIN_ID, GENESIS_MARKER, 12, b"UNIT".to_vec());
+            let contract = client
+                .instantiate(
+                    "lock_unlock_smart_contract",
+                    &ink_e2e::alice(),
+                    &mut constructor,
+                )
+                .submit()
+                .await
+                .expect("instantiate failed");
+            let mut call_builder = contract.call_builder::<LockUnlockSmartContract>();
+            let lock = call_builder.lock(secret_hash(), None, None, None, None);
+            let fresh = client
+                .call(&ink_e2e::alice(), &lock)
+                .value(LOCKED)
+                .dry_run()
+                .await?
+                .exec_result
+                .gas_required;
+
+            // Fill the history and the counters with settled locks
+            for _ in 0..10 {
+                let lock_id = client
+                    .call(&ink_e2e::alice(), &lock)
+                    .value(LOCKED)
+                    .submit()
+                    .await
+                    .expect("lock failed")
+                    .return_value()
+                    .expect("lock rejected")
+                    .lock_id;
+                let redeem = call_builder.redeem(lock_id, SECRET.to_vec(), None);
+                client
+                    .call(&ink_e2e::alice(), &redeem)
+                    .submit()
+                    .await
+                    .expect("redeem failed")
+                    .return_value()
+                    .expect("redeem rejected");
+            }
+
+            // `lock` only touches the root and its own cells, so its weight stays within a
+            // tenth of what it was on the empty contract
+            let grown = client
+                .call(&ink_e2e::alice(), &lock)
+                .value(LOCKED)
+                .dry_run()
+                .await?
+                .exec_result
+                .gas_required;
+            assert!(grown.ref_time() <= fresh.ref_time() + fresh.ref_time() / 10);
+            assert!(grown.proof_size() <= fresh.proof_size() + fresh.proof_size() / 10);
+
+            Ok(())
+        }
+
+        #[ink_e2e::test]
+        async fn e2e_token_locks_record_metadata<Client: E2EBackend>(
+            mut client: Client,
+        ) -> E2EResult<()> {
+            let mut constructor =
+                LockUnlockSmartContractRef::new(CHAIN_ID, GENESIS_MARKER, 12, b"UNIT".to_vec());
+            let contract = client
+                .instantiate(
+                    "lock_unlock_smart_contract",
+                    &ink_e2e::alice(),
+                    &mut constructor,
+                )
+                .submit()
+                .await
+                .expect("instantiate failed");
+            let mut call_builder = contract.call_builder::<LockUnlockSmartContract>();
+            let escrow = contract.account_id;
+
+            let mut constructor = Psp22MockRef::new(3 * LOCKED);
+            let token = client
+                .instantiate("psp22_mock", &ink_e2e::alice(), &mut constructor)
+                .submit()
+                .await
+                .expect("token instantiate failed");
+            let mut token_calls = token.call_builder::<Psp22Mock>();
+            let token = token.account_id;
+            let approve = token_calls.approve(escrow, 3 * LOCKED);
+            client
+                .call(&ink_e2e::alice(), &approve)
+                .submit()
+                .await
+                .expect("approve failed");
+            let lock = call_builder.lock_token(token, LOCKED, secret_hash(), None, None, None);
+            let cached = call_builder.token_metadata_of(token);
+            let native = TokenMetadata {
+                decimals: 12,
+                symbol: b"UNIT".to_vec(),
+            };
+            let mock = TokenMetadata {
+                decimals: 6,
+                symbol: b"MOCK".to_vec(),
+            };
+
+            // Without the extension, the lock falls back to the native metadata and
+            // nothing is cached
+            let first = client
+                .call(&ink_e2e::alice(), &lock)
+                .submit()
+                .await
+                .expect("lock failed")
+                .return_value()
+                .expect("lock rejected");
+            let result = client.call(&ink_e2e::alice(), &cached).dry_run().await?;
+            assert_eq!(result.return_value(), None);
 
-            // Try to redeem with an incorrect message
-            let result = contract.redeem(String::from("Wrong message"));
-            assert_eq!(result, Err(Error::IncorrectMessage));
+            // The first lock after the token reports metadata reads and caches it
+            let metadata = token_calls.set_metadata(Some((6, String::from("MOCK"))));
+            client
+                .call(&ink_e2e::alice(), &metadata)
+                .submit()
+                .await
+                .expect("set_metadata failed");
+            let second = client
+                .call(&ink_e2e::alice(), &lock)
+                .submit()
+                .await
+                .expect("lock failed")
+                .return_value()
+                .expect("lock rejected");
+            let result = client.call(&ink_e2e::alice(), &cached).dry_run().await?;
+            assert_eq!(result.return_value(), Some(mock.clone()));
+
+            // Later changes to the token's metadata do not reach the cache or the locks
+            let metadata = token_calls.set_metadata(Some((18, String::from("NEW"))));
+            client
+                .call(&ink_e2e::alice(), &metadata)
+                .submit()
+                .await
+                .expect("set_metadata failed");
+            let third = client
+                .call(&ink_e2e::alice(), &lock)
+                .submit()
+                .await
+                .expect("lock failed")
+                .return_value()
+                .expect("lock rejected");
+            for (lock_id, expected) in [(first, native), (second, mock.clone()), (third, mock)] {
+                let info = call_builder.get_lock_info(lock_id);
+                let result = client.call(&ink_e2e::alice(), &info).dry_run().await?;
+                let info = result.return_value().expect("lock not found");
+                assert_eq!(info.token_metadata, Some(expected));
+            }
+
+            Ok(())
         }
     }
 }